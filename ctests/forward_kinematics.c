@@ -0,0 +1,95 @@
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "bvh_anim/bvh_anim.h"
+
+#define CHECK(cond) \
+    do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            return EXIT_FAILURE; \
+        } \
+    } while (0)
+
+#define CHECK_CLOSE(a, b) CHECK(fabsf((a) - (b)) < 1.0e-4f)
+
+static const char* BVH_STRING =
+    "HIERARCHY\n"
+    "ROOT Base\n"
+    "{\n"
+    "    OFFSET 0.0 0.0 0.0\n"
+    "    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation\n"
+    "    JOINT Tip\n"
+    "    {\n"
+    "        OFFSET 0.0 10.0 0.0\n"
+    "        CHANNELS 3 Zrotation Xrotation Yrotation\n"
+    "        End Site\n"
+    "        {\n"
+    "            OFFSET 0.0 5.0 0.0\n"
+    "        }\n"
+    "    }\n"
+    "}\n"
+    "MOTION\n"
+    "Frames: 2\n"
+    "Frame Time: 0.5\n"
+    "0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0\n"
+    "4.0 0.0 0.0 90.0 0.0 0.0 0.0 0.0 0.0\n";
+
+int main(int argc, const char* argv[]) {
+    (void)argc;
+    (void)argv;
+
+    struct bvh_BvhFile bvh;
+    CHECK(bvh_parse(BVH_STRING, &bvh) != 0);
+
+    struct bvh_Position* positions =
+        (struct bvh_Position*)malloc(sizeof(struct bvh_Position) * bvh.bvh_num_joints);
+    struct bvh_Matrix4* matrices =
+        (struct bvh_Matrix4*)malloc(sizeof(struct bvh_Matrix4) * bvh.bvh_num_joints);
+    CHECK(positions != NULL && matrices != NULL);
+
+    /* The root rotates 90 degrees about z in the second frame, which swings
+     * the tip from +y to -x. */
+    CHECK(bvh_get_world_positions(&bvh, 1, positions, bvh.bvh_num_joints) != 0);
+    CHECK_CLOSE(positions[0].position_x, 4.0f);
+    CHECK_CLOSE(positions[1].position_x, -6.0f);
+    CHECK_CLOSE(positions[1].position_y, 0.0f);
+
+    CHECK(bvh_get_world_matrices(&bvh, 1, matrices, bvh.bvh_num_joints) != 0);
+    for (size_t i = 0; i < bvh.bvh_num_joints; i++) {
+        /* The translation is stored in the last column. */
+        CHECK_CLOSE(matrices[i].matrix_values[12], positions[i].position_x);
+        CHECK_CLOSE(matrices[i].matrix_values[13], positions[i].position_y);
+        CHECK_CLOSE(matrices[i].matrix_values[14], positions[i].position_z);
+        CHECK_CLOSE(matrices[i].matrix_values[15], 1.0f);
+    }
+
+    /* Half way between the frames, the root has moved 2 units and rotated
+     * 45 degrees. */
+    CHECK(bvh_get_world_positions_at_time(&bvh, 0.25, positions, bvh.bvh_num_joints) != 0);
+    CHECK_CLOSE(positions[0].position_x, 2.0f);
+    CHECK_CLOSE(positions[1].position_x, 2.0f - 10.0f * sqrtf(0.5f));
+    CHECK_CLOSE(positions[1].position_y, 10.0f * sqrtf(0.5f));
+
+    CHECK(bvh_get_world_matrices_at_time(&bvh, 100.0, matrices, bvh.bvh_num_joints) != 0);
+    CHECK_CLOSE(matrices[1].matrix_values[12], -6.0f);
+
+    for (size_t i = 0; i < bvh.bvh_num_joints; i++) {
+        printf(
+            "%s: %f %f %f\n",
+            bvh.bvh_joints[i].joint_name,
+            positions[i].position_x,
+            positions[i].position_y,
+            positions[i].position_z);
+    }
+
+    /* Out of bounds frames and undersized arrays are rejected. */
+    CHECK(bvh_get_world_positions(&bvh, bvh.bvh_num_frames, positions, bvh.bvh_num_joints) == 0);
+    CHECK(bvh_get_world_positions(&bvh, 0, positions, bvh.bvh_num_joints - 1) == 0);
+
+    free(matrices);
+    free(positions);
+
+    return (bvh_destroy(&bvh) == 0) ? EXIT_FAILURE : EXIT_SUCCESS;
+}
@@ -0,0 +1,74 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "bvh_anim/bvh_anim.h"
+
+#define CHECK(cond) \
+    do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            return EXIT_FAILURE; \
+        } \
+    } while (0)
+
+static void* counting_alloc(size_t size, size_t align);
+static void counting_free(void* ptr, size_t size, size_t align);
+
+static size_t live_bytes = 0;
+
+static void* counting_alloc(size_t size, size_t align) {
+    (void)align;
+    live_bytes += size;
+    return calloc(1, size);
+}
+
+static void counting_free(void* ptr, size_t size, size_t align) {
+    (void)align;
+    live_bytes -= size;
+    free(ptr);
+}
+
+static const char* BAD_MOTION =
+    "HIERARCHY\n"
+    "ROOT Base\n"
+    "{\n"
+    "    OFFSET 0.0 0.0 0.0\n"
+    "    CHANNELS 3 Xposition Yposition Zposition\n"
+    "}\n"
+    "MOTION\n"
+    "Frames: 1\n"
+    "Frame Time: 0.033333333\n"
+    "0.0 x 0.0\n";
+
+int main(int argc, const char* argv[]) {
+    (void)argc;
+    (void)argv;
+
+    struct bvh_AllocCallbacks allocator = { counting_alloc, counting_free };
+    struct bvh_BvhFile bvh;
+    struct bvh_Error error;
+
+    bvh_clear_last_error();
+    CHECK(bvh_get_last_error(&error, &allocator) == 0);
+
+    CHECK(bvh_parse(BAD_MOTION, &bvh) == 0);
+    CHECK(bvh_get_last_error(&error, &allocator) != 0);
+    CHECK(error.error_category == ERROR_CATEGORY_MOTION);
+    CHECK(error.error_kind == ERROR_KIND_PARSE_MOTION_SECTION);
+    CHECK(error.error_has_line != 0);
+    CHECK(error.error_line == 9);
+    printf("Error on line %zu: %s\n", error.error_line, error.error_message);
+    CHECK(bvh_error_destroy(&error, &allocator) != 0);
+    CHECK(live_bytes == 0);
+
+    CHECK(bvh_read(NULL, &bvh) == 0);
+    CHECK(bvh_get_last_error(&error, &allocator) != 0);
+    CHECK(error.error_category == ERROR_CATEGORY_OTHER);
+    CHECK(error.error_kind == ERROR_KIND_INVALID_ARGUMENT);
+    CHECK(error.error_has_line == 0);
+    printf("Error: %s\n", error.error_message);
+    CHECK(bvh_error_destroy(&error, &allocator) != 0);
+    CHECK(live_bytes == 0);
+
+    return EXIT_SUCCESS;
+}
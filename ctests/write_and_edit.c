@@ -0,0 +1,114 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "bvh_anim/bvh_anim.h"
+
+#define CHECK(cond) \
+    do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            return EXIT_FAILURE; \
+        } \
+    } while (0)
+
+static const char* BVH_STRING =
+    "HIERARCHY\n"
+    "ROOT Base\n"
+    "{\n"
+    "    OFFSET 0.0 0.0 0.0\n"
+    "    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation\n"
+    "    JOINT Middle1\n"
+    "    {\n"
+    "        OFFSET 0.0 0.0 15.0\n"
+    "        CHANNELS 3 Zrotation Xrotation Yrotation\n"
+    "        End Site\n"
+    "        {\n"
+    "            OFFSET 0.0 0.0 30.0\n"
+    "        }\n"
+    "    }\n"
+    "    JOINT Middle2\n"
+    "    {\n"
+    "        OFFSET 0.0 15.0 0.0\n"
+    "        CHANNELS 3 Zrotation Xrotation Yrotation\n"
+    "        End Site\n"
+    "        {\n"
+    "            OFFSET 0.0 30.0 0.0\n"
+    "        }\n"
+    "    }\n"
+    "}\n"
+    "MOTION\n"
+    "Frames: 2\n"
+    "Frame Time: 0.033333333\n"
+    "0.0 1.0 2.0 3.0 4.0 5.0 6.0 7.0 8.0 9.0 10.0 11.0\n"
+    "12.0 13.0 14.0 15.0 16.0 17.0 18.0 19.0 20.0 21.0 22.0 23.0\n";
+
+int main(int argc, const char* argv[]) {
+    (void)argc;
+    (void)argv;
+
+    struct bvh_BvhFile bvh;
+    CHECK(bvh_parse(BVH_STRING, &bvh) != 0);
+
+    /* Joint lookup and child iteration. */
+    size_t root = bvh_find_joint(&bvh, "Base");
+    CHECK(root == 0);
+    CHECK(bvh_find_joint(&bvh, "Missing") == SIZE_MAX);
+
+    size_t num_children = bvh_num_children(&bvh, root);
+    CHECK(num_children == 2);
+    for (size_t i = 0; i < num_children; i++) {
+        size_t child = bvh_get_child(&bvh, root, i);
+        CHECK(child < bvh.bvh_num_joints);
+        CHECK(bvh.bvh_joints[child].joint_parent_index == root);
+        printf("Child %zu of %s: %s\n", i, bvh.bvh_joints[root].joint_name,
+               bvh.bvh_joints[child].joint_name);
+    }
+    CHECK(bvh_get_child(&bvh, root, num_children) == SIZE_MAX);
+
+    /* Editing motion values. */
+    size_t middle2 = bvh_find_joint(&bvh, "Middle2");
+    CHECK(middle2 == 2);
+    size_t channel_index = bvh.bvh_joints[middle2].joint_channels[0].channel_index;
+
+    float value = 0.0f;
+    CHECK(bvh_get_motion(&bvh, 1, channel_index, &value) != 0);
+    CHECK(value == 21.0f);
+
+    CHECK(bvh_set_motion(&bvh, 1, channel_index, 90.0f) != 0);
+    CHECK(bvh_get_motion(&bvh, 1, channel_index, &value) != 0);
+    CHECK(value == 90.0f);
+    CHECK(bvh_get_frame(&bvh, 1)[channel_index] == 90.0f);
+
+    CHECK(bvh_get_motion(&bvh, bvh.bvh_num_frames, 0, &value) == 0);
+    CHECK(bvh_set_motion(&bvh, 0, bvh.bvh_num_channels, 0.0f) == 0);
+
+    /* Writing, then reading the file back. */
+    FILE* out_file = tmpfile();
+    CHECK(out_file != NULL);
+
+    struct bvh_WriteOptions options = bvh_write_options_default();
+    options.indent_style = INDENT_STYLE_SPACES;
+    options.indent_num_spaces = 2;
+    options.line_terminator = LINE_TERMINATOR_UNIX;
+    CHECK(bvh_write_with_options(&bvh, out_file, &options) != 0);
+
+    rewind(out_file);
+    struct bvh_BvhFile reread;
+    int result = bvh_read(out_file, &reread);
+    fclose(out_file);
+    CHECK(result != 0);
+
+    CHECK(reread.bvh_num_joints == bvh.bvh_num_joints);
+    CHECK(reread.bvh_num_frames == bvh.bvh_num_frames);
+    CHECK(reread.bvh_num_channels == bvh.bvh_num_channels);
+    CHECK(bvh_get_motion(&reread, 1, channel_index, &value) != 0);
+    CHECK(value == 90.0f);
+
+    CHECK(bvh_write(&reread, stdout) != 0);
+
+    CHECK(bvh_destroy(&reread) != 0);
+    CHECK(bvh_destroy(&bvh) != 0);
+
+    return EXIT_SUCCESS;
+}
@@ -1,4 +1,4 @@
-use nalgebra::{Matrix4, Point, Point2, Point3, Vector3};
+use nalgebra::{Matrix4, Point, Point2, Point3, UnitQuaternion, Vector2, Vector3};
 
 /// An ArcBall controller, as defined by Ken Shoemake.
 /// See http://www.talisman.org/~erlkonig/misc/shoemake92-arcball.pdf
@@ -8,6 +8,8 @@ pub struct ArcBall {
     prev_mouse_pos: Point2<f32>,
     center: Point3<f32>,
     radius: f32,
+    orientation: UnitQuaternion<f32>,
+    viewport: Vector2<f32>,
 }
 
 impl ArcBall {
@@ -23,20 +25,62 @@ impl ArcBall {
             prev_mouse_pos: Point::origin(),
             center: Point::origin(),
             radius,
+            orientation: UnitQuaternion::identity(),
+            viewport: Vector2::new(1.0, 1.0),
         }
     }
 
+    /// Sets the point around which the camera orbits.
     #[inline]
-    pub fn on_mouse_move(&mut self, delta_mouse_x: f32, delta_mouse_y: f32) {
+    pub fn set_center(&mut self, center: Point3<f32>) {
+        self.center = center;
+    }
+
+    /// Sets the size of the window in which the mouse moves.
+    #[inline]
+    pub fn set_viewport(&mut self, width: f32, height: f32) {
+        self.viewport = Vector2::new(width.max(1.0), height.max(1.0));
+    }
+
+    /// Moves the mouse to the window position `mouse_x`, `mouse_y`. If
+    /// `is_dragging` is `true`, then the camera is rotated by the arc from
+    /// the previous mouse position to the new one.
+    pub fn on_mouse_move(&mut self, mouse_x: f32, mouse_y: f32, is_dragging: bool) {
         self.prev_mouse_pos = self.mouse_pos;
-        self.mouse_pos.coords.x += delta_mouse_x;
-        self.mouse_pos.coords.y += delta_mouse_y;
+        self.mouse_pos = Point2::new(mouse_x, mouse_y);
+
+        if is_dragging {
+            let from = self.project_to_sphere(&self.prev_mouse_pos);
+            let to = self.project_to_sphere(&self.mouse_pos);
+            if let Some(rotation) = UnitQuaternion::rotation_between(&from, &to) {
+                self.orientation = rotation * self.orientation;
+            }
+        }
     }
 
-    pub fn on_scroll(&mut self, amount: f32) {}
+    /// Zooms the camera in for positive `amount`s, and out for negative ones.
+    pub fn on_scroll(&mut self, amount: f32) {
+        self.radius = (self.radius * 0.9f32.powf(amount)).max(1.0e-3);
+    }
 
     pub fn calculate_view_matrix(&self) -> Matrix4<f32> {
-        unimplemented!()
+        Matrix4::new_translation(&Vector3::new(0.0, 0.0, -self.radius))
+            * self.orientation.to_homogeneous()
+            * Matrix4::new_translation(&-self.center.coords)
+    }
+
+    /// Maps a window position onto the unit sphere of the arcball.
+    fn project_to_sphere(&self, pos: &Point2<f32>) -> Vector3<f32> {
+        let x = (2.0 * pos.x - self.viewport.x) / self.viewport.x;
+        let y = (self.viewport.y - 2.0 * pos.y) / self.viewport.y;
+        let length_squared = x * x + y * y;
+
+        if length_squared <= 1.0 {
+            Vector3::new(x, y, (1.0 - length_squared).sqrt())
+        } else {
+            let length = length_squared.sqrt();
+            Vector3::new(x / length, y / length, 0.0)
+        }
     }
 }
 
@@ -46,3 +90,32 @@ impl Default for ArcBall {
         ArcBall::with_radius(1.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ArcBall;
+    use nalgebra::{Point3, Vector4};
+
+    #[test]
+    fn drag_rotates_around_center() {
+        let mut arcball = ArcBall::with_radius(10.0);
+        arcball.set_center(Point3::new(0.0, 5.0, 0.0));
+        arcball.set_viewport(100.0, 100.0);
+
+        let center = Vector4::new(0.0, 5.0, 0.0, 1.0);
+        assert_eq!(
+            arcball.calculate_view_matrix() * center,
+            Vector4::new(0.0, 0.0, -10.0, 1.0)
+        );
+
+        arcball.on_mouse_move(50.0, 50.0, false);
+        arcball.on_mouse_move(90.0, 50.0, true);
+
+        let view = arcball.calculate_view_matrix();
+        let moved = view * center;
+        assert!((moved - Vector4::new(0.0, 0.0, -10.0, 1.0)).norm() < 1.0e-4);
+
+        let side = view * Vector4::new(1.0, 5.0, 0.0, 1.0);
+        assert!(side.z < -10.0 + 1.0e-4);
+    }
+}
@@ -1,195 +1,166 @@
 #![allow(unused)]
 
 mod arcball_camera;
+mod player;
 
 use arcball_camera::ArcBall;
-use bvh_anim::{Bvh, JointData};
+use bvh_anim::Bvh;
 use gl::{self, types::*};
 use glutin::{
-    dpi::LogicalSize, ContextBuilder, DeviceEvent, ElementState, Event, EventsLoop, KeyboardInput,
-    MouseButton, Touch, TouchPhase, VirtualKeyCode, WindowBuilder, WindowEvent,
+    dpi::LogicalSize, Api, ContextBuilder, ElementState, Event, EventsLoop, GlProfile, GlRequest,
+    KeyboardInput, MouseButton, MouseScrollDelta, Touch, TouchPhase, VirtualKeyCode,
+    WindowBuilder, WindowEvent,
+};
+use nalgebra::{Matrix4, Perspective3, Point3};
+use player::{AnimationPlayer, Bone};
+use std::{
+    env,
+    ffi::CStr,
+    fs::File,
+    io::BufReader,
+    mem, process, ptr,
+    time::{Duration, Instant},
 };
-use nalgebra::{Matrix4, Point2, Point3, Vector3};
-use std::time::{Duration, Instant};
-
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-struct Timer {
-    last_update: Instant,
-    interval: Duration,
-}
-
-impl Timer {
-    fn new(interval: Duration) -> Self {
-        Timer {
-            last_update: Instant::now(),
-            interval,
-        }
-    }
-
-    /// If this method returns `true`, then the timer
-    /// fired during this tick.
-    fn tick(&mut self, delta: &Duration) -> bool {
-        self.interval += *delta;
-
-        let curr_time = self.last_update + *delta;
-        let next_update = self.last_update + self.interval;
-
-        if next_update <= curr_time {
-            self.last_update = Instant::now();
-            true
-        } else {
-            false
-        }
-    }
-}
-
-struct AnimationPlayer {
-    bvh: Bvh,
-    timer: Timer,
-    bones: Vec<Bone>,
-    current_frame: usize,
-    does_loop: bool,
-}
-
-impl AnimationPlayer {
-    fn new(bvh: Bvh) -> Self {
-        let frame_time = *bvh.frame_time();
-        AnimationPlayer {
-            bvh,
-            timer: Timer::new(frame_time),
-            bones: vec![],
-            current_frame: 0,
-            does_loop: false,
-        }
-    }
-
-    fn tick(&mut self, delta: &Duration) {
-        if self.timer.tick(delta) {
-            self.anim_callback();
-        }
-    }
-
-    fn calculate_joints_fk(&mut self) {
-        let base_mat = Matrix4::<f32>::identity();
-    }
 
-    fn anim_callback(&mut self) {}
+const USAGE: &str = "usage: bvh_viewer <file.bvh>
+
+controls:
+    space               play/pause
+    left/right          step one frame backward/forward
+    home/end            jump to the first/last frame
+    up/down             double/halve the playback speed
+    r                   reverse the playback direction
+    l                   toggle looping
+    left mouse drag     rotate the camera
+    right mouse drag    scrub through the animation
+    mouse wheel         zoom
+    escape              quit";
+
+macro_rules! c {
+    ($s:literal) => {
+        unsafe { ::std::ffi::CStr::from_bytes_with_nul_unchecked(concat!($s, "\0").as_bytes()) }
+    };
 }
 
+/// The GPU resources used to draw the bones of the skeleton as lines.
 #[derive(Debug, PartialEq)]
-struct Bone {
-    position: Point3<f32>,
-    direction: Vector3<f32>,
-    length: f32,
+struct BoneRenderer {
     shader_program: GLuint,
-    vbo: GLint,
-    vao: GLint,
-    ebo: GLint,
-    model_matrix_uniform_loc: GLint,
-    view_matrix_uniform_loc: GLint,
-    projection_matrix_uniform_loc: GLint,
+    vbo: GLuint,
+    vao: GLuint,
+    view_projection_matrix_uniform_loc: GLint,
+    num_vertices: usize,
 }
 
-impl Bone {
-    fn new(position: Point3<f32>, direction: Vector3<f32>, length: f32) -> Self {
-        macro_rules! c {
-            ($s:literal) => {
-                unsafe {
-                    ::std::ffi::CStr::from_bytes_with_nul_unchecked(concat!($s, "\0").as_bytes())
-                }
-            };
-        }
-
-        let vert_src = c!(r#"#version 330
+impl BoneRenderer {
+    fn new() -> Self {
+        let vert_src = c!(r#"#version 330 core
 
         layout(location = 0) in vec3 position;
 
-        layout(location = 0) out vec4 f_position;
-        layout(location = 1) out vec4 f_color;
-
-        uniform mat4 model_matrix;
-        uniform mat4 view_matrix;
-        uniform mat4 projection_matrix;
+        uniform mat4 view_projection_matrix;
 
         void main() {
-            mat4 mvp = projection_matrix * view_matrix * model_matrix;
-
-            f_position = mvp * vec4(position, 1.0);
-            f_color = vec4(0.7, 0.7, 0.7, 1.0);
-
-            gl_Position = f_position;
+            gl_Position = view_projection_matrix * vec4(position, 1.0);
         }
 
         "#);
 
-        let frag_src = c!(r#"#version 330
-
-        layout(location = 0) in vec4 f_position;
-        layout(location = 1) in vec4 f_color;
+        let frag_src = c!(r#"#version 330 core
 
         layout(location = 0) out vec4 target_color;
 
         void main() {
-            target_color = f_color;
+            target_color = vec4(0.9, 0.9, 0.9, 1.0);
         }
 
         "#);
 
-        let shader_program = 0;
-
-        let model_matrix_uniform_loc =
-            unsafe { gl::GetUniformLocation(shader_program, c!("model_matrix").as_ptr()) };
-
-        let view_matrix_uniform_loc =
-            unsafe { gl::GetUniformLocation(shader_program, c!("view_matrix").as_ptr()) };
+        let shader_program = unsafe {
+            let vert_shader = compile_shader(gl::VERTEX_SHADER, vert_src);
+            let frag_shader = compile_shader(gl::FRAGMENT_SHADER, frag_src);
+            link_program(vert_shader, frag_shader)
+        };
 
-        let projection_matrix_uniform_loc =
-            unsafe { gl::GetUniformLocation(shader_program, c!("projection_matrix").as_ptr()) };
+        let view_projection_matrix_uniform_loc = unsafe {
+            gl::GetUniformLocation(shader_program, c!("view_projection_matrix").as_ptr())
+        };
 
-        let cuboid_verts = &[
-            Vector3::new(0.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(1.0, 1.0, 0.0),
-        ];
-
-        let cuboid_inds = &[0u16];
-
-        let mut buffers = [0, 0];
+        let (mut vao, mut vbo) = (0, 0);
         unsafe {
-            gl::GenBuffers(buffers.len() as _, buffers.as_mut_ptr() as *mut _);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(
+                0,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                (3 * mem::size_of::<f32>()) as GLsizei,
+                ptr::null(),
+            );
+
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
 
-        let [vbo, ebo] = buffers;
-
-        Bone {
-            position,
-            direction,
-            length,
+        BoneRenderer {
             shader_program,
             vbo,
-            vao: 0,
-            ebo,
-            model_matrix_uniform_loc,
-            view_matrix_uniform_loc,
-            projection_matrix_uniform_loc,
+            vao,
+            view_projection_matrix_uniform_loc,
+            num_vertices: 0,
         }
     }
 
-    fn render(&self) {
+    /// Uploads the line vertices of `bones` to the GPU.
+    fn update(&mut self, bones: &[Bone]) {
+        let vertices = bones
+            .iter()
+            .flat_map(|b| vec![b.start.x, b.start.y, b.start.z, b.end.x, b.end.y, b.end.z])
+            .collect::<Vec<f32>>();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        self.num_vertices = vertices.len() / 3;
+    }
+
+    fn render(&self, view_projection_matrix: &Matrix4<f32>) {
         unsafe {
             gl::UseProgram(self.shader_program);
+            gl::UniformMatrix4fv(
+                self.view_projection_matrix_uniform_loc,
+                1,
+                gl::FALSE,
+                view_projection_matrix.as_slice().as_ptr(),
+            );
+
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::LINES, 0, self.num_vertices as GLsizei);
+            gl::BindVertexArray(0);
 
             gl::UseProgram(0);
         }
     }
 }
 
-impl Drop for Bone {
+impl Drop for BoneRenderer {
     fn drop(&mut self) {
         unsafe {
-            let mut buffers = [self.vbo, self.ebo];
-            gl::DeleteBuffers(buffers.len() as _, buffers.as_mut_ptr() as *mut _);
-            gl::DeleteVertexArrays(1, (&mut self.vao) as *mut _ as *mut _);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
 
             gl::UseProgram(0);
             gl::DeleteProgram(self.shader_program);
@@ -197,13 +168,110 @@ impl Drop for Bone {
     }
 }
 
+unsafe fn compile_shader(kind: GLenum, src: &CStr) -> GLuint {
+    let shader = gl::CreateShader(kind);
+    gl::ShaderSource(shader, 1, &src.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut status = GLint::from(gl::FALSE);
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status != GLint::from(gl::TRUE) {
+        let mut log = vec![0u8; 1024];
+        let mut len = 0;
+        gl::GetShaderInfoLog(shader, log.len() as _, &mut len, log.as_mut_ptr() as *mut _);
+        log.truncate(len as usize);
+        panic!("Could not compile shader: {}", String::from_utf8_lossy(&log));
+    }
+
+    shader
+}
+
+unsafe fn link_program(vert_shader: GLuint, frag_shader: GLuint) -> GLuint {
+    let program = gl::CreateProgram();
+    gl::AttachShader(program, vert_shader);
+    gl::AttachShader(program, frag_shader);
+    gl::LinkProgram(program);
+
+    gl::DetachShader(program, vert_shader);
+    gl::DetachShader(program, frag_shader);
+    gl::DeleteShader(vert_shader);
+    gl::DeleteShader(frag_shader);
+
+    let mut status = GLint::from(gl::FALSE);
+    gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+    if status != GLint::from(gl::TRUE) {
+        let mut log = vec![0u8; 1024];
+        let mut len = 0;
+        gl::GetProgramInfoLog(program, log.len() as _, &mut len, log.as_mut_ptr() as *mut _);
+        log.truncate(len as usize);
+        panic!("Could not link shader program: {}", String::from_utf8_lossy(&log));
+    }
+
+    program
+}
+
+fn load_bvh() -> Bvh {
+    let path = match env::args_os().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let file = File::open(&path).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", path.to_string_lossy(), e);
+        process::exit(1);
+    });
+
+    bvh_anim::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+        eprintln!("Could not load {}: {}", path.to_string_lossy(), e);
+        process::exit(1);
+    })
+}
+
+/// Returns the center and the size of the box which contains all of `bones`.
+fn bones_bounds(bones: &[Bone]) -> (Point3<f32>, f32) {
+    let mut points = bones.iter().flat_map(|b| vec![b.start, b.end]);
+    let first = match points.next() {
+        Some(p) => p,
+        None => return (Point3::origin(), 1.0),
+    };
+
+    let (min, max) = points.fold((first, first), |(min, max), p| {
+        (
+            Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+            Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+        )
+    });
+
+    (nalgebra::center(&min, &max), (max - min).norm().max(1.0))
+}
+
+fn window_title(player: &AnimationPlayer) -> String {
+    format!(
+        "Bvh Viewer - frame {}/{} - {}{} - speed {}x",
+        player.current_frame() + 1,
+        player.num_frames(),
+        if player.is_playing() { "playing" } else { "paused" },
+        if player.does_loop() { " (looping)" } else { "" },
+        player.speed()
+    )
+}
+
 fn main() {
+    let mut player = AnimationPlayer::new(load_bvh());
+    player.set_looping(true);
+
     let mut events_loop = EventsLoop::new();
     let win_builder = WindowBuilder::new()
-        .with_title("Bvh Viewer")
+        .with_title(window_title(&player))
         .with_dimensions(LogicalSize::new(1024.0, 768.0));
 
     let context = ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+        .with_gl_profile(GlProfile::Core)
+        .with_depth_buffer(24)
         .build_windowed(win_builder, &events_loop)
         .expect("Could not create OpenGL Context");
 
@@ -217,75 +285,148 @@ fn main() {
         gl::load_with(|s| context.get_proc_address(s) as _);
 
         gl::ClearColor(0.1, 0.0, 0.4, 1.0);
+        gl::Enable(gl::DEPTH_TEST);
     }
 
+    let mut window_size = context
+        .window()
+        .get_inner_size()
+        .unwrap_or_else(|| LogicalSize::new(1024.0, 768.0));
+
+    let (center, size) = bones_bounds(player.bones());
+    let mut arcball = ArcBall::with_radius(size * 1.5);
+    arcball.set_center(center);
+    arcball.set_viewport(window_size.width as f32, window_size.height as f32);
+
+    let mut bone_renderer = BoneRenderer::new();
+    bone_renderer.update(player.bones());
+
     let mut is_running = true;
     let mut prev_time = Instant::now();
-    let mut arcball = ArcBall::new();
     let mut is_mouse_down = false;
-    let mut prev_touch_pos = Point2::<f32>::origin();
-    let mut loaded_skeletons: Vec<Bvh> = vec![];
+    let mut is_scrubbing = false;
+    let mut cursor_x = 0.0f32;
+    let mut title = window_title(&player);
 
     while is_running {
         let curr_time = Instant::now();
         let dt = curr_time - prev_time;
         prev_time = curr_time;
 
-        events_loop.poll_events(|ev| match ev {
-            Event::DeviceEvent { ref event, .. } => match event {
-                DeviceEvent::Key(KeyboardInput {
-                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                    ..
-                }) => {
-                    is_running = false;
-                }
-                DeviceEvent::MouseMotion {
-                    delta: (ref mx, ref my),
-                }
-                    if is_mouse_down =>
-                {
-                    arcball.on_mouse_move(*mx as f32, *my as f32);
-                }
-                _ => {}
-            },
-            Event::WindowEvent { ref event, .. } => match event {
+        let prev_frame = player.current_frame();
+
+        events_loop.poll_events(|ev| {
+            let event = match ev {
+                Event::WindowEvent { event, .. } => event,
+                _ => return,
+            };
+
+            match event {
                 WindowEvent::CloseRequested | WindowEvent::Destroyed => {
                     is_running = false;
                 }
-                WindowEvent::MouseInput {
-                    ref state,
-                    button: MouseButton::Left,
+                WindowEvent::Resized(size) => {
+                    window_size = size;
+                    let dpi_factor = context.window().get_hidpi_factor();
+                    let physical_size = size.to_physical(dpi_factor);
+                    context.resize(physical_size);
+                    unsafe {
+                        gl::Viewport(
+                            0,
+                            0,
+                            physical_size.width as GLsizei,
+                            physical_size.height as GLsizei,
+                        );
+                    }
+                    arcball.set_viewport(size.width as f32, size.height as f32);
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
                     ..
-                } => match state {
-                    ElementState::Pressed => is_mouse_down = true,
-                    ElementState::Released => is_mouse_down = false,
+                } => match key {
+                    VirtualKeyCode::Escape => is_running = false,
+                    VirtualKeyCode::Space => player.toggle_play(),
+                    VirtualKeyCode::Right => player.step_forward(),
+                    VirtualKeyCode::Left => player.step_backward(),
+                    VirtualKeyCode::Home => player.scrub_to_frame(0),
+                    VirtualKeyCode::End => player.scrub_to_frame(player.num_frames()),
+                    VirtualKeyCode::Up => player.set_speed(player.speed() * 2.0),
+                    VirtualKeyCode::Down => player.set_speed(player.speed() * 0.5),
+                    VirtualKeyCode::R => player.set_speed(-player.speed()),
+                    VirtualKeyCode::L => {
+                        let does_loop = !player.does_loop();
+                        player.set_looping(does_loop);
+                    }
+                    _ => {}
                 },
-                WindowEvent::Touch(ref t) => {
-                    match t.phase {
-                        TouchPhase::Started => {
-                            is_mouse_down = true;
-                            // Set the last touch position to the t.position
-                        }
-                        TouchPhase::Ended | TouchPhase::Cancelled => is_mouse_down = true,
-                        TouchPhase::Moved => {
-                            // Get the touch position
-                            // Calculate the touch delta
-                            // Modify the arcball using the delta info
-                            // Set the last touch position to the current touch position.
+                WindowEvent::CursorMoved { position, .. } => {
+                    cursor_x = position.x as f32;
+                    if is_scrubbing {
+                        player.scrub_to_fraction(cursor_x / window_size.width as f32);
+                    }
+                    arcball.on_mouse_move(position.x as f32, position.y as f32, is_mouse_down);
+                }
+                WindowEvent::MouseInput { state, button, .. } => {
+                    let is_pressed = state == ElementState::Pressed;
+                    match button {
+                        MouseButton::Left => is_mouse_down = is_pressed,
+                        MouseButton::Right => {
+                            is_scrubbing = is_pressed;
+                            if is_scrubbing {
+                                player.pause();
+                                player.scrub_to_fraction(cursor_x / window_size.width as f32);
+                            }
                         }
+                        _ => {}
                     }
                 }
+                WindowEvent::MouseWheel { delta, .. } => match delta {
+                    MouseScrollDelta::LineDelta(_, y) => arcball.on_scroll(y),
+                    MouseScrollDelta::PixelDelta(p) => arcball.on_scroll(p.y as f32 / 20.0),
+                },
+                WindowEvent::Touch(Touch {
+                    phase, location, ..
+                }) => match phase {
+                    TouchPhase::Started => {
+                        is_mouse_down = true;
+                        arcball.on_mouse_move(location.x as f32, location.y as f32, false);
+                    }
+                    TouchPhase::Moved => {
+                        arcball.on_mouse_move(location.x as f32, location.y as f32, true);
+                    }
+                    TouchPhase::Ended | TouchPhase::Cancelled => is_mouse_down = false,
+                },
                 _ => {}
-            },
-            _ => {}
+            }
         });
 
+        player.tick(&dt);
+
+        if player.current_frame() != prev_frame {
+            bone_renderer.update(player.bones());
+        }
+
+        let new_title = window_title(&player);
+        if new_title != title {
+            context.window().set_title(&new_title);
+            title = new_title;
+        }
+
+        let aspect = (window_size.width / window_size.height.max(1.0)) as f32;
+        let projection =
+            Perspective3::new(aspect, 45.0f32.to_radians(), size * 0.01, size * 100.0);
+        let view_projection = projection.as_matrix() * arcball.calculate_view_matrix();
+
         unsafe {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
-        // Tick the skeleton using the delta time
-        // Render the skeleton.
+        bone_renderer.render(&view_projection);
 
         context.swap_buffers().expect("Could not swap buffers");
     }
@@ -0,0 +1,350 @@
+use bvh_anim::Bvh;
+use mint::ColumnMatrix4;
+use nalgebra::{Matrix4, Point3, Vector4};
+use std::time::Duration;
+
+/// A bone of the skeleton, drawn as a line between two points in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bone {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+}
+
+/// Playback state of an animation. This type does not touch any graphics
+/// resources, so it can be driven and inspected without a window.
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    bvh: Bvh,
+    bones: Vec<Bone>,
+    /// Time since the start of the clip, in seconds.
+    time: f64,
+    current_frame: usize,
+    is_playing: bool,
+    does_loop: bool,
+    speed: f64,
+}
+
+impl AnimationPlayer {
+    pub fn new(bvh: Bvh) -> Self {
+        let mut player = AnimationPlayer {
+            bvh,
+            bones: vec![],
+            time: 0.0,
+            current_frame: 0,
+            is_playing: true,
+            does_loop: false,
+            speed: 1.0,
+        };
+        player.calculate_joints_fk();
+        player
+    }
+
+    #[inline]
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    #[inline]
+    pub fn bones(&self) -> &[Bone] {
+        &self.bones[..]
+    }
+
+    #[inline]
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    #[inline]
+    pub fn num_frames(&self) -> usize {
+        self.bvh.num_frames()
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    #[inline]
+    pub fn play(&mut self) {
+        self.is_playing = true;
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.is_playing = false;
+    }
+
+    #[inline]
+    pub fn toggle_play(&mut self) {
+        self.is_playing = !self.is_playing;
+    }
+
+    #[inline]
+    pub fn does_loop(&self) -> bool {
+        self.does_loop
+    }
+
+    #[inline]
+    pub fn set_looping(&mut self, does_loop: bool) {
+        self.does_loop = does_loop;
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Sets the playback speed. Negative speeds play the clip backwards.
+    #[inline]
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// The length of the clip in seconds.
+    #[inline]
+    pub fn duration(&self) -> f64 {
+        self.num_frames() as f64 * self.frame_time()
+    }
+
+    /// Advances the animation by `delta` of wall clock time.
+    pub fn tick(&mut self, delta: &Duration) {
+        if !self.is_playing || self.num_frames() == 0 {
+            return;
+        }
+
+        let duration = self.duration();
+        let mut time = self.time + delta.as_secs_f64() * self.speed;
+
+        if time >= duration || time < 0.0 {
+            if self.does_loop && duration > 0.0 {
+                time = time.rem_euclid(duration);
+            } else {
+                time = time.clamp(0.0, self.last_frame_time());
+                self.is_playing = false;
+            }
+        }
+
+        self.set_time(time);
+    }
+
+    /// Pauses playback and moves forward by a single frame.
+    pub fn step_forward(&mut self) {
+        self.pause();
+        let next = self.current_frame + 1;
+        if next < self.num_frames() {
+            self.scrub_to_frame(next);
+        } else if self.does_loop {
+            self.scrub_to_frame(0);
+        }
+    }
+
+    /// Pauses playback and moves backward by a single frame.
+    pub fn step_backward(&mut self) {
+        self.pause();
+        if self.current_frame > 0 {
+            self.scrub_to_frame(self.current_frame - 1);
+        } else if self.does_loop {
+            self.scrub_to_frame(self.num_frames().saturating_sub(1));
+        }
+    }
+
+    /// Jumps to `frame`, which is clamped to the frames of the clip.
+    pub fn scrub_to_frame(&mut self, frame: usize) {
+        let frame = frame.min(self.num_frames().saturating_sub(1));
+        self.set_time(frame as f64 * self.frame_time());
+    }
+
+    /// Jumps to the frame at `fraction` of the way through the clip, where
+    /// `0.0` is the first frame and `1.0` is the last frame.
+    pub fn scrub_to_fraction(&mut self, fraction: f32) {
+        let last = self.num_frames().saturating_sub(1);
+        let fraction = f64::from(fraction.clamp(0.0, 1.0));
+        self.scrub_to_frame((fraction * last as f64).round() as usize);
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.time = time;
+
+        let frame_time = self.frame_time();
+        let frame = if frame_time > 0.0 {
+            // Guard against `time` landing just below a frame boundary.
+            ((time + 1.0e-9) / frame_time).floor() as usize
+        } else {
+            0
+        };
+        let frame = frame.min(self.num_frames().saturating_sub(1));
+
+        if frame != self.current_frame {
+            self.current_frame = frame;
+            self.anim_callback();
+        }
+    }
+
+    #[inline]
+    fn frame_time(&self) -> f64 {
+        self.bvh.frame_time().as_secs_f64()
+    }
+
+    #[inline]
+    fn last_frame_time(&self) -> f64 {
+        self.num_frames().saturating_sub(1) as f64 * self.frame_time()
+    }
+
+    /// Recalculates the bones of the skeleton at the current frame.
+    fn calculate_joints_fk(&mut self) {
+        self.bones.clear();
+
+        let transforms = match self.bvh.world_transforms(self.current_frame) {
+            Some(transforms) => transforms,
+            None => return,
+        };
+
+        let transforms: Vec<Matrix4<f32>> = transforms.iter().map(to_matrix).collect();
+        let position = |m: &Matrix4<f32>| Point3::from_homogeneous(m.column(3).into_owned());
+
+        for (joint, transform) in self.bvh.joints().zip(&transforms) {
+            let joint = joint.data();
+            let start = position(transform).unwrap_or_else(Point3::origin);
+
+            if let Some(parent) = joint.parent_index() {
+                if let Some(parent_start) = position(&transforms[parent]) {
+                    self.bones.push(Bone {
+                        start: parent_start,
+                        end: start,
+                    });
+                }
+            }
+
+            if let Some(end_site) = joint.end_site() {
+                let end = transform * Vector4::new(end_site.x, end_site.y, end_site.z, 1.0);
+                if let Some(end) = Point3::from_homogeneous(end) {
+                    self.bones.push(Bone { start, end });
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn anim_callback(&mut self) {
+        self.calculate_joints_fk();
+    }
+}
+
+#[inline]
+fn to_matrix(m: &ColumnMatrix4<f32>) -> Matrix4<f32> {
+    Matrix4::from_columns(&[
+        Vector4::new(m.x.x, m.x.y, m.x.z, m.x.w),
+        Vector4::new(m.y.x, m.y.y, m.y.z, m.y.w),
+        Vector4::new(m.z.x, m.z.y, m.z.z, m.z.w),
+        Vector4::new(m.w.x, m.w.y, m.w.z, m.w.w),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnimationPlayer;
+    use bvh_anim::bvh;
+    use nalgebra::Point3;
+    use std::time::Duration;
+
+    fn player() -> AnimationPlayer {
+        AnimationPlayer::new(bvh! {
+            HIERARCHY
+            ROOT Base
+            {
+                OFFSET 0.0 0.0 0.0
+                CHANNELS 3 Xposition Yposition Zposition
+                JOINT Tip
+                {
+                    OFFSET 0.0 1.0 0.0
+                    CHANNELS 0
+                    End Site
+                    {
+                        OFFSET 0.0 1.0 0.0
+                    }
+                }
+            }
+            MOTION
+            Frames: 4
+            Frame Time: 0.1
+            0.0 0.0 0.0
+            1.0 0.0 0.0
+            2.0 0.0 0.0
+            3.0 0.0 0.0
+        })
+    }
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn bones_follow_the_current_frame() {
+        let mut player = player();
+        assert_eq!(player.bones().len(), 2);
+        assert_eq!(player.bones()[0].start, Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(player.bones()[1].end, Point3::new(0.0, 2.0, 0.0));
+
+        player.scrub_to_frame(2);
+        assert_eq!(player.bones()[0].start, Point3::new(2.0, 0.0, 0.0));
+        assert_eq!(player.bones()[1].end, Point3::new(2.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn play_stops_at_the_end() {
+        let mut player = player();
+        player.tick(&millis(150));
+        assert_eq!(player.current_frame(), 1);
+
+        player.tick(&millis(1000));
+        assert_eq!(player.current_frame(), 3);
+        assert!(!player.is_playing());
+    }
+
+    #[test]
+    fn play_loops() {
+        let mut player = player();
+        player.set_looping(true);
+        player.tick(&millis(450));
+        assert_eq!(player.current_frame(), 0);
+        assert!(player.is_playing());
+
+        player.set_speed(-1.0);
+        player.tick(&millis(100));
+        assert_eq!(player.current_frame(), 3);
+    }
+
+    #[test]
+    fn pause_and_speed() {
+        let mut player = player();
+        player.pause();
+        player.tick(&millis(200));
+        assert_eq!(player.current_frame(), 0);
+
+        player.play();
+        player.set_speed(2.0);
+        player.tick(&millis(100));
+        assert_eq!(player.current_frame(), 2);
+    }
+
+    #[test]
+    fn step_and_scrub() {
+        let mut player = player();
+        player.step_forward();
+        assert!(!player.is_playing());
+        assert_eq!(player.current_frame(), 1);
+
+        player.step_backward();
+        player.step_backward();
+        assert_eq!(player.current_frame(), 0);
+
+        player.set_looping(true);
+        player.step_backward();
+        assert_eq!(player.current_frame(), 3);
+
+        player.scrub_to_fraction(0.5);
+        assert_eq!(player.current_frame(), 2);
+        player.scrub_to_frame(10);
+        assert_eq!(player.current_frame(), 3);
+    }
+}
@@ -0,0 +1,489 @@
+//! Python bindings for the `bvh_anim` crate.
+//!
+//! The extension module is called `bvh_anim`, and can be built and installed
+//! with [`maturin`](https://github.com/PyO3/maturin):
+//!
+//! ```text
+//! cd python
+//! maturin develop --release
+//! ```
+//!
+//! ```python
+//! import bvh_anim
+//!
+//! bvh = bvh_anim.load("./data/test_mocapbank.bvh")
+//! print(bvh.root.name, [child.name for child in bvh.root.children])
+//!
+//! motion = bvh.motion                # float32 array of (num_frames, num_channels)
+//! positions = bvh.world_positions()  # float32 array of (num_frames, num_joints, 3)
+//!
+//! bvh.save("./out.bvh", indent=4, line_terminator="unix")
+//! ```
+
+use bvh_anim::{
+    fraction_seconds_to_duration,
+    write::{IndentStyle, LineTerminator, WriteOptions},
+    Bvh, Joint, JointData,
+};
+use numpy::{
+    ndarray::{Array2, Array3},
+    IntoPyArray, PyArray2, PyArray3, PyReadonlyArray2,
+};
+use pyo3::{
+    exceptions::{PyIOError, PyIndexError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+/// A bvh file, containing a skeleton and its motion.
+#[pyclass(name = "Bvh", module = "bvh_anim")]
+struct PyBvh {
+    bvh: Bvh,
+}
+
+/// A single joint of the skeleton of a `Bvh`.
+///
+/// A `Joint` keeps the `Bvh` it belongs to alive.
+#[pyclass(name = "Joint", module = "bvh_anim", frozen)]
+struct PyJoint {
+    bvh: Py<PyBvh>,
+    index: usize,
+}
+
+/// The value of the `indent` parameter: either a number of spaces, or one of
+/// `"tabs"` or `"none"`.
+#[derive(FromPyObject)]
+enum IndentArg {
+    Spaces(usize),
+    Style(String),
+}
+
+/// Load the bvh file at `path`.
+#[pyfunction]
+fn load(path: &str) -> PyResult<PyBvh> {
+    let file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    Bvh::from_reader(BufReader::new(file))
+        .map(|bvh| PyBvh { bvh })
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Parse `data`, which may be a `str` or `bytes`, as a bvh file.
+#[pyfunction]
+fn loads(data: &Bound<'_, PyAny>) -> PyResult<PyBvh> {
+    let bytes: Vec<u8> = match data.extract::<String>() {
+        Ok(string) => string.into_bytes(),
+        Err(_) => data.extract::<Vec<u8>>()?,
+    };
+
+    Bvh::from_bytes(&bytes)
+        .map(|bvh| PyBvh { bvh })
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pymethods]
+impl PyBvh {
+    /// The number of frames of motion.
+    #[getter]
+    fn num_frames(&self) -> usize {
+        self.bvh.num_frames()
+    }
+
+    /// The number of channels in each frame of motion.
+    #[getter]
+    fn num_channels(&self) -> usize {
+        self.bvh.num_channels()
+    }
+
+    /// The number of joints in the skeleton.
+    #[getter]
+    fn num_joints(&self) -> usize {
+        self.bvh.joints().count()
+    }
+
+    /// The time of each frame, in seconds.
+    #[getter]
+    fn frame_time(&self) -> f64 {
+        self.bvh.frame_time().as_secs_f64()
+    }
+
+    #[setter]
+    fn set_frame_time(&mut self, frame_time: f64) -> PyResult<()> {
+        if !frame_time.is_finite() || frame_time < 0.0 {
+            return Err(PyValueError::new_err(
+                "The frame time must be a positive number of seconds",
+            ));
+        }
+        self.bvh
+            .set_frame_time(fraction_seconds_to_duration(frame_time));
+        Ok(())
+    }
+
+    /// The root joint of the skeleton, or `None` if there are no joints.
+    #[getter]
+    fn root(slf: &Bound<'_, Self>) -> Option<PyJoint> {
+        slf.borrow()
+            .bvh
+            .root_joint()
+            .map(|joint| PyJoint::new(slf, &joint))
+    }
+
+    /// All of the joints of the skeleton, in the order in which they appear
+    /// in the file.
+    #[getter]
+    fn joints(slf: &Bound<'_, Self>) -> Vec<PyJoint> {
+        slf.borrow()
+            .bvh
+            .joints()
+            .map(|joint| PyJoint::new(slf, &joint))
+            .collect()
+    }
+
+    /// Find the joint called `name`, or `None` if there is no such joint.
+    fn find_joint(slf: &Bound<'_, Self>, name: &str) -> Option<PyJoint> {
+        slf.borrow()
+            .bvh
+            .joints()
+            .find_by_name(name)
+            .map(|joint| PyJoint::new(slf, &joint))
+    }
+
+    /// A copy of the motion values, as a `float32` array with the shape
+    /// `(num_frames, num_channels)`.
+    ///
+    /// Assigning an array with the same shape replaces the motion values.
+    #[getter]
+    fn motion<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f32>> {
+        let values = self
+            .bvh
+            .frames()
+            .flat_map(|frame| frame.as_slice().to_vec())
+            .collect::<Vec<_>>();
+        Array2::from_shape_vec((self.bvh.num_frames(), self.bvh.num_channels()), values)
+            .expect("The motion values should match the number of frames and channels")
+            .into_pyarray(py)
+    }
+
+    #[setter]
+    fn set_motion(&mut self, motion: PyReadonlyArray2<'_, f32>) -> PyResult<()> {
+        let motion = motion.as_array();
+        let expected = (self.bvh.num_frames(), self.bvh.num_channels());
+        if motion.dim() != expected {
+            return Err(PyValueError::new_err(format!(
+                "Expected an array with the shape {:?}, but got {:?}",
+                expected,
+                motion.dim()
+            )));
+        }
+
+        for (frame, row) in self.bvh.frames_mut().zip(motion.outer_iter()) {
+            for (value, &new_value) in frame.as_mut_slice().iter_mut().zip(row.iter()) {
+                *value = new_value;
+            }
+        }
+        Ok(())
+    }
+
+    /// The world-space position of each joint as a `float32` array.
+    ///
+    /// If `frame` is given, the array has the shape `(num_joints, 3)`.
+    /// Otherwise, the positions of every frame are returned in an array
+    /// with the shape `(num_frames, num_joints, 3)`.
+    #[pyo3(signature = (frame=None))]
+    fn world_positions<'py>(
+        &self,
+        py: Python<'py>,
+        frame: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let num_joints = self.bvh.joints().count();
+        match frame {
+            Some(frame) => {
+                let positions = self.bvh.world_positions(frame).ok_or_else(|| {
+                    PyIndexError::new_err(format!("Frame {} is out of bounds", frame))
+                })?;
+                Ok(positions_to_array(&positions, num_joints)
+                    .into_pyarray(py)
+                    .into_any())
+            }
+            None => {
+                let mut all = Array3::zeros((self.bvh.num_frames(), num_joints, 3));
+                for (frame, positions) in self.bvh.frames_world_positions().enumerate() {
+                    all.index_axis_mut(numpy::ndarray::Axis(0), frame)
+                        .assign(&positions_to_array(&positions, num_joints));
+                }
+                let all: Bound<'py, PyArray3<f32>> = all.into_pyarray(py);
+                Ok(all.into_any())
+            }
+        }
+    }
+
+    /// The world-space position of each joint at `time` seconds from the
+    /// start of the animation, interpolating between frames, as a `float32`
+    /// array with the shape `(num_joints, 3)`.
+    fn world_positions_at_time<'py>(
+        &self,
+        py: Python<'py>,
+        time: f64,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let time = fraction_seconds_to_duration(time.max(0.0));
+        let positions = self
+            .bvh
+            .world_positions_at_time(time)
+            .ok_or_else(|| PyValueError::new_err("The bvh has no frames"))?;
+        Ok(positions_to_array(&positions, self.bvh.joints().count()).into_pyarray(py))
+    }
+
+    /// Format the bvh file as `bytes`.
+    ///
+    /// * `indent` may be a number of spaces, `"tabs"` or `"none"`.
+    /// * `line_terminator` may be `"unix"` or `"windows"`. Defaults to the
+    ///   native line terminator.
+    #[pyo3(signature = (
+        *,
+        indent=None,
+        line_terminator=None,
+        offset_significant_figures=None,
+        frame_time_significant_figures=None,
+        motion_values_significant_figures=None,
+    ))]
+    fn dumps<'py>(
+        &self,
+        py: Python<'py>,
+        indent: Option<IndentArg>,
+        line_terminator: Option<&str>,
+        offset_significant_figures: Option<usize>,
+        frame_time_significant_figures: Option<usize>,
+        motion_values_significant_figures: Option<usize>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let options = write_options(
+            indent,
+            line_terminator,
+            offset_significant_figures,
+            frame_time_significant_figures,
+            motion_values_significant_figures,
+        )?;
+        Ok(PyBytes::new(py, &options.write_to_string(&self.bvh)))
+    }
+
+    /// Write the bvh file to `path`. Takes the same formatting options as
+    /// `dumps`.
+    #[pyo3(signature = (
+        path,
+        *,
+        indent=None,
+        line_terminator=None,
+        offset_significant_figures=None,
+        frame_time_significant_figures=None,
+        motion_values_significant_figures=None,
+    ))]
+    fn save(
+        &self,
+        path: &str,
+        indent: Option<IndentArg>,
+        line_terminator: Option<&str>,
+        offset_significant_figures: Option<usize>,
+        frame_time_significant_figures: Option<usize>,
+        motion_values_significant_figures: Option<usize>,
+    ) -> PyResult<()> {
+        let options = write_options(
+            indent,
+            line_terminator,
+            offset_significant_figures,
+            frame_time_significant_figures,
+            motion_values_significant_figures,
+        )?;
+        let file = File::create(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        options
+            .write(&self.bvh, &mut BufWriter::new(file))
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<bvh_anim.Bvh joints={} frames={} channels={}>",
+            self.bvh.joints().count(),
+            self.bvh.num_frames(),
+            self.bvh.num_channels()
+        )
+    }
+}
+
+impl PyJoint {
+    fn new(bvh: &Bound<'_, PyBvh>, joint: &Joint<'_>) -> Self {
+        PyJoint {
+            bvh: bvh.clone().unbind(),
+            index: joint.data().index(),
+        }
+    }
+
+    /// Call `f` with the `Joint` which `self` refers to.
+    fn with_joint<T, F>(&self, py: Python<'_>, f: F) -> T
+    where
+        F: FnOnce(&Bound<'_, PyBvh>, &Joint<'_>) -> T,
+    {
+        let bvh = self.bvh.bind(py);
+        let borrowed = bvh.borrow();
+        let joint = borrowed
+            .bvh
+            .joints()
+            .nth(self.index)
+            .expect("The joint index should be valid");
+        f(bvh, &joint)
+    }
+
+    fn with_data<T, F>(&self, py: Python<'_>, f: F) -> T
+    where
+        F: FnOnce(&JointData) -> T,
+    {
+        self.with_joint(py, |_, joint| f(joint.data()))
+    }
+}
+
+#[pymethods]
+impl PyJoint {
+    /// The name of the joint.
+    #[getter]
+    fn name(&self, py: Python<'_>) -> String {
+        self.with_data(py, |data| data.name().to_string())
+    }
+
+    /// The index of the joint in `Bvh.joints`.
+    #[getter]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The depth of the joint, where the root joint has a depth of `0`.
+    #[getter]
+    fn depth(&self, py: Python<'_>) -> usize {
+        self.with_data(py, JointData::depth)
+    }
+
+    /// The offset of the joint from its parent, as an `(x, y, z)` tuple.
+    #[getter]
+    fn offset(&self, py: Python<'_>) -> (f32, f32, f32) {
+        self.with_data(py, |data| {
+            let offset = data.offset();
+            (offset.x, offset.y, offset.z)
+        })
+    }
+
+    /// The end site of the joint as an `(x, y, z)` tuple, or `None` if it
+    /// does not have one.
+    #[getter]
+    fn end_site(&self, py: Python<'_>) -> Option<(f32, f32, f32)> {
+        self.with_data(py, |data| data.end_site().map(|e| (e.x, e.y, e.z)))
+    }
+
+    /// The channels of the joint, as a list of `(channel_type, motion_index)`
+    /// tuples, where `motion_index` is the column of the channel in
+    /// `Bvh.motion`.
+    #[getter]
+    fn channels(&self, py: Python<'_>) -> Vec<(&'static str, usize)> {
+        self.with_data(py, |data| {
+            data.channels()
+                .iter()
+                .map(|c| (c.channel_type().as_str(), c.motion_index()))
+                .collect()
+        })
+    }
+
+    /// The parent of the joint, or `None` if this is the root joint.
+    #[getter]
+    fn parent(&self, py: Python<'_>) -> Option<PyJoint> {
+        self.with_joint(py, |bvh, joint| {
+            joint.parent().map(|parent| PyJoint::new(bvh, &parent))
+        })
+    }
+
+    /// The direct children of the joint.
+    #[getter]
+    fn children(&self, py: Python<'_>) -> Vec<PyJoint> {
+        self.with_joint(py, |bvh, joint| {
+            joint
+                .children()
+                .map(|child| PyJoint::new(bvh, &child))
+                .collect()
+        })
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("<bvh_anim.Joint {:?} index={}>", self.name(py), self.index)
+    }
+}
+
+fn write_options(
+    indent: Option<IndentArg>,
+    line_terminator: Option<&str>,
+    offset_significant_figures: Option<usize>,
+    frame_time_significant_figures: Option<usize>,
+    motion_values_significant_figures: Option<usize>,
+) -> PyResult<WriteOptions> {
+    let mut options = WriteOptions::new();
+
+    if let Some(indent) = indent {
+        let indent = match indent {
+            IndentArg::Spaces(n) => IndentStyle::with_spaces(n),
+            IndentArg::Style(ref style) if style == "tabs" => IndentStyle::Tabs,
+            IndentArg::Style(ref style) if style == "none" => IndentStyle::NoIndentation,
+            IndentArg::Style(style) => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown indent style {:?}, expected a number of spaces, \"tabs\" or \"none\"",
+                    style
+                )));
+            }
+        };
+        options = options.with_indent(indent);
+    }
+
+    if let Some(line_terminator) = line_terminator {
+        let line_terminator = match line_terminator {
+            "unix" => LineTerminator::Unix,
+            "windows" => LineTerminator::Windows,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown line terminator {:?}, expected \"unix\" or \"windows\"",
+                    line_terminator
+                )));
+            }
+        };
+        options = options.with_line_terminator(line_terminator);
+    }
+
+    if let Some(figures) = offset_significant_figures {
+        options = options.with_offset_significant_figures(figures);
+    }
+    if let Some(figures) = frame_time_significant_figures {
+        options = options.with_frame_time_significant_figures(figures);
+    }
+    if let Some(figures) = motion_values_significant_figures {
+        options = options.with_motion_values_significant_figures(figures);
+    }
+
+    Ok(options)
+}
+
+fn positions_to_array(positions: &[mint::Vector3<f32>], num_joints: usize) -> Array2<f32> {
+    Array2::from_shape_fn((num_joints, 3), |(joint, axis)| {
+        let p = &positions[joint];
+        match axis {
+            0 => p.x,
+            1 => p.y,
+            _ => p.z,
+        }
+    })
+}
+
+/// Python bindings for the `bvh_anim` crate, a `.bvh` file parser.
+#[pymodule]
+#[pyo3(name = "bvh_anim")]
+fn bvh_anim_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBvh>()?;
+    m.add_class::<PyJoint>()?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(loads, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,134 @@
+import os
+
+import numpy as np
+import pytest
+
+import bvh_anim
+
+DATA_DIR = os.path.join(os.path.dirname(__file__), "..", "..", "data")
+
+SIMPLE_SKELETON = """
+HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Middle1
+    {
+        OFFSET 0.0 0.0 15.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Tip1
+        {
+            OFFSET 0.0 0.0 30.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            End Site
+            {
+                OFFSET 0.0 0.0 45.0
+            }
+        }
+    }
+    JOINT Middle2
+    {
+        OFFSET 0.0 15.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Tip2
+        {
+            OFFSET 0.0 30.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            End Site
+            {
+                OFFSET 0.0 45.0 0.0
+            }
+        }
+    }
+}
+MOTION
+Frames: 3
+Frame Time: 0.033333333333
+0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0
+2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0 2.0
+"""
+
+
+def test_load_file():
+    bvh = bvh_anim.load(os.path.join(DATA_DIR, "test_mocapbank.bvh"))
+    assert bvh.num_joints > 0
+    assert bvh.motion.shape == (bvh.num_frames, bvh.num_channels)
+
+
+def test_load_error():
+    with pytest.raises(ValueError):
+        bvh_anim.loads("HIERARCHY\nMOTION\n")
+    with pytest.raises(IOError):
+        bvh_anim.load(os.path.join(DATA_DIR, "does_not_exist.bvh"))
+
+
+def test_joint_tree():
+    bvh = bvh_anim.loads(SIMPLE_SKELETON)
+    assert [joint.name for joint in bvh.joints] == [
+        "Base",
+        "Middle1",
+        "Tip1",
+        "Middle2",
+        "Tip2",
+    ]
+
+    root = bvh.root
+    assert root.parent is None
+    assert root.depth == 0
+    assert [child.name for child in root.children] == ["Middle1", "Middle2"]
+
+    tip = bvh.find_joint("Tip2")
+    assert tip.parent.name == "Middle2"
+    assert tip.depth == 2
+    assert tip.offset == (0.0, 30.0, 0.0)
+    assert tip.end_site == (0.0, 45.0, 0.0)
+    assert [index for _, index in tip.channels] == [15, 16, 17]
+    assert bvh.find_joint("Missing") is None
+
+
+def test_motion():
+    bvh = bvh_anim.loads(SIMPLE_SKELETON)
+    motion = bvh.motion
+    assert motion.dtype == np.float32
+    assert motion.shape == (3, 18)
+    assert np.all(motion[1] == 1.0)
+
+    bvh.motion = np.full((3, 18), 5.0, dtype=np.float32)
+    assert np.all(bvh.motion == 5.0)
+
+    with pytest.raises(ValueError):
+        bvh.motion = np.zeros((2, 18), dtype=np.float32)
+
+
+def test_world_positions():
+    bvh = bvh_anim.loads(SIMPLE_SKELETON)
+    positions = bvh.world_positions(0)
+    assert positions.shape == (5, 3)
+    np.testing.assert_allclose(positions[2], [0.0, 0.0, 45.0], atol=1e-5)
+
+    all_positions = bvh.world_positions()
+    assert all_positions.shape == (3, 5, 3)
+    np.testing.assert_allclose(all_positions[0], positions)
+
+    np.testing.assert_allclose(bvh.world_positions_at_time(0.0), positions)
+
+    with pytest.raises(IndexError):
+        bvh.world_positions(3)
+
+
+def test_round_trip(tmp_path):
+    bvh = bvh_anim.loads(SIMPLE_SKELETON)
+    data = bvh.dumps(indent=4, line_terminator="unix")
+    assert isinstance(data, bytes)
+    assert b"    JOINT Middle1" in data
+
+    path = str(tmp_path / "out.bvh")
+    bvh.save(path, indent="tabs")
+    loaded = bvh_anim.load(path)
+    assert [j.name for j in loaded.joints] == [j.name for j in bvh.joints]
+    np.testing.assert_allclose(loaded.motion, bvh.motion)
+
+    with pytest.raises(ValueError):
+        bvh.dumps(indent="sideways")
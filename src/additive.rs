@@ -0,0 +1,274 @@
+//! Additive animation layers.
+//!
+//! An additive clip stores the difference between each frame of a clip and a
+//! reference pose, rather than the pose itself. It can then be layered on
+//! top of another clip, such as adding breathing or recoil to locomotion.
+//!
+//! Rotations are stored as the rotation from the reference pose to the
+//! frame, in the space of each joint, and positions as the offset from the
+//! reference pose. Both are written back as channel values in the order in
+//! which the channels of each joint are listed.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, additive::AdditiveOptions};
+//! let breathe = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Chest
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 2
+//!     Frame Time: 0.5
+//!     0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     0.0 90.0 0.0 0.0 0.0 0.0 0.0 10.0 0.0
+//! };
+//! let mut walk = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Chest
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 2
+//!     Frame Time: 0.5
+//!     0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     5.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! let options = AdditiveOptions::new().with_weight(0.5);
+//! let additive = options.make_additive(&breathe).unwrap();
+//! options.apply(&mut walk, &additive).unwrap();
+//!
+//! let frame = walk.frames().nth(1).unwrap().as_slice();
+//! assert_eq!(frame[0], 5.0);
+//! assert!((frame[7] - 5.0).abs() < 1.0e-4);
+//! ```
+
+use crate::{
+    blend::same_hierarchy,
+    errors::BlendError,
+    kinematics::{rotation_from_channels, set_rotation_channels},
+    mask::{self, JointMask},
+    math::Quat,
+    Bvh, Channel, JointName,
+};
+
+/// The pose which an additive clip is relative to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReferencePose {
+    /// A frame of the clip which is made additive.
+    Frame(usize),
+    /// The motion values of a frame, with one value for each channel of the
+    /// clip which is made additive.
+    Values(Vec<f32>),
+}
+
+impl Default for ReferencePose {
+    #[inline]
+    fn default() -> Self {
+        ReferencePose::Frame(0)
+    }
+}
+
+/// Specify options for making and applying additive clips.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdditiveOptions {
+    /// The pose which additive clips are made relative to. Defaults to the
+    /// first frame of the clip.
+    pub reference: ReferencePose,
+    /// How much of an additive clip is applied, where `0.0` leaves the base
+    /// clip unchanged and `1.0` applies all of it. Defaults to `1.0`.
+    pub weight: f32,
+    /// The joints which an additive clip is applied to, and how much of it
+    /// is applied to each, or `None` to apply it fully to every joint.
+    /// Defaults to `None`.
+    pub mask: Option<JointMask>,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for AdditiveOptions {
+    #[inline]
+    fn default() -> Self {
+        AdditiveOptions {
+            reference: Default::default(),
+            weight: 1.0,
+            mask: None,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl AdditiveOptions {
+    /// Create a new `AdditiveOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `reference` on `self` to the new `ReferencePose`.
+    #[inline]
+    pub fn with_reference(self, reference: ReferencePose) -> Self {
+        AdditiveOptions { reference, ..self }
+    }
+
+    /// Sets `weight` on `self` to the new weight.
+    #[inline]
+    pub fn with_weight(self, weight: f32) -> Self {
+        AdditiveOptions { weight, ..self }
+    }
+
+    /// Only apply additive clips to the joints called `names`.
+    #[inline]
+    pub fn with_joints<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<JointName>,
+    {
+        self.with_mask(JointMask::from_names(names))
+    }
+
+    /// Sets `mask` on `self` to the new `JointMask`.
+    #[inline]
+    pub fn with_mask(self, mask: JointMask) -> Self {
+        AdditiveOptions {
+            mask: Some(mask),
+            ..self
+        }
+    }
+
+    /// Makes an additive clip from `bvh`, relative to `reference`.
+    ///
+    /// The additive clip has the same hierarchy and number of frames as
+    /// `bvh`. Returns an error if the reference frame is out of bounds, or
+    /// the reference values do not have one value for each channel.
+    pub fn make_additive(&self, bvh: &Bvh) -> Result<Bvh, BlendError> {
+        let num_channels = bvh.num_channels;
+        let reference = match self.reference {
+            ReferencePose::Frame(frame) => bvh
+                .frames()
+                .nth(frame)
+                .ok_or(BlendError::FrameOutOfBounds { frame })?
+                .as_slice()
+                .to_vec(),
+            ReferencePose::Values(ref values) => {
+                if values.len() != num_channels {
+                    return Err(BlendError::InvalidReference);
+                }
+                values.clone()
+            }
+        };
+
+        let mut motion_values = Vec::with_capacity(bvh.motion_values.len());
+        let mut previous = vec![0.0; num_channels];
+        for frame in bvh.frames() {
+            let frame = frame.as_slice();
+            let mut values = previous.clone();
+            for joint in &bvh.joints {
+                let channels = joint.channels();
+                for channel in channels {
+                    if channel.channel_type().is_position() {
+                        let index = channel.motion_index();
+                        values[index] = frame[index] - reference[index];
+                    }
+                }
+
+                if has_rotations(channels) {
+                    let rotation = rotation_from_channels(channels.iter().cloned(), &reference)
+                        .conjugate()
+                        * rotation_from_channels(channels.iter().cloned(), frame);
+                    set_rotation_channels(channels, &mut values, rotation);
+                }
+            }
+
+            motion_values.extend_from_slice(&values);
+            previous = values;
+        }
+
+        let mut additive = bvh.clone();
+        additive.motion_values = motion_values;
+        Ok(additive)
+    }
+
+    /// Applies the additive clip `additive` on top of `base`, scaled by
+    /// `weight` and the weight of each joint in `mask`.
+    ///
+    /// If `additive` is shorter than `base`, it is repeated. Returns an error
+    /// without changing `base` if the clips do not share the same hierarchy,
+    /// or if a selected joint does not exist.
+    pub fn apply(&self, base: &mut Bvh, additive: &Bvh) -> Result<(), BlendError> {
+        if !same_hierarchy(&base.joints, &additive.joints) {
+            return Err(BlendError::HierarchyMismatch);
+        }
+
+        let weights = mask::resolve(self.mask.as_ref(), &base.joints)?;
+
+        if additive.num_frames == 0 {
+            return Ok(());
+        }
+
+        let num_channels = base.num_channels;
+        let frames = base
+            .motion_values
+            .chunks_mut(num_channels)
+            .take(base.num_frames);
+        for (frame, values) in frames.enumerate() {
+            let layer = additive.frames().nth(frame % additive.num_frames).unwrap();
+            let layer = layer.as_slice();
+
+            for (joint, &weight) in base.joints.iter().zip(weights.iter()) {
+                if weight > 0.0 {
+                    apply_joint(joint.channels(), values, layer, self.weight * weight);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies the additive values `layer` of a single joint with `channels` on
+/// top of `frame`, scaled by `weight`.
+fn apply_joint(channels: &[Channel], frame: &mut [f32], layer: &[f32], weight: f32) {
+    for channel in channels {
+        if channel.channel_type().is_position() {
+            let index = channel.motion_index();
+            frame[index] += layer[index] * weight;
+        }
+    }
+
+    if has_rotations(channels) {
+        let base = rotation_from_channels(channels.iter().cloned(), frame);
+        let layer = rotation_from_channels(channels.iter().cloned(), layer);
+        let layer = Quat::identity().slerp(layer, weight);
+        set_rotation_channels(channels, frame, base * layer);
+    }
+}
+
+#[inline]
+fn has_rotations(channels: &[Channel]) -> bool {
+    channels.iter().any(|c| c.channel_type().is_rotation())
+}
@@ -0,0 +1,681 @@
+//! A compact binary container for `Bvh` data.
+//!
+//! Parsing the text `bvh` format is comparatively slow, and the text files
+//! are large. The binary container stores exactly the same information as the
+//! text format, so it can be used as a drop-in cache: a `Bvh` loaded from the
+//! text format, written as a binary container, and read back again compares
+//! equal to the original.
+//!
+//! # Layout
+//!
+//! All integers and floats are stored in little-endian byte order.
+//!
+//! | Section       | Contents                                                        |
+//! |---------------|-----------------------------------------------------------------|
+//! | Header        | magic `b"BVHB"`, `u16` version, `u16` flags, `u32` number of joints, `u32` number of channels, `u32` number of frames, frame time as `u64` seconds and `u32` nanoseconds |
+//! | Joint table   | per joint: `u32` parent index (`u32::MAX` for the root), `u32` name length, name bytes, offset as 3 `f32`s, `u8` end site flag, followed by the end site offset as 3 `f32`s if the flag is `1` |
+//! | Channel table | per motion index: `u32` joint index, `u8` channel type          |
+//! | Motion block  | `u64` length in bytes, followed by the motion values as `f32`s, one frame after another |
+//!
+//! If bit `0` of the flags is set, then the motion block is compressed
+//! with `zlib`. Compression requires the `compression` feature to be enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, Bvh};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 3 Xposition Yposition Zposition
+//!         End Site
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 2
+//!     Frame Time: 0.033333333
+//!     1.0 2.0 3.0
+//!     4.0 5.0 6.0
+//! };
+//!
+//! let bytes = bvh.to_binary()?;
+//! assert_eq!(Bvh::from_binary(&bytes)?, bvh);
+//! # Result::<(), Box<dyn std::error::Error>>::Ok(())
+//! ```
+
+use crate::{
+    errors::LoadBinaryError, joint::JointPrivateData, Bvh, Channel, ChannelType, JointData,
+};
+use mint::Vector3;
+use smallvec::SmallVec;
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+/// The bytes at the start of every binary container.
+pub const MAGIC: [u8; 4] = *b"BVHB";
+
+/// The version of the container format written by this library.
+pub const VERSION: u16 = 1;
+
+/// Flag which is set if the motion block is compressed.
+const FLAG_COMPRESSED: u16 = 1;
+
+/// Parent index of the root joint, and joint index of unused channels.
+const NO_INDEX: u32 = u32::MAX;
+
+/// Size in bytes of a single entry of the channel table.
+const CHANNEL_ENTRY_SIZE: usize = 5;
+
+/// Specify options for writing a `Bvh` as a binary container.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BinaryOptions {
+    /// Whether to compress the motion block.
+    #[cfg(feature = "compression")]
+    pub compress: bool,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for BinaryOptions {
+    #[inline]
+    fn default() -> Self {
+        BinaryOptions {
+            #[cfg(feature = "compression")]
+            compress: false,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl BinaryOptions {
+    /// Create a new `BinaryOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `compress` on `self` to the new `compress`.
+    #[cfg(feature = "compression")]
+    #[inline]
+    pub const fn with_compression(self, compress: bool) -> Self {
+        BinaryOptions { compress, ..self }
+    }
+
+    /// Output the `Bvh` as a binary container to the `writer`.
+    pub fn write<W: Write>(&self, bvh: &Bvh, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.write_to_vec(bvh)?)?;
+        writer.flush()
+    }
+
+    /// Output the `Bvh` as a binary container to a `Vec<u8>`.
+    ///
+    /// Returns an error of kind `InvalidInput` if the motion index of a
+    /// channel is out of bounds, or is shared by more than one channel.
+    pub fn write_to_vec(&self, bvh: &Bvh) -> io::Result<Vec<u8>> {
+        let mut channel_table = vec![(NO_INDEX, 0u8); bvh.num_channels];
+        for (joint_index, joint) in bvh.joints.iter().enumerate() {
+            for channel in joint.channels() {
+                match channel_table.get_mut(channel.motion_index()) {
+                    Some(entry) if entry.0 == NO_INDEX => {
+                        *entry = (
+                            joint_index as u32,
+                            channel_type_to_code(channel.channel_type()),
+                        );
+                    }
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "the motion index {} of the joint {:?} is not valid",
+                                channel.motion_index(),
+                                joint.name()
+                            ),
+                        ))
+                    }
+                }
+            }
+        }
+
+        let (flags, motion) = self.motion_block(bvh);
+
+        let mut out = Vec::with_capacity(64 + motion.len());
+        out.extend_from_slice(&MAGIC);
+        put_u16(&mut out, VERSION);
+        put_u16(&mut out, flags);
+        put_u32(&mut out, bvh.joints.len() as u32);
+        put_u32(&mut out, bvh.num_channels as u32);
+        put_u32(&mut out, bvh.num_frames as u32);
+        put_u64(&mut out, bvh.frame_time.as_secs());
+        put_u32(&mut out, bvh.frame_time.subsec_nanos());
+
+        for joint in &bvh.joints {
+            put_u32(
+                &mut out,
+                joint.parent_index().map(|p| p as u32).unwrap_or(NO_INDEX),
+            );
+            put_u32(&mut out, joint.name().len() as u32);
+            out.extend_from_slice(joint.name());
+            put_vector3(&mut out, joint.offset());
+            match joint.end_site() {
+                Some(end_site) => {
+                    out.push(1);
+                    put_vector3(&mut out, end_site);
+                }
+                None => out.push(0),
+            }
+        }
+
+        for (joint_index, channel_code) in channel_table {
+            put_u32(&mut out, joint_index);
+            out.push(channel_code);
+        }
+
+        put_u64(&mut out, motion.len() as u64);
+        out.extend_from_slice(&motion);
+
+        Ok(out)
+    }
+
+    /// Returns the flags and the encoded bytes of the motion block.
+    fn motion_block(&self, bvh: &Bvh) -> (u16, Vec<u8>) {
+        let mut motion = Vec::with_capacity(bvh.motion_values.len() * 4);
+        for value in &bvh.motion_values {
+            motion.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+
+        #[cfg(feature = "compression")]
+        {
+            if self.compress {
+                use flate2::{write::ZlibEncoder, Compression};
+
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                // Writing into a `Vec` cannot fail.
+                encoder
+                    .write_all(&motion)
+                    .and_then(|_| encoder.finish())
+                    .map(|compressed| (FLAG_COMPRESSED, compressed))
+                    .expect("could not compress the motion block")
+            } else {
+                (0, motion)
+            }
+        }
+
+        #[cfg(not(feature = "compression"))]
+        {
+            (0, motion)
+        }
+    }
+}
+
+/// A read-only view of a binary container.
+///
+/// The header and tables are validated when the view is created, but the
+/// joints and motion values are only decoded on demand. Unless the motion
+/// block is compressed, the motion values are read directly from the
+/// borrowed bytes.
+#[derive(Clone, Debug)]
+pub struct BinaryBvh<'a> {
+    version: u16,
+    flags: u16,
+    num_joints: usize,
+    num_channels: usize,
+    num_frames: usize,
+    frame_time: Duration,
+    joint_table: &'a [u8],
+    channel_table: &'a [u8],
+    motion: Cow<'a, [u8]>,
+}
+
+impl<'a> BinaryBvh<'a> {
+    /// Validates the binary container in `bytes`, and creates a view of it.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, LoadBinaryError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(LoadBinaryError::InvalidMagic);
+        }
+
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(LoadBinaryError::UnsupportedVersion { version });
+        }
+
+        let flags = reader.u16()?;
+        let num_joints = reader.u32()? as usize;
+        let num_channels = reader.u32()? as usize;
+        let num_frames = reader.u32()? as usize;
+        let secs = reader.u64()?;
+        let nanos = reader.u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err(LoadBinaryError::InvalidFrameTime);
+        }
+        let frame_time = Duration::new(secs, nanos);
+
+        // The joints must be in depth-first order, so the parent of each joint
+        // is one of the ancestors of the joint before it.
+        let mut ancestors = Vec::new();
+        let joint_table_start = reader.position();
+        for index in 0..num_joints {
+            let parent = reader.u32()?;
+            let is_valid_parent = if index == 0 {
+                parent == NO_INDEX
+            } else {
+                while ancestors.last().map_or(false, |&a| a != parent) {
+                    ancestors.pop();
+                }
+                !ancestors.is_empty()
+            };
+
+            if !is_valid_parent {
+                return Err(LoadBinaryError::InvalidJoint { index });
+            }
+            ancestors.push(index as u32);
+
+            let name_len = reader.u32()? as usize;
+            reader.take(name_len)?;
+            reader.vector3()?;
+            // The root cannot have an end site.
+            match reader.u8()? {
+                0 => {}
+                1 if index != 0 => {
+                    reader.vector3()?;
+                }
+                _ => return Err(LoadBinaryError::InvalidJoint { index }),
+            }
+        }
+        let joint_table = &bytes[joint_table_start..reader.position()];
+
+        let channel_table = reader.take(num_channels * CHANNEL_ENTRY_SIZE)?;
+        for (index, entry) in channel_table.chunks(CHANNEL_ENTRY_SIZE).enumerate() {
+            let joint_index = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+            let is_valid_joint = joint_index == NO_INDEX || (joint_index as usize) < num_joints;
+            if !is_valid_joint || channel_type_from_code(entry[4]).is_none() {
+                return Err(LoadBinaryError::InvalidChannel { index });
+            }
+        }
+
+        let expected = num_frames
+            .checked_mul(num_channels)
+            .and_then(|n| n.checked_mul(4))
+            .unwrap_or(usize::MAX);
+
+        let motion_len = reader.u64()? as usize;
+        let motion_bytes = reader.take(motion_len)?;
+        let motion = if flags & FLAG_COMPRESSED != 0 {
+            Cow::Owned(decompress(motion_bytes, expected)?)
+        } else {
+            Cow::Borrowed(motion_bytes)
+        };
+
+        if motion.len() != expected {
+            return Err(LoadBinaryError::MotionSizeMismatch {
+                expected,
+                actual: motion.len(),
+            });
+        }
+
+        Ok(BinaryBvh {
+            version,
+            flags,
+            num_joints,
+            num_channels,
+            num_frames,
+            frame_time,
+            joint_table,
+            channel_table,
+            motion,
+        })
+    }
+
+    /// Returns the version of the container format.
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Returns `true` if the motion block of the container is compressed.
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.flags & FLAG_COMPRESSED != 0
+    }
+
+    /// Returns the number of joints in the container.
+    #[inline]
+    pub fn num_joints(&self) -> usize {
+        self.num_joints
+    }
+
+    /// Returns the number of channels in the container.
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// Returns the number of frames in the container.
+    #[inline]
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Returns the time it takes to play one frame.
+    #[inline]
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Returns the motion value of the channel with `motion_index` at `frame`,
+    /// or `None` if either index is out of bounds.
+    #[inline]
+    pub fn motion_value(&self, frame: usize, motion_index: usize) -> Option<f32> {
+        self.frame(frame)?.get(motion_index)
+    }
+
+    /// Returns a view of the motion values at `frame`, or `None` if `frame`
+    /// is out of bounds.
+    #[inline]
+    pub fn frame(&self, frame: usize) -> Option<BinaryFrame<'_>> {
+        if frame >= self.num_frames {
+            return None;
+        }
+
+        let frame_size = self.num_channels * 4;
+        let start = frame * frame_size;
+        Some(BinaryFrame {
+            bytes: &self.motion[start..start + frame_size],
+        })
+    }
+
+    /// Decodes the container into a `Bvh`.
+    pub fn to_bvh(&self) -> Result<Bvh, LoadBinaryError> {
+        let mut reader = ByteReader::new(self.joint_table);
+        let mut joints: Vec<JointData> = Vec::with_capacity(self.num_joints);
+        let mut channels: Vec<SmallVec<[Channel; 6]>> = vec![Default::default(); self.num_joints];
+
+        for (index, entry) in self.channel_table.chunks(CHANNEL_ENTRY_SIZE).enumerate() {
+            let joint_index = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+            if joint_index == NO_INDEX {
+                continue;
+            }
+
+            let channel_type = channel_type_from_code(entry[4])
+                .ok_or(LoadBinaryError::InvalidChannel { index })?;
+            channels[joint_index as usize].push(Channel::new(channel_type, index));
+        }
+
+        for (index, channels) in channels.into_iter().enumerate() {
+            let parent = reader.u32()?;
+            let name_len = reader.u32()? as usize;
+            let name = reader.take(name_len)?;
+            let offset = reader.vector3()?;
+            let end_site = match reader.u8()? {
+                1 => Some(reader.vector3()?),
+                _ => None,
+            };
+
+            let mut joint = if parent == NO_INDEX {
+                JointData::empty_root()
+            } else {
+                let parent = parent as usize;
+                let depth = joints[parent].depth() + 1;
+                let mut joint = JointData::empty_child();
+                if let Some(private) = joint.private_data_mut() {
+                    *private = JointPrivateData::new(index, parent, depth);
+                }
+                joint
+            };
+
+            joint.set_name(name);
+            joint.set_offset(offset, false);
+            if let (Some(end_site), true) = (end_site, joint.is_child()) {
+                joint.set_offset(end_site, true);
+            }
+            joint.set_channels(channels);
+
+            joints.push(joint);
+        }
+
+        let motion_values = self
+            .motion
+            .chunks(4)
+            .map(|b| f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+            .collect();
+
+        Ok(Bvh {
+            joints,
+            motion_values,
+            num_frames: self.num_frames,
+            num_channels: self.num_channels,
+            frame_time: self.frame_time,
+        })
+    }
+}
+
+/// A view of the motion values of a single frame in a `BinaryBvh`.
+#[derive(Clone, Copy, Debug)]
+pub struct BinaryFrame<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryFrame<'a> {
+    /// Returns the number of motion values in the frame.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len() / 4
+    }
+
+    /// Returns `true` if the frame has no motion values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the motion value at `motion_index`, or `None` if it is out
+    /// of bounds.
+    #[inline]
+    pub fn get(&self, motion_index: usize) -> Option<f32> {
+        let start = motion_index.checked_mul(4)?;
+        let b = self.bytes.get(start..start + 4)?;
+        Some(f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+    }
+
+    /// Returns an iterator over the motion values of the frame.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = f32> + 'a {
+        self.bytes
+            .chunks(4)
+            .map(|b| f32::from_bits(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+    }
+}
+
+impl Bvh {
+    /// Decodes a `Bvh` from a binary container.
+    ///
+    /// See the [`binary`][`binary`] module for details of the format.
+    ///
+    /// [`binary`]: binary/index.html
+    #[inline]
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, LoadBinaryError> {
+        BinaryBvh::from_bytes(bytes)?.to_bvh()
+    }
+
+    /// Reads a binary container from the `reader`, and decodes it into a `Bvh`.
+    pub fn from_binary_reader<R: Read>(mut reader: R) -> Result<Self, LoadBinaryError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Bvh::from_binary(&bytes)
+    }
+
+    /// Writes the `Bvh` as a binary container to the `writer`, with the default
+    /// options.
+    ///
+    /// # Notes
+    ///
+    /// To customise the output, see the [`BinaryOptions`][`BinaryOptions`] type.
+    ///
+    /// [`BinaryOptions`]: binary/struct.BinaryOptions.html
+    #[inline]
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        BinaryOptions::default().write(self, writer)
+    }
+
+    /// Encodes the `Bvh` as a binary container with the default options.
+    ///
+    /// Returns an error if the motion indices of the channels are not valid.
+    ///
+    /// # Notes
+    ///
+    /// To customise the output, see the [`BinaryOptions`][`BinaryOptions`] type.
+    ///
+    /// [`BinaryOptions`]: binary/struct.BinaryOptions.html
+    #[inline]
+    pub fn to_binary(&self) -> io::Result<Vec<u8>> {
+        BinaryOptions::default().write_to_vec(self)
+    }
+}
+
+/// Decompresses the motion block in `bytes`, which should decompress to
+/// `expected` bytes. At most one byte more than `expected` is decompressed, so
+/// that a malicious container cannot exhaust memory.
+#[cfg(feature = "compression")]
+fn decompress(bytes: &[u8], expected: usize) -> Result<Vec<u8>, LoadBinaryError> {
+    use flate2::read::ZlibDecoder;
+
+    let limit = (expected as u64).saturating_add(1);
+    let mut out = Vec::new();
+    ZlibDecoder::new(bytes).take(limit).read_to_end(&mut out)?;
+    if out.len() > expected {
+        return Err(LoadBinaryError::MotionSizeMismatch {
+            expected,
+            actual: out.len(),
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+#[inline]
+fn decompress(_: &[u8], _: usize) -> Result<Vec<u8>, LoadBinaryError> {
+    Err(LoadBinaryError::CompressionUnsupported)
+}
+
+/// Reads little-endian values from a byte slice.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    #[inline]
+    fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, position: 0 }
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadBinaryError> {
+        let offset = self.position;
+        let bytes = offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or(LoadBinaryError::UnexpectedEof { offset })?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn u8(&mut self) -> Result<u8, LoadBinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    #[inline]
+    fn u16(&mut self) -> Result<u16, LoadBinaryError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    #[inline]
+    fn u32(&mut self) -> Result<u32, LoadBinaryError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    #[inline]
+    fn u64(&mut self) -> Result<u64, LoadBinaryError> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes([
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+        ]))
+    }
+
+    #[inline]
+    fn f32(&mut self) -> Result<f32, LoadBinaryError> {
+        self.u32().map(f32::from_bits)
+    }
+
+    #[inline]
+    fn vector3(&mut self) -> Result<Vector3<f32>, LoadBinaryError> {
+        Ok(Vector3 {
+            x: self.f32()?,
+            y: self.f32()?,
+            z: self.f32()?,
+        })
+    }
+}
+
+#[inline]
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn put_vector3(out: &mut Vec<u8>, value: &Vector3<f32>) {
+    for component in &[value.x, value.y, value.z] {
+        put_u32(out, component.to_bits());
+    }
+}
+
+#[inline]
+fn channel_type_to_code(channel_type: ChannelType) -> u8 {
+    match channel_type {
+        ChannelType::RotationX => 0,
+        ChannelType::RotationY => 1,
+        ChannelType::RotationZ => 2,
+        ChannelType::PositionX => 3,
+        ChannelType::PositionY => 4,
+        ChannelType::PositionZ => 5,
+    }
+}
+
+#[inline]
+fn channel_type_from_code(code: u8) -> Option<ChannelType> {
+    match code {
+        0 => Some(ChannelType::RotationX),
+        1 => Some(ChannelType::RotationY),
+        2 => Some(ChannelType::RotationZ),
+        3 => Some(ChannelType::PositionX),
+        4 => Some(ChannelType::PositionY),
+        5 => Some(ChannelType::PositionZ),
+        _ => None,
+    }
+}
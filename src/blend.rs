@@ -0,0 +1,334 @@
+//! Transitions between two `Bvh` clips.
+//!
+//! A crossfade plays the first clip up to a transition frame, then blends
+//! into the second clip from its own transition frame over a number of
+//! frames, and then plays the rest of the second clip. Rotations are blended
+//! per joint with spherical linear interpolation, and positions are blended
+//! linearly.
+//!
+//! Both clips must share the same hierarchy: the same joints, in the same
+//! order, with the same channels. The result uses the hierarchy, channel
+//! order and frame time of the first clip. A `JointMask` can limit the
+//! transition to part of the body, such as the upper body, while the other
+//! joints keep the motion of the first clip.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, blend::CrossfadeOptions};
+//! # use std::time::Duration;
+//! let walk = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Head
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 0
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 3
+//!     Frame Time: 0.5
+//!     0.0 0.0 0.0 0.0 0.0 0.0
+//!     1.0 0.0 0.0 0.0 0.0 0.0
+//!     2.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//! let turn = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Head
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 0
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 3
+//!     Frame Time: 0.5
+//!     50.0 0.0 0.0 0.0 0.0 90.0
+//!     50.0 0.0 0.0 0.0 0.0 90.0
+//!     50.0 0.0 0.0 0.0 0.0 90.0
+//! };
+//!
+//! let blended = CrossfadeOptions::new()
+//!     .with_frames(1, 0)
+//!     .with_duration(Duration::from_millis(500))
+//!     .crossfade(&walk, &turn)
+//!     .unwrap();
+//!
+//! // One frame of the walk, one blended frame, and then the turn, which
+//! // continues from where the walk was at the transition.
+//! assert_eq!(blended.num_frames(), 4);
+//! let rotations = blended.frames().map(|f| f.as_slice()[5].round()).collect::<Vec<_>>();
+//! assert_eq!(rotations, vec![0.0, 45.0, 90.0, 90.0]);
+//! let positions = blended.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+//! assert_eq!(positions, vec![0.0, 1.0, 1.0, 1.0]);
+//! ```
+
+use crate::{
+    duration_to_fractional_seconds,
+    errors::BlendError,
+    kinematics::{position_channels, rotation_from_channels, set_rotation_channels},
+    mask::{self, JointMask},
+    math::Vec3,
+    Axis, Bvh, Channel, JointData,
+};
+use std::time::Duration;
+
+/// Specify options for crossfading between two `Bvh` clips.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossfadeOptions {
+    /// The frame of the first clip at which the transition starts. Defaults
+    /// to `0`.
+    pub from_frame: usize,
+    /// The frame of the second clip at which the transition starts. Defaults
+    /// to `0`.
+    pub to_frame: usize,
+    /// The duration of the transition, which is rounded to a whole number of
+    /// frames. Defaults to zero, which cuts straight from one clip to the
+    /// other.
+    pub duration: Duration,
+    /// Whether the root of the second clip is moved across the ground, so
+    /// that it starts the transition where the first clip is. Defaults to
+    /// `true`.
+    pub align_root: bool,
+    /// The axis which points up, away from the ground. Defaults to `Axis::Y`.
+    pub up_axis: Axis,
+    /// The joints which transition to the second clip, and how far each of
+    /// them transitions, or `None` for every joint to transition fully.
+    /// Joints which do not transition fully keep part of the motion of the
+    /// first clip, holding its last frame once it ends. Defaults to `None`.
+    pub mask: Option<JointMask>,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for CrossfadeOptions {
+    #[inline]
+    fn default() -> Self {
+        CrossfadeOptions {
+            from_frame: 0,
+            to_frame: 0,
+            duration: Duration::default(),
+            align_root: true,
+            up_axis: Axis::Y,
+            mask: None,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl CrossfadeOptions {
+    /// Create a new `CrossfadeOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `from_frame` and `to_frame` on `self` to the transition frames of
+    /// the first and second clips.
+    #[inline]
+    pub fn with_frames(self, from_frame: usize, to_frame: usize) -> Self {
+        CrossfadeOptions {
+            from_frame,
+            to_frame,
+            ..self
+        }
+    }
+
+    /// Sets `duration` on `self` to the new `Duration`.
+    #[inline]
+    pub fn with_duration(self, duration: Duration) -> Self {
+        CrossfadeOptions { duration, ..self }
+    }
+
+    /// Sets `align_root` on `self` to the new value.
+    #[inline]
+    pub fn with_align_root(self, align_root: bool) -> Self {
+        CrossfadeOptions { align_root, ..self }
+    }
+
+    /// Sets `up_axis` on `self` to the new `Axis`.
+    #[inline]
+    pub fn with_up_axis(self, up_axis: Axis) -> Self {
+        CrossfadeOptions { up_axis, ..self }
+    }
+
+    /// Sets `mask` on `self` to the new `JointMask`.
+    #[inline]
+    pub fn with_mask(self, mask: JointMask) -> Self {
+        CrossfadeOptions {
+            mask: Some(mask),
+            ..self
+        }
+    }
+
+    /// Crossfades from `first` to `second`, and returns the combined clip.
+    ///
+    /// The result contains the frames of `first` before `from_frame`, then
+    /// the transition, in which each frame of `first` from `from_frame` is
+    /// blended with a frame of `second` from `to_frame`, and then the
+    /// remaining frames of `second`. Frames past the end of either clip
+    /// during the transition hold its last frame.
+    ///
+    /// Returns an error if the clips do not share the same hierarchy or
+    /// frame time, if a transition frame is out of bounds, or if a joint in
+    /// `mask` does not exist.
+    pub fn crossfade(&self, first: &Bvh, second: &Bvh) -> Result<Bvh, BlendError> {
+        if !same_hierarchy(&first.joints, &second.joints) {
+            return Err(BlendError::HierarchyMismatch);
+        }
+        if first.frame_time != second.frame_time {
+            return Err(BlendError::FrameTimeMismatch);
+        }
+        for &(bvh, frame) in &[(first, self.from_frame), (second, self.to_frame)] {
+            if frame >= bvh.num_frames {
+                return Err(BlendError::FrameOutOfBounds { frame });
+            }
+        }
+        let weights = mask::resolve(self.mask.as_ref(), &first.joints)?;
+        let full = weights.iter().all(|&w| w >= 1.0);
+
+        let frame_time = duration_to_fractional_seconds(&first.frame_time);
+        let transition = if frame_time > 0.0 {
+            (duration_to_fractional_seconds(&self.duration) / frame_time).round() as usize
+        } else {
+            0
+        };
+
+        let num_channels = first.num_channels;
+        let frame_of = |bvh: &Bvh, frame: usize| -> Vec<f32> {
+            let frame = frame.min(bvh.num_frames - 1);
+            bvh.motion_values[frame * num_channels..][..num_channels].to_vec()
+        };
+
+        let offset = if self.align_root {
+            ground_offset(
+                &first.joints,
+                &frame_of(first, self.from_frame),
+                &frame_of(second, self.to_frame),
+                self.up_axis,
+            )
+        } else {
+            Vec3::zero()
+        };
+        let second_frame = |frame: usize| {
+            let mut values = frame_of(second, frame);
+            if let Some(root) = first.joints.first() {
+                shift_position_channels(root.channels(), &mut values, offset);
+            }
+            values
+        };
+
+        let mut motion_values = first.motion_values[..self.from_frame * num_channels].to_vec();
+        let steps = transition.max(second.num_frames - self.to_frame);
+        for step in 0..steps {
+            let target = second_frame(self.to_frame + step);
+            if step >= transition && full {
+                motion_values.extend_from_slice(&target);
+                continue;
+            }
+
+            let t = if step < transition {
+                (step + 1) as f32 / (transition + 1) as f32
+            } else {
+                1.0
+            };
+            let mut values = frame_of(first, self.from_frame + step);
+            blend_frame(&first.joints, &weights, &mut values, &target, t);
+            motion_values.extend_from_slice(&values);
+        }
+
+        let mut result = first.clone();
+        result.num_frames = motion_values.len() / num_channels.max(1);
+        result.motion_values = motion_values;
+        Ok(result)
+    }
+}
+
+/// Returns `true` if `a` and `b` have the same joints, in the same order,
+/// with the same channels.
+pub(crate) fn same_hierarchy(a: &[JointData], b: &[JointData]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b.iter()).all(|(a, b)| {
+            a.name() == b.name()
+                && a.parent_index() == b.parent_index()
+                && a.channels() == b.channels()
+        })
+}
+
+/// Blends the motion values `frame` towards `target` by `t`, scaled by the
+/// weight of each joint, in place.
+///
+/// The rotation channels of each joint are blended with spherical linear
+/// interpolation, and the position channels are blended linearly.
+pub(crate) fn blend_frame(
+    joints: &[JointData],
+    weights: &[f32],
+    frame: &mut [f32],
+    target: &[f32],
+    t: f32,
+) {
+    for (joint, &weight) in joints.iter().zip(weights.iter()) {
+        if weight > 0.0 {
+            blend_joint(joint.channels(), frame, target, t * weight);
+        }
+    }
+}
+
+/// Blends the `channels` of a single joint in `frame` towards `target` by
+/// `t`, in place.
+pub(crate) fn blend_joint(channels: &[Channel], frame: &mut [f32], target: &[f32], t: f32) {
+    let rotation = rotation_from_channels(channels.iter().cloned(), frame);
+    let target_rotation = rotation_from_channels(channels.iter().cloned(), target);
+
+    for channel in channels {
+        if channel.channel_type().is_position() {
+            let index = channel.motion_index();
+            frame[index] += (target[index] - frame[index]) * t;
+        }
+    }
+
+    if channels.iter().any(|c| c.channel_type().is_rotation()) {
+        set_rotation_channels(channels, frame, rotation.slerp(target_rotation, t));
+    }
+}
+
+/// Returns the translation across the ground which moves the root of
+/// `second` onto the root of `first`.
+fn ground_offset(joints: &[JointData], first: &[f32], second: &[f32], up_axis: Axis) -> Vec3 {
+    let root = match joints.first() {
+        Some(root) => root,
+        None => return Vec3::zero(),
+    };
+    let mut offset = position_channels(root, first) - position_channels(root, second);
+    offset.set(up_axis, 0.0);
+    offset
+}
+
+/// Adds `offset` to the position `channels` in `frame`, using the first
+/// channel for each axis.
+fn shift_position_channels(channels: &[Channel], frame: &mut [f32], offset: Vec3) {
+    let mut seen = [false; 3];
+    for channel in channels {
+        let ty = channel.channel_type();
+        if ty.is_position() && !seen[ty.axis() as usize] {
+            seen[ty.axis() as usize] = true;
+            frame[channel.motion_index()] += offset.get(ty.axis());
+        }
+    }
+}
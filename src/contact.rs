@@ -0,0 +1,429 @@
+//! Detects when the feet of a `Bvh` are planted on the ground, and removes
+//! foot skate.
+//!
+//! A foot is in contact with the ground in a frame when it is close to the
+//! ground, and moving slowly. Clips which have been retargeted or blended
+//! often slide their feet while they are planted. The cleanup pass pins each
+//! planted foot to where it first touched the ground, and bends the leg to
+//! reach it with two-bone inverse kinematics, writing the corrected rotations
+//! back to the motion of the `Bvh`.
+//!
+//! The leg chain of a foot is made of its parent (the knee) and its
+//! grandparent (the hip), which must each have three rotation channels.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, contact::ContactOptions};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT UpLeg
+//!         {
+//!             OFFSET 1.0 0.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             JOINT Leg
+//!             {
+//!                 OFFSET 0.0 -10.0 0.5
+//!                 CHANNELS 3 Zrotation Xrotation Yrotation
+//!                 JOINT Foot
+//!                 {
+//!                     OFFSET 0.0 -10.0 -0.5
+//!                     CHANNELS 3 Zrotation Xrotation Yrotation
+//!                     End Site
+//!                     {
+//!                         OFFSET 0.0 0.0 2.0
+//!                     }
+//!                 }
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 4
+//!     Frame Time: 0.033333333
+//!     0.0 20.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     0.0 20.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     0.0 20.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     0.0 30.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! let contacts = ContactOptions::new()
+//!     .with_feet(vec!["Foot"])
+//!     .detect(&bvh)
+//!     .unwrap();
+//!
+//! assert_eq!(contacts[0].contacts(), &[true, true, false, false]);
+//! ```
+
+use crate::{
+    errors::ContactError,
+    euler::is_tait_bryan,
+    ik::two_bone,
+    joint::joint_index_by_name,
+    kinematics::{set_rotation_channels, world_transforms},
+    math::{Quat, Vec3},
+    Axis, Bvh, Channel, JointData, JointName,
+};
+use bstr::BString;
+use smallvec::SmallVec;
+
+/// The contact flags of a single foot joint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FootContacts {
+    joint: usize,
+    contacts: Vec<bool>,
+}
+
+impl FootContacts {
+    /// The index of the foot joint, as in
+    /// [`JointData::index`][`JointData::index`].
+    ///
+    /// [`JointData::index`]: ../enum.JointData.html#method.index
+    #[inline]
+    pub fn joint(&self) -> usize {
+        self.joint
+    }
+
+    /// Whether the foot is in contact with the ground, for each frame.
+    #[inline]
+    pub fn contacts(&self) -> &[bool] {
+        &self.contacts[..]
+    }
+
+    /// Returns `true` if the foot is in contact with the ground at `frame`.
+    /// Returns `false` if `frame` is out of bounds.
+    #[inline]
+    pub fn is_planted(&self, frame: usize) -> bool {
+        self.contacts.get(frame).cloned().unwrap_or(false)
+    }
+}
+
+/// Specify options for detecting the foot contacts of a `Bvh`.
+///
+/// The default thresholds are suitable for a skeleton measured in
+/// centimetres.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactOptions {
+    /// The names of the foot joints.
+    pub feet: Vec<JointName>,
+    /// The axis which points up, away from the ground. Defaults to `Axis::Y`.
+    pub up_axis: Axis,
+    /// The height of the ground along `up_axis`. Defaults to `0.0`.
+    pub ground_height: f32,
+    /// The greatest height above the ground at which a foot may be in
+    /// contact with it. Defaults to `5.0`.
+    pub height_threshold: f32,
+    /// The greatest speed, in units per second, at which a foot may be in
+    /// contact with the ground. Defaults to `30.0`.
+    pub velocity_threshold: f32,
+    /// The number of frames after a foot leaves the ground over which the
+    /// cleanup pass blends from the pinned position back to the original
+    /// motion. Defaults to `5`.
+    pub blend_frames: usize,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for ContactOptions {
+    #[inline]
+    fn default() -> Self {
+        ContactOptions {
+            feet: Vec::new(),
+            up_axis: Axis::Y,
+            ground_height: 0.0,
+            height_threshold: 5.0,
+            velocity_threshold: 30.0,
+            blend_frames: 5,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl ContactOptions {
+    /// Create a new `ContactOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `feet` on `self` to the joints named in `names`.
+    pub fn with_feet<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<JointName>,
+    {
+        ContactOptions {
+            feet: names.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets `up_axis` on `self` to the new `Axis`.
+    #[inline]
+    pub fn with_up_axis(self, up_axis: Axis) -> Self {
+        ContactOptions { up_axis, ..self }
+    }
+
+    /// Sets `ground_height` on `self` to the new height.
+    #[inline]
+    pub fn with_ground_height(self, ground_height: f32) -> Self {
+        ContactOptions {
+            ground_height,
+            ..self
+        }
+    }
+
+    /// Sets `height_threshold` on `self` to the new threshold.
+    #[inline]
+    pub fn with_height_threshold(self, height_threshold: f32) -> Self {
+        ContactOptions {
+            height_threshold,
+            ..self
+        }
+    }
+
+    /// Sets `velocity_threshold` on `self` to the new threshold.
+    #[inline]
+    pub fn with_velocity_threshold(self, velocity_threshold: f32) -> Self {
+        ContactOptions {
+            velocity_threshold,
+            ..self
+        }
+    }
+
+    /// Sets `blend_frames` on `self` to the new number of frames.
+    #[inline]
+    pub fn with_blend_frames(self, blend_frames: usize) -> Self {
+        ContactOptions {
+            blend_frames,
+            ..self
+        }
+    }
+
+    /// Detects the frames in which each foot of `bvh` is in contact with the
+    /// ground.
+    ///
+    /// Returns the contacts of each foot in the order of `feet`, or an error
+    /// if a foot does not exist.
+    pub fn detect(&self, bvh: &Bvh) -> Result<Vec<FootContacts>, ContactError> {
+        self.detect_at(bvh, &world_positions(bvh))
+    }
+
+    /// Detects the foot contacts of `bvh`, given the world-space `positions`
+    /// of each joint in each frame.
+    fn detect_at(
+        &self,
+        bvh: &Bvh,
+        positions: &[Vec<Vec3>],
+    ) -> Result<Vec<FootContacts>, ContactError> {
+        let feet = self.find_feet(bvh)?;
+        let velocities = bvh.linear_velocities();
+
+        Ok(feet
+            .into_iter()
+            .map(|joint| {
+                let contacts = positions
+                    .iter()
+                    .zip(velocities.iter())
+                    .map(|(positions, velocities)| {
+                        let height = positions[joint].get(self.up_axis);
+                        let speed = Vec3::from(velocities[joint]).length();
+                        height - self.ground_height <= self.height_threshold
+                            && speed <= self.velocity_threshold
+                    })
+                    .collect();
+                FootContacts { joint, contacts }
+            })
+            .collect())
+    }
+
+    /// Detects the foot contacts of `bvh`, and then pins each foot in place
+    /// while it is in contact with the ground, by changing the rotations of
+    /// the hip, knee and foot joints.
+    ///
+    /// Returns the contacts which were detected, or an error without changing
+    /// `bvh` if a foot does not exist or does not have a valid leg chain.
+    pub fn cleanup(&self, bvh: &mut Bvh) -> Result<Vec<FootContacts>, ContactError> {
+        let positions = world_positions(bvh);
+        let contacts = self.detect_at(bvh, &positions)?;
+        let legs = contacts
+            .iter()
+            .map(|foot| find_leg(bvh, foot.joint))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let num_channels = bvh.num_channels;
+        for (foot, leg) in contacts.iter().zip(legs.iter()) {
+            let targets = self.pinned_targets(&positions, foot);
+
+            for (frame, target) in targets.into_iter().enumerate() {
+                let target = match target {
+                    Some(target) => target,
+                    None => continue,
+                };
+
+                let values = &mut bvh.motion_values[frame * num_channels..][..num_channels];
+                leg.reach(&bvh.joints, values, target);
+            }
+        }
+
+        Ok(contacts)
+    }
+
+    /// Returns the position to which the foot is pinned in each frame, if any.
+    fn pinned_targets(&self, positions: &[Vec<Vec3>], foot: &FootContacts) -> Vec<Option<Vec3>> {
+        let positions = positions
+            .iter()
+            .map(|positions| positions[foot.joint])
+            .collect::<Vec<_>>();
+
+        let mut targets = vec![None; positions.len()];
+        for (start, end) in contact_ranges(&foot.contacts) {
+            let pinned = positions[start];
+            for target in &mut targets[start..=end] {
+                *target = Some(pinned);
+            }
+
+            let blend = self.blend_frames + 1;
+            for step in 1..blend {
+                let frame = end + step;
+                if frame >= positions.len() || foot.contacts[frame] {
+                    break;
+                }
+                let t = step as f32 / blend as f32;
+                targets[frame] = Some(pinned.lerp(positions[frame], t));
+            }
+        }
+
+        targets
+    }
+
+    fn find_feet(&self, bvh: &Bvh) -> Result<Vec<usize>, ContactError> {
+        self.feet
+            .iter()
+            .map(|name| {
+                joint_index_by_name(&bvh.joints, &name[..], |name| ContactError::MissingJoint {
+                    name,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The joints of a leg, from the hip to the foot.
+struct Leg {
+    hip: usize,
+    knee: usize,
+    foot: usize,
+}
+
+impl Leg {
+    /// Bends the leg in the motion values of a single `frame` so that the
+    /// foot reaches `target`, without changing the world rotation of the foot.
+    fn reach(&self, joints: &[JointData], frame: &mut [f32], target: Vec3) {
+        let world = world_transforms(joints, frame);
+        let (hip, knee, foot) = (&world[self.hip], &world[self.knee], &world[self.foot]);
+
+        let (upper, lower) = two_bone(hip.translation, knee.translation, foot.translation, target);
+        let hip_rotation = upper * hip.rotation;
+        let knee_rotation = lower * knee.rotation;
+        let parent_rotation = joints[self.hip]
+            .parent_index()
+            .map(|parent| world[parent].rotation)
+            .unwrap_or_else(Quat::identity);
+
+        set_rotation_channels(
+            joints[self.hip].channels(),
+            frame,
+            parent_rotation.conjugate() * hip_rotation,
+        );
+        set_rotation_channels(
+            joints[self.knee].channels(),
+            frame,
+            hip_rotation.conjugate() * knee_rotation,
+        );
+        if has_rotations(&joints[self.foot]) {
+            set_rotation_channels(
+                joints[self.foot].channels(),
+                frame,
+                knee_rotation.conjugate() * foot.rotation,
+            );
+        }
+    }
+}
+
+/// Returns the world-space position of each joint of `bvh` in each frame.
+fn world_positions(bvh: &Bvh) -> Vec<Vec<Vec3>> {
+    bvh.frames()
+        .map(|frame| {
+            world_transforms(&bvh.joints, frame.as_slice())
+                .iter()
+                .map(|t| t.translation)
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds the leg chain which ends at the joint `foot`.
+fn find_leg(bvh: &Bvh, foot: usize) -> Result<Leg, ContactError> {
+    let joints = &bvh.joints;
+    let knee = joints[foot].parent_index();
+    let hip = knee.and_then(|knee| joints[knee].parent_index());
+
+    match (hip, knee) {
+        (Some(hip), Some(knee)) if has_rotations(&joints[hip]) && has_rotations(&joints[knee]) => {
+            Ok(Leg { hip, knee, foot })
+        }
+        _ => Err(ContactError::InvalidLeg {
+            name: BString::from(joints[foot].name().to_vec()),
+        }),
+    }
+}
+
+/// Returns `true` if `joint` has exactly one rotation channel for each axis.
+fn has_rotations(joint: &JointData) -> bool {
+    let rotations = joint
+        .channels()
+        .iter()
+        .filter(|c| c.channel_type().is_rotation())
+        .cloned()
+        .collect::<SmallVec<[Channel; 3]>>();
+    is_tait_bryan(&rotations)
+}
+
+/// Returns the first and last frame of each run of consecutive contacts.
+fn contact_ranges(contacts: &[bool]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (frame, &contact) in contacts.iter().enumerate() {
+        match (start, contact) {
+            (None, true) => start = Some(frame),
+            (Some(first), false) => {
+                ranges.push((first, frame - 1));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(first) = start {
+        ranges.push((first, contacts.len() - 1));
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::contact_ranges;
+
+    #[test]
+    fn ranges_of_contacts() {
+        assert_eq!(contact_ranges(&[]), vec![]);
+        assert_eq!(
+            contact_ranges(&[true, true, false, false, true, false, true]),
+            vec![(0, 1), (4, 4), (6, 6)]
+        );
+        assert_eq!(contact_ranges(&[false, true, true]), vec![(1, 2)]);
+    }
+}
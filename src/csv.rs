@@ -0,0 +1,332 @@
+//! Contains options for exporting and importing `Bvh` motion as `csv` tables.
+//!
+//! The table contains a header row, followed by one row per frame. The first
+//! column is the time of the frame in seconds, and each following column holds
+//! the values of a single channel, named `<joint>.<ChannelType>`, for example
+//! `Hips.Xposition` or `Chest.Zrotation`.
+//!
+//! World-space joint positions may optionally be appended as the columns
+//! `<joint>.WorldX`, `<joint>.WorldY` and `<joint>.WorldZ`. These columns are
+//! ignored when importing.
+
+use bstr::{io::BufReadExt, BString, ByteSlice};
+use crate::{
+    duration_to_fractional_seconds, errors::LoadCsvError, fraction_seconds_to_duration,
+    kinematics, write::LineTerminator, Bvh, Channel, ChannelType, Frame,
+};
+use lexical::try_parse;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// Suffixes of the world-space position columns.
+const WORLD_POSITION_SUFFIXES: [&str; 3] = ["WorldX", "WorldY", "WorldZ"];
+
+/// Name of the time column.
+const TIME_COLUMN: &str = "time";
+
+/// Specify formatting options for converting a `Bvh` to and from `csv`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CsvOptions {
+    /// The byte used to separate columns.
+    pub delimiter: u8,
+    /// Which style new line terminator to use when writing the table.
+    pub line_terminator: LineTerminator,
+    /// Number of digits after the decimal point to use when writing the time
+    /// column.
+    pub time_significant_figures: usize,
+    /// Number of digits after the decimal point to use when writing motion
+    /// values and world positions.
+    pub motion_values_significant_figures: usize,
+    /// Whether to append the world-space position of each joint.
+    pub include_world_positions: bool,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for CsvOptions {
+    #[inline]
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            line_terminator: Default::default(),
+            time_significant_figures: 7,
+            motion_values_significant_figures: 6,
+            include_world_positions: false,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Create a new `CsvOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `delimiter` on `self` to the new `delimiter`.
+    #[inline]
+    pub const fn with_delimiter(self, delimiter: u8) -> Self {
+        CsvOptions { delimiter, ..self }
+    }
+
+    /// Sets `line_terminator` on `self` to the new `LineTerminator`.
+    #[inline]
+    pub fn with_line_terminator(self, line_terminator: LineTerminator) -> Self {
+        CsvOptions {
+            line_terminator,
+            ..self
+        }
+    }
+
+    /// Sets `time_significant_figures` on `self` to the new `time_significant_figures`.
+    #[inline]
+    pub const fn with_time_significant_figures(self, time_significant_figures: usize) -> Self {
+        CsvOptions {
+            time_significant_figures,
+            ..self
+        }
+    }
+
+    /// Sets `motion_values_significant_figures` on `self` to the new
+    /// `motion_values_significant_figures`.
+    #[inline]
+    pub const fn with_motion_values_significant_figures(
+        self,
+        motion_values_significant_figures: usize,
+    ) -> Self {
+        CsvOptions {
+            motion_values_significant_figures,
+            ..self
+        }
+    }
+
+    /// Sets `include_world_positions` on `self` to the new `include_world_positions`.
+    #[inline]
+    pub const fn with_world_positions(self, include_world_positions: bool) -> Self {
+        CsvOptions {
+            include_world_positions,
+            ..self
+        }
+    }
+
+    /// Output the motion of the `Bvh` as a `csv` table to the `writer`.
+    pub fn write<W: Write>(&self, bvh: &Bvh, writer: &mut W) -> io::Result<()> {
+        let mut line = Vec::new();
+
+        self.header(bvh, &mut line);
+        writer.write_all(&line)?;
+
+        for (frame_index, frame) in bvh.frames().enumerate() {
+            self.row(bvh, frame_index, frame, &mut line);
+            writer.write_all(&line)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Output the motion of the `Bvh` as a `csv` table to a `BString`.
+    pub fn write_to_string(&self, bvh: &Bvh) -> BString {
+        let mut out_string = vec![];
+        let mut line = vec![];
+
+        self.header(bvh, &mut line);
+        out_string.append(&mut line);
+
+        for (frame_index, frame) in bvh.frames().enumerate() {
+            self.row(bvh, frame_index, frame, &mut line);
+            out_string.append(&mut line);
+        }
+
+        BString::from(out_string)
+    }
+
+    /// Reads a `csv` table from `reader` and replaces the motion values of `bvh`
+    /// with its contents.
+    ///
+    /// Columns are matched to the channels of `bvh` by name, and every channel of
+    /// `bvh` must have a column. Unrecognised columns are ignored, but no two
+    /// columns may have the same name. If the table
+    /// contains at least two rows, the frame time of `bvh` is set from the
+    /// difference between the first two values of the time column.
+    ///
+    /// On failure, `bvh` is left unmodified.
+    pub fn read_motion<R: BufReadExt>(&self, bvh: &mut Bvh, reader: R) -> Result<(), LoadCsvError> {
+        let mut lines = reader.byte_lines().enumerate();
+
+        let (header_line, header) = loop {
+            match lines.next() {
+                Some((line_num, line)) => {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        break (line_num, line);
+                    }
+                }
+                None => return Err(LoadCsvError::MissingHeader),
+            }
+        };
+
+        let header_fields = header.split(|&b| b == self.delimiter).collect::<Vec<_>>();
+        let num_columns = header_fields.len();
+
+        let mut columns_by_name = HashMap::with_capacity(num_columns);
+        for (column, name) in header_fields.into_iter().enumerate() {
+            let name = name.trim();
+            if columns_by_name.insert(name.to_vec(), column).is_some() {
+                return Err(LoadCsvError::DuplicateColumn {
+                    name: BString::from(name),
+                    line: header_line,
+                });
+            }
+        }
+
+        let time_column = columns_by_name.get(TIME_COLUMN.as_bytes()).cloned();
+
+        let mut channel_columns: Vec<(Channel, usize)> = Vec::with_capacity(bvh.num_channels());
+        for joint in bvh.joints() {
+            for channel in joint.data().channels() {
+                let name = column_name(joint.data().name().as_bytes(), channel.channel_type());
+                match columns_by_name.get(&name[..]) {
+                    Some(&column) => channel_columns.push((*channel, column)),
+                    None => {
+                        return Err(LoadCsvError::MissingChannel {
+                            joint_name: BString::from(joint.data().name().as_bytes()),
+                            channel_type: channel.channel_type(),
+                            line: header_line,
+                        })
+                    }
+                }
+            }
+        }
+
+        let mut motion_values = Vec::new();
+        let mut times = Vec::with_capacity(2);
+        let mut row = vec![0.0f32; bvh.num_channels()];
+        let mut num_frames = 0usize;
+
+        for (line_num, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields = line
+                .split(|&b| b == self.delimiter)
+                .map(|f| f.trim())
+                .collect::<Vec<_>>();
+
+            if fields.len() != num_columns {
+                return Err(LoadCsvError::ColumnCountMismatch {
+                    expected_columns: num_columns,
+                    actual_columns: fields.len(),
+                    line: line_num,
+                });
+            }
+
+            for &(channel, column) in &channel_columns {
+                row[channel.motion_index()] = try_parse::<f32, _>(fields[column]).map_err(|e| {
+                    LoadCsvError::ParseValue {
+                        parse_error: e,
+                        column,
+                        line: line_num,
+                    }
+                })?;
+            }
+
+            if let Some(column) = time_column {
+                if times.len() < 2 {
+                    let time = try_parse::<f64, _>(fields[column]).map_err(|e| {
+                        LoadCsvError::ParseValue {
+                            parse_error: e,
+                            column,
+                            line: line_num,
+                        }
+                    })?;
+                    times.push(time);
+                }
+            }
+
+            motion_values.extend_from_slice(&row);
+            num_frames += 1;
+        }
+
+        if let [t0, t1] = times[..] {
+            if t1 > t0 {
+                bvh.set_frame_time(fraction_seconds_to_duration(t1 - t0));
+            }
+        }
+
+        bvh.motion_values = motion_values;
+        bvh.num_frames = num_frames;
+
+        Ok(())
+    }
+
+    fn header(&self, bvh: &Bvh, line: &mut Vec<u8>) {
+        line.clear();
+        line.extend_from_slice(TIME_COLUMN.as_bytes());
+
+        for joint in bvh.joints() {
+            let name = joint.data().name().as_bytes();
+            for channel in joint.data().channels() {
+                line.push(self.delimiter);
+                line.extend_from_slice(&column_name(name, channel.channel_type()));
+            }
+        }
+
+        if self.include_world_positions {
+            for joint in bvh.joints() {
+                for suffix in WORLD_POSITION_SUFFIXES.iter() {
+                    line.push(self.delimiter);
+                    line.extend_from_slice(joint.data().name().as_bytes());
+                    line.push(b'.');
+                    line.extend_from_slice(suffix.as_bytes());
+                }
+            }
+        }
+
+        line.extend_from_slice(self.line_terminator.as_bstr().as_bytes());
+    }
+
+    fn row(&self, bvh: &Bvh, frame_index: usize, frame: &Frame, line: &mut Vec<u8>) {
+        line.clear();
+
+        let time = frame_index as f64 * duration_to_fractional_seconds(bvh.frame_time());
+        line.extend_from_slice(format!("{:.*}", self.time_significant_figures, time).as_bytes());
+
+        let precision = self.motion_values_significant_figures;
+        for joint in bvh.joints() {
+            for channel in joint.data().channels() {
+                line.push(self.delimiter);
+                let value = format!("{:.*}", precision, frame[channel]);
+                line.extend_from_slice(value.as_bytes());
+            }
+        }
+
+        if self.include_world_positions {
+            for position in kinematics::world_transforms(&bvh.joints, frame.as_slice())
+                .iter()
+                .map(|t| t.translation)
+            {
+                for value in &[position.x, position.y, position.z] {
+                    line.push(self.delimiter);
+                    line.extend_from_slice(format!("{:.*}", precision, value).as_bytes());
+                }
+            }
+        }
+
+        line.extend_from_slice(self.line_terminator.as_bstr().as_bytes());
+    }
+}
+
+/// Returns the name of the column which holds `channel_type` of the joint
+/// named `joint_name`.
+#[inline]
+fn column_name(joint_name: &[u8], channel_type: ChannelType) -> Vec<u8> {
+    let mut name = joint_name.to_vec();
+    name.push(b'.');
+    name.extend_from_slice(channel_type.as_str().as_bytes());
+    name
+}
@@ -0,0 +1,241 @@
+//! Velocities and accelerations of the joints of a `Bvh`.
+//!
+//! Derivatives are estimated with finite differences between frames, scaled
+//! by the `frame_time` of the clip. Frames in the middle of the clip use
+//! central differences, and the first and last frames use one-sided
+//! differences.
+//!
+//! Each method returns one array per frame, and each array is indexed by
+//! [`JointData::index`][`JointData::index`].
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, derivative::RotationSpace};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Tip
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!             CHANNELS 0
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 1.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 3
+//!     Frame Time: 0.5
+//!     0.0 0.0 0.0 0.0 0.0 0.0
+//!     1.0 0.0 0.0 0.0 0.0 45.0
+//!     2.0 0.0 0.0 0.0 0.0 90.0
+//! };
+//!
+//! let velocities = bvh.linear_velocities();
+//! assert_eq!(velocities[1][1], [2.0, 0.0, 0.0].into());
+//!
+//! let angular = bvh.angular_velocities(RotationSpace::Global);
+//! assert!((angular[1][0].y - std::f32::consts::FRAC_PI_2).abs() < 1.0e-4);
+//! ```
+//!
+//! [`JointData::index`]: ../enum.JointData.html#method.index
+
+use crate::{
+    duration_to_fractional_seconds,
+    kinematics::{local_rotation, world_transforms},
+    math::{Quat, Vec3},
+    Bvh,
+};
+use mint::Vector3;
+
+/// The space in which the rotation of a joint is differentiated.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RotationSpace {
+    /// The rotation of the joint relative to its parent. The angular velocity
+    /// is expressed in the space of the parent joint.
+    Local,
+    /// The rotation of the joint in world space. The angular velocity is
+    /// expressed in world space.
+    Global,
+}
+
+impl Default for RotationSpace {
+    #[inline]
+    fn default() -> Self {
+        RotationSpace::Global
+    }
+}
+
+impl Bvh {
+    /// Computes the linear velocity of the world-space position of each joint
+    /// in each frame, in units per second.
+    ///
+    /// The returned array is indexed by frame, and then by
+    /// [`JointData::index`][`JointData::index`]. If the clip has fewer than
+    /// two frames, or its frame time is zero, every velocity is zero.
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn linear_velocities(&self) -> Vec<Vec<Vector3<f32>>> {
+        let positions = self.all_world_positions();
+        let dt = duration_to_fractional_seconds(&self.frame_time) as f32;
+        differentiate(&positions, dt)
+    }
+
+    /// Computes the linear acceleration of the world-space position of each
+    /// joint in each frame, in units per second squared.
+    ///
+    /// The returned array is indexed by frame, and then by
+    /// [`JointData::index`][`JointData::index`]. The first and last frames
+    /// use the acceleration of their neighbouring frame. If the clip has fewer
+    /// than three frames, or its frame time is zero, every acceleration is
+    /// zero.
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn linear_accelerations(&self) -> Vec<Vec<Vector3<f32>>> {
+        let positions = self.all_world_positions();
+        let dt = duration_to_fractional_seconds(&self.frame_time) as f32;
+        let num_joints = self.joints.len();
+        let mut accelerations = vec![vec![Vec3::zero(); num_joints]; positions.len()];
+
+        if positions.len() >= 3 && dt > 0.0 {
+            let scale = 1.0 / (dt * dt);
+            for frame in 1..positions.len() - 1 {
+                for joint in 0..num_joints {
+                    let previous = positions[frame - 1][joint];
+                    let current = positions[frame][joint];
+                    let next = positions[frame + 1][joint];
+                    accelerations[frame][joint] = (next - current * 2.0 + previous) * scale;
+                }
+            }
+
+            let last = positions.len() - 1;
+            accelerations[0] = accelerations[1].clone();
+            accelerations[last] = accelerations[last - 1].clone();
+        }
+
+        to_mint(accelerations)
+    }
+
+    /// Computes the angular velocity of the rotation of each joint in each
+    /// frame, in `space`.
+    ///
+    /// Each angular velocity is a rotation vector, whose direction is the
+    /// axis of rotation and whose length is the speed of rotation in radians
+    /// per second.
+    ///
+    /// The returned array is indexed by frame, and then by
+    /// [`JointData::index`][`JointData::index`]. If the clip has fewer than
+    /// two frames, or its frame time is zero, every angular velocity is zero.
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn angular_velocities(&self, space: RotationSpace) -> Vec<Vec<Vector3<f32>>> {
+        let rotations = self
+            .frames()
+            .map(|frame| match space {
+                RotationSpace::Local => self
+                    .joints
+                    .iter()
+                    .map(|joint| local_rotation(joint, frame.as_slice()))
+                    .collect(),
+                RotationSpace::Global => world_transforms(&self.joints, frame.as_slice())
+                    .iter()
+                    .map(|t| t.rotation)
+                    .collect(),
+            })
+            .collect::<Vec<Vec<Quat>>>();
+
+        let dt = duration_to_fractional_seconds(&self.frame_time) as f32;
+        let num_joints = self.joints.len();
+        let num_frames = rotations.len();
+        let mut velocities = vec![vec![Vec3::zero(); num_joints]; num_frames];
+
+        if num_frames >= 2 && dt > 0.0 {
+            for (frame, velocity) in velocities.iter_mut().enumerate() {
+                let (from, to) = difference_frames(frame, num_frames);
+                let scale = 1.0 / (dt * (to - from) as f32);
+                for (joint, value) in velocity.iter_mut().enumerate() {
+                    let delta = rotations[to][joint] * rotations[from][joint].conjugate();
+                    *value = delta.to_scaled_axis() * scale;
+                }
+            }
+        }
+
+        to_mint(velocities)
+    }
+
+    fn all_world_positions(&self) -> Vec<Vec<Vec3>> {
+        self.frames()
+            .map(|frame| {
+                world_transforms(&self.joints, frame.as_slice())
+                    .iter()
+                    .map(|t| t.translation)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Returns the frames between which the derivative at `frame` is estimated.
+#[inline]
+fn difference_frames(frame: usize, num_frames: usize) -> (usize, usize) {
+    let from = frame.saturating_sub(1);
+    let to = (frame + 1).min(num_frames - 1);
+    (from, to)
+}
+
+/// Differentiates `values`, which are sampled every `dt` seconds.
+fn differentiate(values: &[Vec<Vec3>], dt: f32) -> Vec<Vec<Vector3<f32>>> {
+    let num_frames = values.len();
+    let mut derivatives = values
+        .iter()
+        .map(|frame| vec![Vec3::zero(); frame.len()])
+        .collect::<Vec<_>>();
+
+    if num_frames >= 2 && dt > 0.0 {
+        for (frame, derivative) in derivatives.iter_mut().enumerate() {
+            let (from, to) = difference_frames(frame, num_frames);
+            let scale = 1.0 / (dt * (to - from) as f32);
+            for (joint, value) in derivative.iter_mut().enumerate() {
+                *value = (values[to][joint] - values[from][joint]) * scale;
+            }
+        }
+    }
+
+    to_mint(derivatives)
+}
+
+#[inline]
+fn to_mint(values: Vec<Vec<Vec3>>) -> Vec<Vec<Vector3<f32>>> {
+    values
+        .into_iter()
+        .map(|frame| frame.into_iter().map(Into::into).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{difference_frames, differentiate};
+    use crate::math::Vec3;
+
+    #[test]
+    fn one_sided_differences_at_ends() {
+        assert_eq!(difference_frames(0, 4), (0, 1));
+        assert_eq!(difference_frames(2, 4), (1, 3));
+        assert_eq!(difference_frames(3, 4), (2, 3));
+
+        let values = [0.0, 1.0, 4.0, 9.0]
+            .iter()
+            .map(|&x| vec![Vec3::new(x, 0.0, 0.0)])
+            .collect::<Vec<_>>();
+        let derivatives = differentiate(&values, 0.5)
+            .iter()
+            .map(|frame| frame[0].x)
+            .collect::<Vec<_>>();
+        assert_eq!(derivatives, vec![2.0, 4.0, 8.0, 10.0]);
+    }
+}
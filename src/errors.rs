@@ -1,7 +1,7 @@
 //! Errors which may occur when manipulating `Bvh` files.
 
 use bstr::BString;
-use crate::{Axis, Channel};
+use crate::{Axis, Channel, ChannelType};
 use lexical::Error as LexicalError;
 use std::{error::Error as StdError, fmt, io};
 
@@ -459,3 +459,641 @@ impl StdError for ParseChannelError {
         "The channel could not be parsed from the given string"
     }
 }
+
+/// Represents an error which may occur when loading motion values from
+/// a `csv` table.
+#[derive(Debug)]
+pub enum LoadCsvError {
+    /// An I/O error occurred.
+    Io(io::Error),
+    /// The table does not contain a header row.
+    MissingHeader,
+    /// The header does not contain a column for a channel of the `Bvh`.
+    MissingChannel {
+        /// Name of the joint which owns the channel.
+        joint_name: BString,
+        /// The type of the channel.
+        channel_type: ChannelType,
+        /// The line of the header.
+        line: usize,
+    },
+    /// The header contains more than one column with the same name.
+    DuplicateColumn {
+        /// The name of the column.
+        name: BString,
+        /// The line of the header.
+        line: usize,
+    },
+    /// A row has a different number of columns than the header.
+    ColumnCountMismatch {
+        /// The number of columns in the header.
+        expected_columns: usize,
+        /// The number of columns in the row.
+        actual_columns: usize,
+        /// The line where the error occurred.
+        line: usize,
+    },
+    /// A value in the table could not be parsed.
+    ParseValue {
+        /// The parse error.
+        parse_error: LexicalError,
+        /// The index of the column where the error occurred.
+        column: usize,
+        /// The line where the error occurred.
+        line: usize,
+    },
+}
+
+impl LoadCsvError {
+    /// Get the line where the error occurred, or `None` if there is
+    /// no associated line number.
+    #[inline]
+    pub fn line(&self) -> Option<usize> {
+        match *self {
+            LoadCsvError::MissingChannel { line, .. }
+            | LoadCsvError::DuplicateColumn { line, .. }
+            | LoadCsvError::ColumnCountMismatch { line, .. }
+            | LoadCsvError::ParseValue { line, .. } => Some(line),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadCsvError {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        LoadCsvError::Io(e)
+    }
+}
+
+impl fmt::Display for LoadCsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LoadCsvError::Io(ref e) => fmt::Display::fmt(e, f),
+            LoadCsvError::MissingHeader => f.write_str("the csv header row is missing"),
+            LoadCsvError::MissingChannel {
+                ref joint_name,
+                channel_type,
+                line,
+            } => write!(
+                f,
+                "{}: missing the column for the {} channel of joint {}",
+                line, channel_type, joint_name
+            ),
+            LoadCsvError::DuplicateColumn { ref name, line } => {
+                write!(f, "{}: the column {} appears more than once", line, name)
+            }
+            LoadCsvError::ColumnCountMismatch {
+                expected_columns,
+                actual_columns,
+                line,
+            } => write!(
+                f,
+                "{}: expected {} columns, found {}",
+                line, expected_columns, actual_columns
+            ),
+            LoadCsvError::ParseValue {
+                ref parse_error,
+                column,
+                line,
+            } => write!(
+                f,
+                "{}: could not parse the value in column {}: {}",
+                line, column, parse_error
+            ),
+        }
+    }
+}
+
+impl StdError for LoadCsvError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            LoadCsvError::Io(ref e) => Some(e),
+            LoadCsvError::ParseValue {
+                ref parse_error, ..
+            } => Some(parse_error),
+            _ => None,
+        }
+    }
+}
+
+/// Errors which may arise when reading a binary `Bvh` container.
+#[derive(Debug)]
+pub enum LoadBinaryError {
+    /// An I/O error occurred.
+    Io(io::Error),
+    /// The data does not begin with the binary container magic bytes.
+    InvalidMagic,
+    /// The container was written with an unsupported version of the format.
+    UnsupportedVersion {
+        /// The version of the container.
+        version: u16,
+    },
+    /// The data ended before the container was complete.
+    UnexpectedEof {
+        /// The byte offset at which more data was expected.
+        offset: usize,
+    },
+    /// The frame time in the header is not valid.
+    InvalidFrameTime,
+    /// An entry in the joint table is not valid.
+    InvalidJoint {
+        /// The index of the joint.
+        index: usize,
+    },
+    /// An entry in the channel table is not valid.
+    InvalidChannel {
+        /// The motion index of the channel.
+        index: usize,
+    },
+    /// The size of the motion block does not match the number of frames
+    /// and channels.
+    MotionSizeMismatch {
+        /// The expected size of the motion block in bytes.
+        expected: usize,
+        /// The actual size of the motion block in bytes.
+        actual: usize,
+    },
+    /// The motion block is compressed, but the `compression` feature is
+    /// not enabled.
+    CompressionUnsupported,
+}
+
+impl From<io::Error> for LoadBinaryError {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        LoadBinaryError::Io(e)
+    }
+}
+
+impl fmt::Display for LoadBinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LoadBinaryError::Io(ref e) => fmt::Display::fmt(e, f),
+            LoadBinaryError::InvalidMagic => f.write_str("the data is not a binary bvh container"),
+            LoadBinaryError::UnsupportedVersion { version } => {
+                write!(f, "unsupported binary bvh version {}", version)
+            }
+            LoadBinaryError::UnexpectedEof { offset } => {
+                write!(f, "unexpected end of data at byte {}", offset)
+            }
+            LoadBinaryError::InvalidFrameTime => f.write_str("the frame time is not valid"),
+            LoadBinaryError::InvalidJoint { index } => {
+                write!(f, "the joint at index {} is not valid", index)
+            }
+            LoadBinaryError::InvalidChannel { index } => {
+                write!(f, "the channel at index {} is not valid", index)
+            }
+            LoadBinaryError::MotionSizeMismatch { expected, actual } => write!(
+                f,
+                "expected a motion block of {} bytes, found {}",
+                expected, actual
+            ),
+            LoadBinaryError::CompressionUnsupported => f.write_str(
+                "the motion block is compressed, but the `compression` feature is not enabled",
+            ),
+        }
+    }
+}
+
+impl StdError for LoadBinaryError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            LoadBinaryError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors which may arise when filtering the motion of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterError {
+    /// A joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The frame time of the `Bvh` is zero, so a cutoff frequency cannot be
+    /// converted into a fraction of the sample rate.
+    InvalidFrameTime,
+    /// The cutoff frequency is not between zero and the Nyquist frequency.
+    InvalidCutoff {
+        /// The cutoff frequency, in Hz.
+        cutoff: f32,
+        /// The Nyquist frequency of the `Bvh`, in Hz.
+        nyquist: f32,
+    },
+    /// The parameters of the filter are not valid, such as a Savitzky-Golay
+    /// filter with a polynomial order which is not less than its window.
+    InvalidParameters,
+    /// The rotation channels of a joint cannot describe its filtered
+    /// rotation in a frame.
+    PoseNotPreserved {
+        /// The name of the joint.
+        name: BString,
+        /// The index of the frame.
+        frame: usize,
+    },
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FilterError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            FilterError::InvalidFrameTime => f.write_str("the frame time is zero"),
+            FilterError::InvalidCutoff { cutoff, nyquist } => write!(
+                f,
+                "the cutoff frequency of {}Hz is not between 0Hz and the nyquist frequency of {}Hz",
+                cutoff, nyquist
+            ),
+            FilterError::InvalidParameters => f.write_str("the filter parameters are not valid"),
+            FilterError::PoseNotPreserved { ref name, frame } => write!(
+                f,
+                "the channels of the joint {:?} cannot describe its filtered pose in frame {}",
+                name, frame
+            ),
+        }
+    }
+}
+
+impl StdError for FilterError {}
+
+impl From<MaskError> for FilterError {
+    #[inline]
+    fn from(e: MaskError) -> Self {
+        match e {
+            MaskError::MissingJoint { name } => FilterError::MissingJoint { name },
+        }
+    }
+}
+
+/// Errors which may arise when detecting or cleaning up the foot contacts of
+/// a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContactError {
+    /// A foot joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// A foot joint does not have a parent and grandparent with three
+    /// rotation channels each, which are needed to solve the leg chain.
+    InvalidLeg {
+        /// The name of the foot joint.
+        name: BString,
+    },
+}
+
+impl fmt::Display for ContactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ContactError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            ContactError::InvalidLeg { ref name } => write!(
+                f,
+                "the joint {:?} does not have two parent joints with three rotation channels",
+                name
+            ),
+        }
+    }
+}
+
+impl StdError for ContactError {}
+
+/// Errors which may arise when solving a chain of joints with inverse
+/// kinematics.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IkError {
+    /// A joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The end joint of the chain is not a descendant of the start joint.
+    NotAChain {
+        /// The name of the start joint.
+        start: BString,
+        /// The name of the end joint.
+        end: BString,
+    },
+    /// The two-bone solver was used on a chain which does not have exactly
+    /// three joints.
+    InvalidChainLength {
+        /// The number of joints in the chain.
+        len: usize,
+    },
+    /// The frame to solve does not exist in the `Bvh`.
+    FrameOutOfBounds {
+        /// The index of the frame.
+        frame: usize,
+    },
+    /// The minimum of a rotation limit is greater than its maximum, or is not
+    /// a number.
+    InvalidLimit {
+        /// The name of the joint.
+        name: BString,
+    },
+}
+
+impl fmt::Display for IkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            IkError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            IkError::NotAChain { ref start, ref end } => write!(
+                f,
+                "the joint {:?} is not a descendant of the joint {:?}",
+                end, start
+            ),
+            IkError::InvalidChainLength { len } => write!(
+                f,
+                "the two-bone solver needs a chain of 3 joints, but the chain has {}",
+                len
+            ),
+            IkError::FrameOutOfBounds { frame } => write!(f, "frame {} is out of bounds", frame),
+            IkError::InvalidLimit { ref name } => write!(
+                f,
+                "the rotation limit of the joint {:?} has a minimum greater than its maximum",
+                name
+            ),
+        }
+    }
+}
+
+impl StdError for IkError {}
+
+/// Errors which may arise when applying a root motion track to a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RootMotionError {
+    /// The root motion track does not have the same number of frames as the
+    /// `Bvh`.
+    FrameCountMismatch {
+        /// The number of frames of the `Bvh`.
+        expected: usize,
+        /// The number of frames of the root motion track.
+        found: usize,
+    },
+    /// The root joint does not have a channel which is needed to store the
+    /// root motion.
+    MissingChannel {
+        /// The type of the missing channel.
+        channel_type: ChannelType,
+    },
+}
+
+impl fmt::Display for RootMotionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RootMotionError::FrameCountMismatch { expected, found } => write!(
+                f,
+                "expected a root motion track of {} frames, but it has {}",
+                expected, found
+            ),
+            RootMotionError::MissingChannel { channel_type } => {
+                write!(f, "the root joint does not have a {} channel", channel_type)
+            }
+        }
+    }
+}
+
+impl StdError for RootMotionError {}
+
+/// Errors which may arise when blending two `Bvh` clips together.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlendError {
+    /// The clips do not have the same joints and channels.
+    HierarchyMismatch,
+    /// The clips do not have the same frame time.
+    FrameTimeMismatch,
+    /// A frame does not exist in its clip.
+    FrameOutOfBounds {
+        /// The index of the frame.
+        frame: usize,
+    },
+    /// A joint which was selected by name does not exist in the clips.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The values of a reference pose do not match the channels of the clip.
+    InvalidReference,
+}
+
+impl fmt::Display for BlendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BlendError::HierarchyMismatch => {
+                f.write_str("the clips do not have the same joints and channels")
+            }
+            BlendError::FrameTimeMismatch => f.write_str("the clips do not have the same frame time"),
+            BlendError::FrameOutOfBounds { frame } => write!(f, "frame {} is out of bounds", frame),
+            BlendError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            BlendError::InvalidReference => {
+                f.write_str("the reference pose does not match the channels of the clip")
+            }
+        }
+    }
+}
+
+impl StdError for BlendError {}
+
+impl From<MaskError> for BlendError {
+    #[inline]
+    fn from(e: MaskError) -> Self {
+        match e {
+            MaskError::MissingJoint { name } => BlendError::MissingJoint { name },
+        }
+    }
+}
+
+/// Errors which may arise when editing the joint hierarchy of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HierarchyError {
+    /// A joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// A joint with the same name already exists in the `Bvh`.
+    DuplicateJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The root joint cannot be removed or moved.
+    RootJoint,
+    /// A joint is not a child of the expected parent.
+    NotAChild {
+        /// The name of the joint.
+        name: BString,
+        /// The name of the expected parent.
+        parent: BString,
+    },
+    /// A joint cannot be moved under itself or one of its descendants.
+    InvalidParent {
+        /// The name of the joint.
+        name: BString,
+        /// The name of the new parent.
+        parent: BString,
+    },
+    /// A joint name is empty or contains whitespace, so it cannot be written
+    /// to a bvh file.
+    InvalidName {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The channels of a joint cannot describe its pose in a frame after the
+    /// edit.
+    PoseNotPreserved {
+        /// The name of the joint.
+        name: BString,
+        /// The index of the frame.
+        frame: usize,
+    },
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            HierarchyError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            HierarchyError::DuplicateJoint { ref name } => {
+                write!(f, "a joint called {:?} already exists", name)
+            }
+            HierarchyError::RootJoint => f.write_str("the root joint cannot be removed or moved"),
+            HierarchyError::NotAChild {
+                ref name,
+                ref parent,
+            } => write!(f, "the joint {:?} is not a child of {:?}", name, parent),
+            HierarchyError::InvalidParent {
+                ref name,
+                ref parent,
+            } => write!(
+                f,
+                "the joint {:?} cannot be moved under its descendant {:?}",
+                name, parent
+            ),
+            HierarchyError::InvalidName { ref name } => {
+                write!(f, "the joint name {:?} is empty or contains whitespace", name)
+            }
+            HierarchyError::PoseNotPreserved { ref name, frame } => write!(
+                f,
+                "the channels of the joint {:?} cannot describe its pose in frame {}",
+                name, frame
+            ),
+        }
+    }
+}
+
+impl StdError for HierarchyError {}
+
+/// Errors which may arise when changing the channels of the joints of a
+/// `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayoutError {
+    /// A joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// The new channels of a joint cannot describe its pose in a frame.
+    PoseNotPreserved {
+        /// The name of the joint.
+        name: BString,
+        /// The index of the frame.
+        frame: usize,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LayoutError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            LayoutError::PoseNotPreserved { ref name, frame } => write!(
+                f,
+                "the new channels of the joint {:?} cannot describe its pose in frame {}",
+                name, frame
+            ),
+        }
+    }
+}
+
+impl StdError for LayoutError {}
+
+/// Errors which may arise when applying a `JointMask` to a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MaskError {
+    /// A joint in the mask does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MaskError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+        }
+    }
+}
+
+impl StdError for MaskError {}
+
+/// Errors which may arise when changing the rest pose of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RestPoseError {
+    /// A joint which was selected by name does not exist in the `Bvh`.
+    MissingJoint {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// A joint has no children or end site, so it has no bone to rotate.
+    MissingBone {
+        /// The name of the joint.
+        name: BString,
+    },
+    /// No arms were found in the skeleton.
+    MissingArms,
+    /// The channels of a joint cannot describe its pose relative to the new
+    /// rest pose in a frame.
+    PoseNotPreserved {
+        /// The name of the joint.
+        name: BString,
+        /// The index of the frame.
+        frame: usize,
+    },
+}
+
+impl fmt::Display for RestPoseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RestPoseError::MissingJoint { ref name } => {
+                write!(f, "the joint {:?} does not exist", name)
+            }
+            RestPoseError::MissingBone { ref name } => {
+                write!(f, "the joint {:?} has no children or end site", name)
+            }
+            RestPoseError::MissingArms => f.write_str("no arms were found in the skeleton"),
+            RestPoseError::PoseNotPreserved { ref name, frame } => write!(
+                f,
+                "the channels of the joint {:?} cannot describe its pose in frame {}",
+                name, frame
+            ),
+        }
+    }
+}
+
+impl StdError for RestPoseError {}
@@ -0,0 +1,252 @@
+//! Removes discontinuities from the euler angles of rotation channels.
+//!
+//! Many exporters wrap rotation channels into a fixed range, so a joint which
+//! rotates smoothly past `180°` jumps from `179°` to `-179°` between frames.
+//! The rotation is unchanged, but anything which operates on the raw values,
+//! such as filtering or interpolating them, sees a jump of `358°`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, euler::UnwrapOptions};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 1 Zrotation
+//!         JOINT Tip
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!             CHANNELS 1 Zrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 1.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 3
+//!     Frame Time: 0.033333333
+//!     170.0 0.0
+//!     179.0 0.0
+//!     -178.0 0.0
+//! };
+//!
+//! UnwrapOptions::new().unwrap(&mut bvh);
+//!
+//! let values = bvh.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+//! assert_eq!(values, vec![170.0, 179.0, 182.0]);
+//! ```
+
+use crate::{Axis, Bvh, Channel};
+use smallvec::SmallVec;
+
+/// How the rotation channels of a `Bvh` are unwrapped.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UnwrapMode {
+    /// Each rotation channel is unwrapped independently, by adding or
+    /// subtracting multiples of `360°` so that it changes by at most `180°`
+    /// between frames.
+    PerChannel,
+    /// The rotation channels of each joint are unwrapped together. A joint
+    /// with a rotation channel for each axis has two sets of euler angles
+    /// which describe the same rotation. In each frame, the set which is
+    /// closest to the previous frame is chosen, and then unwrapped as in
+    /// `PerChannel`.
+    ///
+    /// Joints without exactly one rotation channel for each axis are
+    /// unwrapped as in `PerChannel`.
+    ClosestEuler,
+}
+
+impl Default for UnwrapMode {
+    #[inline]
+    fn default() -> Self {
+        UnwrapMode::PerChannel
+    }
+}
+
+/// Specify options for unwrapping the rotation channels of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnwrapOptions {
+    /// How the rotation channels are unwrapped.
+    pub mode: UnwrapMode,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for UnwrapOptions {
+    #[inline]
+    fn default() -> Self {
+        UnwrapOptions {
+            mode: Default::default(),
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl UnwrapOptions {
+    /// Create a new `UnwrapOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `mode` on `self` to the new `UnwrapMode`.
+    #[inline]
+    pub fn with_mode(self, mode: UnwrapMode) -> Self {
+        UnwrapOptions { mode, ..self }
+    }
+
+    /// Unwraps the rotation channels of `bvh` in place. The first frame is
+    /// left unchanged, and every other frame describes the same pose as
+    /// it did before.
+    pub fn unwrap(&self, bvh: &mut Bvh) {
+        let num_channels = bvh.num_channels;
+        let num_frames = bvh.num_frames;
+
+        for joint_index in 0..bvh.joints.len() {
+            let rotations = bvh.joints[joint_index]
+                .channels()
+                .iter()
+                .filter(|c| c.channel_type().is_rotation())
+                .cloned()
+                .collect::<SmallVec<[Channel; 3]>>();
+
+            if rotations.is_empty() {
+                continue;
+            }
+
+            let values = &mut bvh.motion_values;
+            let index =
+                |frame: usize, channel: &Channel| frame * num_channels + channel.motion_index();
+
+            if self.mode == UnwrapMode::ClosestEuler && is_tait_bryan(&rotations) {
+                for frame in 1..num_frames {
+                    let previous = rotations
+                        .iter()
+                        .map(|c| values[index(frame - 1, c)])
+                        .collect::<SmallVec<[f32; 3]>>();
+                    let current = rotations
+                        .iter()
+                        .map(|c| values[index(frame, c)])
+                        .collect::<SmallVec<[f32; 3]>>();
+
+                    let closest = closest_euler(&previous, &current);
+                    for (channel, &value) in rotations.iter().zip(closest.iter()) {
+                        values[index(frame, channel)] = value;
+                    }
+                }
+            } else {
+                for channel in &rotations {
+                    for frame in 1..num_frames {
+                        let previous = values[index(frame - 1, channel)];
+                        let current = &mut values[index(frame, channel)];
+                        *current = unwrap_angle(previous, *current);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Bvh {
+    /// Unwraps the rotation channels of `self` with the default
+    /// `UnwrapOptions`.
+    ///
+    /// See the [`euler`][`euler`] module for more information.
+    ///
+    /// [`euler`]: euler/index.html
+    #[inline]
+    pub fn unwrap_rotations(&mut self) {
+        UnwrapOptions::new().unwrap(self)
+    }
+}
+
+/// Returns the angle equivalent to `angle` which is closest to `previous`.
+#[inline]
+pub(crate) fn unwrap_angle(previous: f32, angle: f32) -> f32 {
+    angle - 360.0 * ((angle - previous) / 360.0).round()
+}
+
+/// Returns `true` if `rotations` has exactly one channel for each axis.
+pub(crate) fn is_tait_bryan(rotations: &[Channel]) -> bool {
+    let axes = rotations
+        .iter()
+        .map(|c| c.channel_type().axis())
+        .collect::<SmallVec<[Axis; 3]>>();
+    axes.len() == 3 && axes[0] != axes[1] && axes[1] != axes[2] && axes[0] != axes[2]
+}
+
+/// Returns the unwrapped set of euler angles equivalent to `current` which is
+/// closest to `previous`.
+///
+/// Any rotation `(a, b, c)` around three distinct axes is equivalent to the
+/// rotation `(a + 180, 180 - b, c + 180)`, so both are unwrapped, and the one
+/// with the smallest change from `previous` is chosen.
+pub(crate) fn closest_euler(previous: &[f32], current: &[f32]) -> SmallVec<[f32; 3]> {
+    let unwrapped = |angles: [f32; 3]| -> SmallVec<[f32; 3]> {
+        angles
+            .iter()
+            .zip(previous.iter())
+            .map(|(&angle, &previous)| unwrap_angle(previous, angle))
+            .collect()
+    };
+
+    let distance = |angles: &[f32]| -> f32 {
+        angles
+            .iter()
+            .zip(previous.iter())
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum()
+    };
+
+    let first = unwrapped([current[0], current[1], current[2]]);
+    let second = unwrapped([current[0] + 180.0, 180.0 - current[1], current[2] + 180.0]);
+
+    if distance(&second) < distance(&first) {
+        second
+    } else {
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest_euler, unwrap_angle};
+    use crate::{math::Quat, Axis};
+
+    #[test]
+    fn equivalent_euler_solutions() {
+        let orders = [
+            [Axis::X, Axis::Y, Axis::Z],
+            [Axis::Z, Axis::X, Axis::Y],
+            [Axis::Y, Axis::Z, Axis::X],
+        ];
+
+        for order in &orders {
+            let angles = [30.0, 40.0, -60.0];
+            let alternative = [210.0, 140.0, 120.0];
+
+            let a = Quat::from_euler(order, &angles);
+            let b = Quat::from_euler(order, &alternative);
+            assert!(a.dot(b).abs() > 1.0 - 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn closest_euler_solution() {
+        assert_eq!(unwrap_angle(179.0, -179.0), 181.0);
+        assert_eq!(unwrap_angle(-170.0, 185.0), -175.0);
+
+        // Passing through the singularity of the middle axis flips the
+        // other two axes by 180 degrees in the wrapped representation.
+        let previous = [10.0, 88.0, 20.0];
+        let current = [-170.0, 88.0, -160.0];
+        let closest = closest_euler(&previous, &current);
+        assert!((closest[0] - 10.0).abs() < 1.0e-4);
+        assert!((closest[1] - 92.0).abs() < 1.0e-4);
+        assert!((closest[2] - 20.0).abs() < 1.0e-4);
+    }
+}
@@ -12,24 +12,34 @@
 //! [`Bvh::from_ffi`]: struct.Bvh.html#method.from_ffi
 //! [`Bvh::into_ffi`]: struct.Bvh.html#method.into_ffi
 
-use bstr::BStr;
-use cfile::CFile;
 use crate::{
-    duation_to_fractional_seconds, fraction_seconds_to_duration, frames_iter_logic,
-    joint::JointPrivateData, Bvh, Channel, ChannelType, JointData, JointName,
+    duration_to_fractional_seconds,
+    errors::{LoadError, LoadErrorKind, LoadJointsError, LoadMotionError},
+    fraction_seconds_to_duration, frames_iter_logic,
+    joint::JointPrivateData,
+    kinematics::{self, sample_frames},
+    math::{Transform, Vec3},
+    write::{IndentStyle, LineTerminator, WriteOptions},
+    Bvh, Channel, ChannelType, JointData, JointName,
 };
+use bstr::BStr;
+use cfile::CFile;
 use foreign_types::ForeignType;
-use libc::{c_char, c_double, c_float, c_int, c_void, size_t, strlen, uint32_t, uint8_t, FILE};
+use libc::{
+    c_char, c_double, c_float, c_int, c_void, fflush, fwrite, size_t, strlen, uint32_t, uint8_t,
+    FILE,
+};
 use mint::Vector3;
 use pkg_version::{pkg_version_major, pkg_version_minor, pkg_version_patch};
 use std::{
     alloc::{self, Layout},
+    cell::RefCell,
     convert::TryFrom,
     error::Error,
     ffi::{CStr, CString},
     fmt,
-    io::BufReader,
-    mem,
+    io::{self, BufReader},
+    mem::{self, ManuallyDrop},
     ptr::{self, NonNull},
     slice,
 };
@@ -128,7 +138,7 @@ impl bvh_AllocCallbacks {
         }
 
         if let Some(alloc_cbk) = self.alloc_cbk {
-            (alloc_cbk)(mem::size_of::<T>(), mem::align_of::<T>())
+            (alloc_cbk)(mem::size_of::<T>() * n, mem::align_of::<T>())
         } else {
             ptr::null_mut()
         }
@@ -389,6 +399,153 @@ impl fmt::Debug for bvh_BvhFile {
     }
 }
 
+/// Specify the indentation style used by `bvh_write_with_options`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum bvh_IndentStyle {
+    /// Do not indent nested joints.
+    INDENT_STYLE_NONE,
+    /// Use a single tab for each level of indentation.
+    INDENT_STYLE_TABS,
+    /// Use `bvh_WriteOptions::indent_num_spaces` spaces for each level of
+    /// indentation.
+    INDENT_STYLE_SPACES,
+}
+
+/// Specify the line terminator used by `bvh_write_with_options`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum bvh_LineTerminator {
+    /// Use Unix-style line endings (`'\n'`).
+    LINE_TERMINATOR_UNIX,
+    /// Use Windows-style line endings (`'\r\n'`).
+    LINE_TERMINATOR_WINDOWS,
+}
+
+/// Formatting options used when writing a `bvh_BvhFile`. Mirrors the
+/// `WriteOptions` type of the rust library.
+///
+/// Use `bvh_write_options_default` to get the default options.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct bvh_WriteOptions {
+    /// Which indentation style to use for nested joints. Must be one of the
+    /// `bvh_IndentStyle` values.
+    pub indent_style: c_int,
+    /// The number of spaces per indentation level. Only used if
+    /// `indent_style` is `INDENT_STYLE_SPACES`. A value of `0` means
+    /// that nested joints are not indented.
+    pub indent_num_spaces: size_t,
+    /// Which style of line terminator to use. Must be one of the
+    /// `bvh_LineTerminator` values.
+    pub line_terminator: c_int,
+    /// Number of significant figures to use when writing `OFFSET` values.
+    pub offset_significant_figures: size_t,
+    /// Number of significant figures to use when writing the `Frame Time` value.
+    pub frame_time_significant_figures: size_t,
+    /// Number of significant figures to use when writing `MOTION` values.
+    pub motion_values_significant_figures: size_t,
+}
+
+/// A position in world space.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct bvh_Position {
+    /// The x-component of the position.
+    pub position_x: c_float,
+    /// The y-component of the position.
+    pub position_y: c_float,
+    /// The z-component of the position.
+    pub position_z: c_float,
+}
+
+/// A 4x4 homogeneous transformation matrix.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct bvh_Matrix4 {
+    /// The values of the matrix, in column-major order. The translation
+    /// is stored in elements `12`, `13` and `14`.
+    pub matrix_values: [c_float; 16],
+}
+
+/// The broad category of a `bvh_Error`. Mirrors the `LoadErrorKind` type
+/// of the rust library.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum bvh_ErrorCategory {
+    /// An error occurred when loading the joints hierarchy.
+    ERROR_CATEGORY_JOINTS,
+    /// An error occurred when loading the motion values.
+    ERROR_CATEGORY_MOTION,
+    /// An error occurred which was not caused by loading a bvh file, such as
+    /// a `NULL` parameter or an invalid allocator.
+    ERROR_CATEGORY_OTHER,
+}
+
+/// The specific kind of a `bvh_Error`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum bvh_ErrorKind {
+    /// An I/O error occurred.
+    ERROR_KIND_IO,
+    /// The skeletal hierarchy is missing the `ROOT` joint.
+    ERROR_KIND_MISSING_ROOT,
+    /// A name could not be found for a joint.
+    ERROR_KIND_MISSING_JOINT_NAME,
+    /// A `CHANNELS` section was encountered in the wrong location.
+    ERROR_KIND_UNEXPECTED_CHANNELS_SECTION,
+    /// The number of channels could not be parsed in a `CHANNELS` section.
+    ERROR_KIND_PARSE_NUM_CHANNELS,
+    /// A channel type could not be parsed in a `CHANNELS` section.
+    ERROR_KIND_PARSE_CHANNEL,
+    /// An `OFFSET` section was encountered in the wrong location.
+    ERROR_KIND_UNEXPECTED_OFFSET_SECTION,
+    /// An axis in an `OFFSET` section could not be parsed.
+    ERROR_KIND_PARSE_OFFSET,
+    /// An `OFFSET` section was missing an axis.
+    ERROR_KIND_MISSING_OFFSET_AXIS,
+    /// The `MOTION` section is missing.
+    ERROR_KIND_MISSING_MOTION_SECTION,
+    /// The `Frames:` value could not be parsed.
+    ERROR_KIND_MISSING_NUM_FRAMES,
+    /// The `Frame Time:` value could not be parsed.
+    ERROR_KIND_MISSING_FRAME_TIME,
+    /// A motion value could not be parsed.
+    ERROR_KIND_PARSE_MOTION_SECTION,
+    /// The number of motion values does not match the number of frames
+    /// and channels.
+    ERROR_KIND_MOTION_COUNT_MISMATCH,
+    /// A parameter was `NULL` or otherwise invalid.
+    ERROR_KIND_INVALID_ARGUMENT,
+    /// A `bvh_AllocCallbacks` contained a `NULL` function, or an allocation
+    /// failed.
+    ERROR_KIND_INVALID_ALLOCATOR,
+    /// A `bvh_BvhFile` did not contain consistent data.
+    ERROR_KIND_INVALID_DATA,
+}
+
+/// Describes the last error which occurred on the current thread. Use
+/// `bvh_get_last_error` to retrieve it.
+///
+/// The `error_message` string is owned by the `bvh_Error`, and must be
+/// freed with `bvh_error_destroy`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct bvh_Error {
+    /// The broad category of the error.
+    pub error_category: bvh_ErrorCategory,
+    /// The specific kind of the error.
+    pub error_kind: bvh_ErrorKind,
+    /// The zero-based index of the line in the bvh source where the error
+    /// occurred. Should not be used if `bvh_Error::error_has_line` is `0`.
+    pub error_line: size_t,
+    /// Boolean condition as to whether the error has a line number. If it
+    /// does not, then this value is `0`, Otherwise it will be greater than `0`.
+    pub error_has_line: uint8_t,
+    /// A human readable description of the error.
+    pub error_message: *mut c_char,
+}
+
 /// Get the version of the linked `bvh` library.
 ///
 /// If any parameters are `NULL`, then they will be ignored.
@@ -411,6 +568,87 @@ pub unsafe extern "C" fn bvh_get_version(
     }
 }
 
+/// Get the last error which occurred on the current thread, and write it
+/// to `out_error`. The `error_message` of `out_error` is allocated using
+/// `error_alloc_callbacks`.
+///
+/// Functions which return `0` on failure record the reason for the failure,
+/// which replaces any previously recorded error. Successful calls do not
+/// clear the last error.
+///
+/// * If there is an error to report, this function returns a value greater
+///   than `0`, and `out_error` will be in a valid state.
+///
+/// * If there is no error, or the message could not be allocated, this
+///   function returns `0`, and `out_error` will not be modified.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_last_error(
+    out_error: *mut bvh_Error,
+    error_alloc_callbacks: *const bvh_AllocCallbacks,
+) -> c_int {
+    if out_error.is_null() || error_alloc_callbacks.is_null() {
+        return 0;
+    }
+
+    let allocator = &*error_alloc_callbacks;
+    LAST_ERROR.with(|last_error| match *last_error.borrow() {
+        Some(ref last_error) => {
+            let message = allocator.joint_name_to_cstring(last_error.message.as_bytes().into());
+            if message.is_null() {
+                return 0;
+            }
+
+            *out_error = bvh_Error {
+                error_category: last_error.category,
+                error_kind: last_error.kind,
+                error_line: last_error.line.unwrap_or(0),
+                error_has_line: if last_error.line.is_some() { 1 } else { 0 },
+                error_message: message,
+            };
+            1
+        }
+        None => 0,
+    })
+}
+
+/// Clear the last error which occurred on the current thread, so that
+/// `bvh_get_last_error` will return `0` until another error occurs.
+#[no_mangle]
+pub extern "C" fn bvh_clear_last_error() {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
+
+/// Destroy the `bvh_Error`, freeing the `error_message` using
+/// `error_alloc_callbacks`, which should be the same callbacks passed to
+/// `bvh_get_last_error`.
+///
+/// Returns `0` if `error` could not be deallocated, otherwise returns a
+/// value greater than `0`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_error_destroy(
+    error: *mut bvh_Error,
+    error_alloc_callbacks: *const bvh_AllocCallbacks,
+) -> c_int {
+    if error.is_null() || error_alloc_callbacks.is_null() {
+        return 0;
+    }
+
+    let allocator = &*error_alloc_callbacks;
+    if allocator.validate().is_err() {
+        return 0;
+    }
+
+    let error = &mut *error;
+    if !error.error_message.is_null() {
+        allocator.free_n(error.error_message, strlen(error.error_message) + 1);
+        error.error_message = ptr::null_mut();
+    }
+
+    1
+}
+
 /// Read the contents of `bvh_file`, and write the data to `out_bvh`,
 /// using the default allocator.
 ///
@@ -418,7 +656,8 @@ pub unsafe extern "C" fn bvh_get_version(
 ///   `out_bvh` will be in a valid state.
 ///
 /// * On failure, this function returns `0`, and `out_bvh` will not
-///   be modified.
+///   be modified. The reason for the failure can be retrieved with
+///   `bvh_get_last_error`.
 ///
 /// This function will not close `bvh_file`.
 #[no_mangle]
@@ -439,7 +678,8 @@ pub unsafe extern "C" fn bvh_read(bvh_file: *mut FILE, out_bvh: *mut bvh_BvhFile
 ///   `out_bvh` will be in a valid state.
 ///
 /// * On failure, this function returns `0`, and `out_bvh` will not
-///   be modified.
+///   be modified. The reason for the failure can be retrieved with
+///   `bvh_get_last_error`.
 ///
 /// This function will not close `bvh_file`.
 ///
@@ -459,14 +699,35 @@ pub unsafe extern "C" fn bvh_read_with_allocator(
     bvh_alloc_callbacks: *const bvh_AllocCallbacks,
     joint_alloc_callbacks: *const bvh_AllocCallbacks,
 ) -> c_int {
-    let cfile = match NonNull::new(bvh_file) {
-        Some(f) => BufReader::new(CFile::from_ptr(f.as_ptr())),
-        None => return 0,
+    if out_bvh.is_null() || bvh_alloc_callbacks.is_null() || joint_alloc_callbacks.is_null() {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+            "A required parameter was NULL",
+        )
+        .record();
+        return 0;
+    }
+
+    // The `CFile` closes the file when dropped, but `bvh_file` is still
+    // owned by the caller.
+    let mut cfile = match NonNull::new(bvh_file) {
+        Some(f) => ManuallyDrop::new(CFile::from_ptr(f.as_ptr())),
+        None => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+                "The file was NULL",
+            )
+            .record();
+            return 0;
+        }
     };
 
-    let bvh = match Bvh::from_reader(cfile) {
+    let bvh = match Bvh::from_reader(BufReader::new(&mut *cfile)) {
         Ok(bvh) => bvh,
-        Err(_) => return 0,
+        Err(e) => {
+            LastError::from(&e).record();
+            return 0;
+        }
     };
 
     match bvh.into_ffi_with_allocator(&*bvh_alloc_callbacks, &*joint_alloc_callbacks) {
@@ -474,7 +735,14 @@ pub unsafe extern "C" fn bvh_read_with_allocator(
             *out_bvh = bvh;
             1
         }
-        _ => 0,
+        _ => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_ALLOCATOR,
+                InvalidAllocator { _priv: () }.to_string(),
+            )
+            .record();
+            0
+        }
     }
 }
 
@@ -485,7 +753,8 @@ pub unsafe extern "C" fn bvh_read_with_allocator(
 ///   `out_bvh` will be in a valid state.
 ///
 /// * On failure, this function returns `0`, and `out_bvh` will not
-///   be modified.
+///   be modified. The reason for the failure can be retrieved with
+///   `bvh_get_last_error`.
 #[no_mangle]
 #[must_use]
 pub unsafe extern "C" fn bvh_parse(bvh_string: *const c_char, out_bvh: *mut bvh_BvhFile) -> c_int {
@@ -504,7 +773,8 @@ pub unsafe extern "C" fn bvh_parse(bvh_string: *const c_char, out_bvh: *mut bvh_
 ///   `out_bvh` will be in a valid state.
 ///
 /// * On failure, this function returns `0`, and `out_bvh` will not
-///   be modified.
+///   be modified. The reason for the failure can be retrieved with
+///   `bvh_get_last_error`.
 #[no_mangle]
 #[must_use]
 pub unsafe extern "C" fn bvh_parse_with_allocator(
@@ -513,15 +783,24 @@ pub unsafe extern "C" fn bvh_parse_with_allocator(
     bvh_alloc_callbacks: *const bvh_AllocCallbacks,
     joint_alloc_callbacks: *const bvh_AllocCallbacks,
 ) -> c_int {
-    // @TODO(burtonageo): errors
-    if out_bvh.is_null() || bvh_alloc_callbacks.is_null() || joint_alloc_callbacks.is_null() {
+    if bvh_string.is_null()
+        || out_bvh.is_null()
+        || bvh_alloc_callbacks.is_null()
+        || joint_alloc_callbacks.is_null()
+    {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+            "A required parameter was NULL",
+        )
+        .record();
         return 0;
     }
 
     let bvh_string = CStr::from_ptr(bvh_string);
     let bvh = match Bvh::from_bytes(bvh_string.to_bytes()) {
         Ok(bvh) => bvh,
-        Err(_) => {
+        Err(e) => {
+            LastError::from(&e).record();
             return 0;
         }
     };
@@ -531,7 +810,14 @@ pub unsafe extern "C" fn bvh_parse_with_allocator(
             *out_bvh = bvh;
             1
         }
-        _ => 0,
+        _ => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_ALLOCATOR,
+                InvalidAllocator { _priv: () }.to_string(),
+            )
+            .record();
+            0
+        }
     }
 }
 
@@ -628,23 +914,23 @@ pub unsafe extern "C" fn bvh_to_string(
     if bvh_file.is_null() {
         return 1;
     }
-    
+
     let bvh = match Bvh::from_ffi(*bvh_file) {
         Ok(bvh) => bvh,
         Err(_) => return 1,
     };
-    
+
     let string = bvh.to_bstring();
     *bvh_file = bvh.into_ffi();
     let out_buf_len = string.len() + 1;
-    
+
     *out_buffer = (out_buffer_allocator(out_buf_len, mem::align_of::<u8>()) as *mut _);
     let out_buffer = *out_buffer;
-    
+
     if out_buffer.is_null() {
         return 1;
     }
-    
+
     unsafe {
         ptr::write_bytes(out_buffer, 0u8, out_buf_len);
         ptr::copy_nonoverlapping(string.as_ptr(), out_buffer as *mut _, out_buf_len);
@@ -700,6 +986,621 @@ pub unsafe extern "C" fn bvh_get_frame(
         .unwrap_or(ptr::null_mut())
 }
 
+/// Get the default `bvh_WriteOptions`. These are the same options used
+/// by `bvh_write`.
+#[no_mangle]
+pub extern "C" fn bvh_write_options_default() -> bvh_WriteOptions {
+    WriteOptions::default().into()
+}
+
+/// Write `bvh_file` to `out_file` using the default `bvh_WriteOptions`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, this function returns `0`, and the contents of
+///   `out_file` are unspecified. The reason for the failure can be
+///   retrieved with `bvh_get_last_error`.
+///
+/// This function will not close `out_file`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_write(bvh_file: *const bvh_BvhFile, out_file: *mut FILE) -> c_int {
+    let options = bvh_write_options_default();
+    bvh_write_with_options(bvh_file, out_file, &options)
+}
+
+/// Write `bvh_file` to `out_file` using the formatting given in `options`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, this function returns `0`, and the contents of
+///   `out_file` are unspecified. The reason for the failure can be
+///   retrieved with `bvh_get_last_error`.
+///
+/// This function will not close `out_file`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_write_with_options(
+    bvh_file: *const bvh_BvhFile,
+    out_file: *mut FILE,
+    options: *const bvh_WriteOptions,
+) -> c_int {
+    if bvh_file.is_null() || out_file.is_null() || options.is_null() {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+            "A required parameter was NULL",
+        )
+        .record();
+        return 0;
+    }
+
+    let bvh = match Bvh::copy_from_ffi(&*bvh_file) {
+        Ok(bvh) => bvh,
+        Err(_) => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_DATA,
+                "The bvh file contained invalid joints or motion data",
+            )
+            .record();
+            return 0;
+        }
+    };
+
+    let options = match WriteOptions::try_from(*options) {
+        Ok(options) => options,
+        Err(_) => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+                "The write options contained an invalid indent style or line terminator",
+            )
+            .record();
+            return 0;
+        }
+    };
+    let string = options.write_to_string(&bvh);
+    let bytes: &[u8] = string.as_ref();
+
+    let written = fwrite(bytes.as_ptr() as *const c_void, 1, bytes.len(), out_file);
+    if written != bytes.len() || fflush(out_file) != 0 {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_IO,
+            io::Error::last_os_error().to_string(),
+        )
+        .record();
+        return 0;
+    }
+
+    1
+}
+
+/// Find the index of the joint called `joint_name` in the
+/// `bvh_file::bvh_joints` array.
+///
+/// If there is no joint with that name, or if either parameter is `NULL`,
+/// then this will return `SIZE_MAX`.
+#[no_mangle]
+pub unsafe extern "C" fn bvh_find_joint(
+    bvh_file: *const bvh_BvhFile,
+    joint_name: *const c_char,
+) -> size_t {
+    if bvh_file.is_null() || joint_name.is_null() {
+        return usize::MAX;
+    }
+
+    let joint_name = CStr::from_ptr(joint_name);
+    let joints = ptr_to_array((*bvh_file).bvh_joints, (*bvh_file).bvh_num_joints);
+
+    joints
+        .iter()
+        .position(|joint| {
+            !joint.joint_name.is_null() && CStr::from_ptr(joint.joint_name) == joint_name
+        })
+        .unwrap_or(usize::MAX)
+}
+
+/// Get the number of direct children of the joint at `joint_index` in
+/// the `bvh_file::bvh_joints` array.
+///
+/// If `joint_index` is out of bounds, then this will return `0`.
+#[no_mangle]
+pub unsafe extern "C" fn bvh_num_children(
+    bvh_file: *const bvh_BvhFile,
+    joint_index: size_t,
+) -> size_t {
+    if bvh_file.is_null() {
+        return 0;
+    }
+
+    children_of(&*bvh_file, joint_index).count()
+}
+
+/// Get the index in the `bvh_file::bvh_joints` array of the `child_num`th
+/// direct child of the joint at `joint_index`. Children are returned in
+/// the order in which they appear in the file.
+///
+/// If `joint_index` is out of bounds, or `child_num` is greater than or
+/// equal to the value returned by `bvh_num_children`, then this will return
+/// `SIZE_MAX`.
+#[no_mangle]
+pub unsafe extern "C" fn bvh_get_child(
+    bvh_file: *const bvh_BvhFile,
+    joint_index: size_t,
+    child_num: size_t,
+) -> size_t {
+    if bvh_file.is_null() {
+        return usize::MAX;
+    }
+
+    children_of(&*bvh_file, joint_index)
+        .nth(child_num)
+        .unwrap_or(usize::MAX)
+}
+
+/// Returns the indices of the direct children of the joint at `joint_index`.
+///
+/// The joints are in depth-first order, so the descendants of a joint follow
+/// it, and all have a parent index which is at least `joint_index`.
+unsafe fn children_of(
+    bvh_file: &bvh_BvhFile,
+    joint_index: size_t,
+) -> impl Iterator<Item = size_t> + '_ {
+    ptr_to_array(bvh_file.bvh_joints, bvh_file.bvh_num_joints)
+        .iter()
+        .enumerate()
+        .skip(joint_index.saturating_add(1))
+        .take_while(move |(_, joint)| joint.joint_parent_index >= joint_index)
+        .filter(move |(_, joint)| joint.joint_parent_index == joint_index)
+        .map(|(index, _)| index)
+}
+
+/// Get the motion value of `bvh_file` at `frame_num` for the channel with
+/// the motion index `channel_index`, and write it to `out_value`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * If `frame_num` or `channel_index` are out of bounds, this function
+///   returns `0`, and `out_value` will not be modified.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_motion(
+    bvh_file: *const bvh_BvhFile,
+    frame_num: size_t,
+    channel_index: size_t,
+    out_value: *mut c_float,
+) -> c_int {
+    if bvh_file.is_null() || out_value.is_null() {
+        return 0;
+    }
+
+    match motion_value_ptr(&*bvh_file, frame_num, channel_index) {
+        Some(value) => {
+            *out_value = *value;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Set the motion value of `bvh_file` at `frame_num` for the channel with
+/// the motion index `channel_index` to `new_value`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * If `frame_num` or `channel_index` are out of bounds, this function
+///   returns `0`, and `bvh_file` will not be modified.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_set_motion(
+    bvh_file: *mut bvh_BvhFile,
+    frame_num: size_t,
+    channel_index: size_t,
+    new_value: c_float,
+) -> c_int {
+    if bvh_file.is_null() {
+        return 0;
+    }
+
+    match motion_value_ptr(&*bvh_file, frame_num, channel_index) {
+        Some(value) => {
+            *value = new_value;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Copies the joints of `bvh_file` into a `Vec` of `JointData`.
+///
+/// Returns `Err` if any of the joints are invalid, or if they are not in a
+/// consistent order.
+unsafe fn joints_from_ffi(bvh_file: &bvh_BvhFile) -> Result<Vec<JointData>, ()> {
+    if bvh_file.bvh_num_joints != 0 && bvh_file.bvh_joints.is_null() {
+        return Err(());
+    }
+
+    let ffi_joints = ptr_to_array(bvh_file.bvh_joints, bvh_file.bvh_num_joints);
+    let mut joints = Vec::with_capacity(ffi_joints.len());
+
+    for (i, ffi_joint) in ffi_joints.iter().enumerate() {
+        if ffi_joint.joint_name.is_null()
+            || (ffi_joint.joint_num_channels != 0 && ffi_joint.joint_channels.is_null())
+        {
+            return Err(());
+        }
+
+        let name = JointName::from(CStr::from_ptr(ffi_joint.joint_name));
+        let channels = ptr_to_array(ffi_joint.joint_channels, ffi_joint.joint_num_channels)
+            .iter()
+            .map(|&c| Channel::from(c));
+
+        let joint = if i == 0 {
+            JointData::Root {
+                name,
+                offset: ffi_joint.joint_offset.into(),
+                channels: channels.collect(),
+            }
+        } else {
+            if ffi_joint.joint_parent_index >= i {
+                return Err(());
+            }
+
+            JointData::Child {
+                name,
+                offset: ffi_joint.joint_offset.into(),
+                channels: channels.collect(),
+                end_site_offset: if ffi_joint.joint_has_end_site != 0 {
+                    Some(ffi_joint.joint_end_site.into())
+                } else {
+                    None
+                },
+                private: JointPrivateData {
+                    self_index: i,
+                    parent_index: ffi_joint.joint_parent_index,
+                    depth: ffi_joint.joint_depth,
+                },
+            }
+        };
+
+        joints.push(joint);
+    }
+
+    Ok(joints)
+}
+
+/// Returns a pointer to the motion value at `frame_num` and `channel_index`,
+/// or `None` if either are out of bounds.
+#[inline]
+unsafe fn motion_value_ptr(
+    bvh_file: &bvh_BvhFile,
+    frame_num: size_t,
+    channel_index: size_t,
+) -> Option<*mut c_float> {
+    if bvh_file.bvh_motion_data.is_null()
+        || frame_num >= bvh_file.bvh_num_frames
+        || channel_index >= bvh_file.bvh_num_channels
+    {
+        return None;
+    }
+
+    let index = frame_num * bvh_file.bvh_num_channels + channel_index;
+    isize::try_from(index)
+        .ok()
+        .map(|i| bvh_file.bvh_motion_data.offset(i))
+}
+
+/// Evaluate the world-space position of each joint of `bvh_file` at
+/// `frame_num`, and write them to `out_positions`, which is indexed in the
+/// same way as `bvh_file::bvh_joints`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, including if `frame_num` is out of bounds or
+///   `out_positions_len` is less than `bvh_file::bvh_num_joints`, this
+///   function returns `0`, and `out_positions` will not be modified. The
+///   reason for the failure can be retrieved with `bvh_get_last_error`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_world_positions(
+    bvh_file: *const bvh_BvhFile,
+    frame_num: size_t,
+    out_positions: *mut bvh_Position,
+    out_positions_len: size_t,
+) -> c_int {
+    write_world_transforms(
+        bvh_file,
+        |_| Some((frame_num, frame_num, 0.0)),
+        out_positions,
+        out_positions_len,
+        transform_to_position,
+    )
+}
+
+/// Evaluate the world-space transform of each joint of `bvh_file` at
+/// `frame_num`, and write them to `out_matrices`, which is indexed in the
+/// same way as `bvh_file::bvh_joints`.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, including if `frame_num` is out of bounds or
+///   `out_matrices_len` is less than `bvh_file::bvh_num_joints`, this
+///   function returns `0`, and `out_matrices` will not be modified. The
+///   reason for the failure can be retrieved with `bvh_get_last_error`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_world_matrices(
+    bvh_file: *const bvh_BvhFile,
+    frame_num: size_t,
+    out_matrices: *mut bvh_Matrix4,
+    out_matrices_len: size_t,
+) -> c_int {
+    write_world_transforms(
+        bvh_file,
+        |_| Some((frame_num, frame_num, 0.0)),
+        out_matrices,
+        out_matrices_len,
+        transform_to_matrix,
+    )
+}
+
+/// Evaluate the world-space position of each joint of `bvh_file` at `time`
+/// seconds from the start of the animation, and write them to
+/// `out_positions`, which is indexed in the same way as
+/// `bvh_file::bvh_joints`.
+///
+/// Poses between two frames are interpolated. Times outside of the
+/// animation are clamped to the first or last frame.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, including if `bvh_file` has no frames or
+///   `out_positions_len` is less than `bvh_file::bvh_num_joints`, this
+///   function returns `0`, and `out_positions` will not be modified. The
+///   reason for the failure can be retrieved with `bvh_get_last_error`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_world_positions_at_time(
+    bvh_file: *const bvh_BvhFile,
+    time: c_double,
+    out_positions: *mut bvh_Position,
+    out_positions_len: size_t,
+) -> c_int {
+    write_world_transforms(
+        bvh_file,
+        |bvh| sample_frames(bvh.bvh_num_frames, bvh.bvh_frame_time, time),
+        out_positions,
+        out_positions_len,
+        transform_to_position,
+    )
+}
+
+/// Evaluate the world-space transform of each joint of `bvh_file` at `time`
+/// seconds from the start of the animation, and write them to
+/// `out_matrices`, which is indexed in the same way as
+/// `bvh_file::bvh_joints`.
+///
+/// Poses between two frames are interpolated. Times outside of the
+/// animation are clamped to the first or last frame.
+///
+/// * On success, this function returns a value greater than `0`.
+///
+/// * On failure, including if `bvh_file` has no frames or
+///   `out_matrices_len` is less than `bvh_file::bvh_num_joints`, this
+///   function returns `0`, and `out_matrices` will not be modified. The
+///   reason for the failure can be retrieved with `bvh_get_last_error`.
+#[no_mangle]
+#[must_use]
+pub unsafe extern "C" fn bvh_get_world_matrices_at_time(
+    bvh_file: *const bvh_BvhFile,
+    time: c_double,
+    out_matrices: *mut bvh_Matrix4,
+    out_matrices_len: size_t,
+) -> c_int {
+    write_world_transforms(
+        bvh_file,
+        |bvh| sample_frames(bvh.bvh_num_frames, bvh.bvh_frame_time, time),
+        out_matrices,
+        out_matrices_len,
+        transform_to_matrix,
+    )
+}
+
+/// Shared implementation of the forward kinematics functions. `sample`
+/// picks the two frames to blend between, and the blend factor.
+unsafe fn write_world_transforms<T, S>(
+    bvh_file: *const bvh_BvhFile,
+    sample: S,
+    out_values: *mut T,
+    out_values_len: size_t,
+    convert: fn(&Transform) -> T,
+) -> c_int
+where
+    S: FnOnce(&bvh_BvhFile) -> Option<(usize, usize, f32)>,
+{
+    if bvh_file.is_null() || (out_values.is_null() && out_values_len != 0) {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+            "A required parameter was NULL",
+        )
+        .record();
+        return 0;
+    }
+
+    let bvh_file = &*bvh_file;
+    if out_values_len < bvh_file.bvh_num_joints {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+            format!(
+                "The output array has room for {} joints, but the bvh has {} joints",
+                out_values_len, bvh_file.bvh_num_joints
+            ),
+        )
+        .record();
+        return 0;
+    }
+
+    let (from, to, t) = match sample(bvh_file) {
+        Some((from, to, t)) if from < bvh_file.bvh_num_frames && to < bvh_file.bvh_num_frames => {
+            (from, to, t)
+        }
+        _ => {
+            LastError::other(
+                bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT,
+                "The frame is out of bounds",
+            )
+            .record();
+            return 0;
+        }
+    };
+
+    let joints = ptr_to_array(bvh_file.bvh_joints, bvh_file.bvh_num_joints);
+    if !joints_are_consistent(bvh_file, joints) {
+        LastError::other(
+            bvh_ErrorKind::ERROR_KIND_INVALID_DATA,
+            "The bvh file contained invalid joints or motion data",
+        )
+        .record();
+        return 0;
+    }
+
+    let num_channels = bvh_file.bvh_num_channels;
+    let motion = ptr_to_array(
+        bvh_file.bvh_motion_data,
+        num_channels * bvh_file.bvh_num_frames,
+    );
+    let from = &motion[from * num_channels..(from + 1) * num_channels];
+    let to = &motion[to * num_channels..(to + 1) * num_channels];
+
+    let local_transform = |joint: &bvh_Joint, frame: &[f32]| {
+        let channels = ptr_to_array(joint.joint_channels, joint.joint_num_channels);
+        let channels = channels.iter().map(|&c| Channel::from(c));
+        Transform::new(
+            kinematics::rotation_from_channels(channels.clone(), frame),
+            Vec3::from(Vector3::from(joint.joint_offset))
+                + kinematics::position_from_channels(channels, frame),
+        )
+    };
+
+    let transforms =
+        kinematics::compose_world_transforms(joints.iter().enumerate().map(|(i, joint)| {
+            let parent = if i == 0 {
+                None
+            } else {
+                Some(joint.joint_parent_index)
+            };
+            let local = kinematics::lerp_transform(
+                &local_transform(joint, from),
+                &local_transform(joint, to),
+                t,
+            );
+            (parent, local)
+        }));
+
+    let out_values = slice::from_raw_parts_mut(out_values, out_values_len);
+    for (out, transform) in out_values.iter_mut().zip(transforms) {
+        *out = convert(&transform);
+    }
+
+    1
+}
+
+#[inline]
+fn transform_to_position(transform: &Transform) -> bvh_Position {
+    bvh_Position {
+        position_x: transform.translation.x,
+        position_y: transform.translation.y,
+        position_z: transform.translation.z,
+    }
+}
+
+#[inline]
+fn transform_to_matrix(transform: &Transform) -> bvh_Matrix4 {
+    let m = transform.to_column_matrix();
+    bvh_Matrix4 {
+        matrix_values: [
+            m.x.x, m.x.y, m.x.z, m.x.w, m.y.x, m.y.y, m.y.z, m.y.w, m.z.x, m.z.y, m.z.z, m.z.w,
+            m.w.x, m.w.y, m.w.z, m.w.w,
+        ],
+    }
+}
+
+/// Checks that the joints of `bvh_file` can be evaluated without reading
+/// out of bounds.
+unsafe fn joints_are_consistent(bvh_file: &bvh_BvhFile, joints: &[bvh_Joint]) -> bool {
+    if (bvh_file.bvh_num_joints != 0 && bvh_file.bvh_joints.is_null())
+        || (bvh_file.bvh_num_channels != 0 && bvh_file.bvh_motion_data.is_null())
+    {
+        return false;
+    }
+
+    joints.iter().enumerate().all(|(i, joint)| {
+        let channels = ptr_to_array(joint.joint_channels, joint.joint_num_channels);
+        (i == 0 || joint.joint_parent_index < i)
+            && (joint.joint_num_channels == 0 || !joint.joint_channels.is_null())
+            && channels
+                .iter()
+                .all(|c| c.channel_index < bvh_file.bvh_num_channels)
+    })
+}
+
+impl From<WriteOptions> for bvh_WriteOptions {
+    #[inline]
+    fn from(options: WriteOptions) -> Self {
+        let (indent_style, indent_num_spaces) = match options.indent {
+            IndentStyle::NoIndentation => (bvh_IndentStyle::INDENT_STYLE_NONE, 0),
+            IndentStyle::Tabs => (bvh_IndentStyle::INDENT_STYLE_TABS, 0),
+            IndentStyle::Spaces(n) => (bvh_IndentStyle::INDENT_STYLE_SPACES, n.get()),
+        };
+        let indent_style = indent_style as c_int;
+
+        let line_terminator = match options.line_terminator {
+            LineTerminator::Unix => bvh_LineTerminator::LINE_TERMINATOR_UNIX,
+            LineTerminator::Windows => bvh_LineTerminator::LINE_TERMINATOR_WINDOWS,
+        } as c_int;
+
+        bvh_WriteOptions {
+            indent_style,
+            indent_num_spaces,
+            line_terminator,
+            offset_significant_figures: options.offset_significant_figures,
+            frame_time_significant_figures: options.frame_time_significant_figures,
+            motion_values_significant_figures: options.motion_values_significant_figures,
+        }
+    }
+}
+
+impl TryFrom<bvh_WriteOptions> for WriteOptions {
+    type Error = ();
+
+    /// Fails if `indent_style` or `line_terminator` is not one of the values
+    /// of its enum.
+    #[inline]
+    fn try_from(options: bvh_WriteOptions) -> Result<Self, ()> {
+        let indent = match options.indent_style {
+            s if s == bvh_IndentStyle::INDENT_STYLE_NONE as c_int => IndentStyle::NoIndentation,
+            s if s == bvh_IndentStyle::INDENT_STYLE_TABS as c_int => IndentStyle::Tabs,
+            s if s == bvh_IndentStyle::INDENT_STYLE_SPACES as c_int => {
+                IndentStyle::with_spaces(options.indent_num_spaces)
+            }
+            _ => return Err(()),
+        };
+
+        let line_terminator = match options.line_terminator {
+            t if t == bvh_LineTerminator::LINE_TERMINATOR_UNIX as c_int => LineTerminator::Unix,
+            t if t == bvh_LineTerminator::LINE_TERMINATOR_WINDOWS as c_int => {
+                LineTerminator::Windows
+            }
+            _ => return Err(()),
+        };
+
+        Ok(WriteOptions::new()
+            .with_indent(indent)
+            .with_line_terminator(line_terminator)
+            .with_offset_significant_figures(options.offset_significant_figures)
+            .with_frame_time_significant_figures(options.frame_time_significant_figures)
+            .with_motion_values_significant_figures(options.motion_values_significant_figures))
+    }
+}
+
 impl From<Vector3<f32>> for bvh_Offset {
     #[inline]
     fn from(v: Vector3<f32>) -> Self {
@@ -869,6 +1770,39 @@ impl Bvh {
         Err(())
     }
 
+    /// Construct a `Bvh` by copying the data of a `ffi::bvh_BvhFile`,
+    /// leaving `bvh` untouched.
+    ///
+    /// Returns `Err` if any of the arrays of `bvh` are `NULL` when they
+    /// shouldn't be, if the joints are not in a consistent order, or if the
+    /// motion index of a channel is out of bounds.
+    unsafe fn copy_from_ffi(bvh: &bvh_BvhFile) -> Result<Self, ()> {
+        let num_motion_values = bvh
+            .bvh_num_channels
+            .checked_mul(bvh.bvh_num_frames)
+            .ok_or(())?;
+        if num_motion_values != 0 && bvh.bvh_motion_data.is_null() {
+            return Err(());
+        }
+
+        let joints = joints_from_ffi(bvh)?;
+        let channels_in_bounds = joints
+            .iter()
+            .flat_map(|joint| joint.channels())
+            .all(|c| c.motion_index() < bvh.bvh_num_channels);
+        if !channels_in_bounds {
+            return Err(());
+        }
+
+        Ok(Bvh {
+            joints,
+            motion_values: ptr_to_array(bvh.bvh_motion_data, num_motion_values).to_vec(),
+            num_channels: bvh.bvh_num_channels,
+            num_frames: bvh.bvh_num_frames,
+            frame_time: fraction_seconds_to_duration(bvh.bvh_frame_time),
+        })
+    }
+
     /// Converts the `Bvh` into a `ffi::bvh_BvhFile`, using the default
     /// `bvh_AllocCallback`s.
     ///
@@ -930,7 +1864,7 @@ impl Bvh {
 
         out_bvh.bvh_joints = unsafe { bvh_allocator.copy_vec_to_alloc(out_joints) };
 
-        out_bvh.bvh_frame_time = duation_to_fractional_seconds(self.frame_time());
+        out_bvh.bvh_frame_time = duration_to_fractional_seconds(self.frame_time());
         out_bvh.bvh_motion_data = unsafe { bvh_allocator.copy_vec_to_alloc(self.motion_values) };
         out_bvh.bvh_num_channels = self.num_channels;
         out_bvh.bvh_num_frames = self.num_frames;
@@ -939,6 +1873,102 @@ impl Bvh {
     }
 }
 
+/// The last error which occurred on the current thread, which is reported
+/// through `bvh_get_last_error`.
+#[derive(Debug)]
+struct LastError {
+    category: bvh_ErrorCategory,
+    kind: bvh_ErrorKind,
+    line: Option<usize>,
+    message: String,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = RefCell::new(None);
+}
+
+impl LastError {
+    /// Create a new `LastError` which was not caused by loading a bvh file.
+    #[inline]
+    fn other<M: Into<String>>(kind: bvh_ErrorKind, message: M) -> Self {
+        LastError {
+            category: bvh_ErrorCategory::ERROR_CATEGORY_OTHER,
+            kind,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    /// Store `self` as the last error of the current thread.
+    #[inline]
+    fn record(self) {
+        LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(self));
+    }
+}
+
+impl From<&'_ LoadError> for LastError {
+    fn from(error: &LoadError) -> Self {
+        let (category, kind) = match *error.kind() {
+            LoadErrorKind::Joints(ref e) => {
+                let kind = match *e {
+                    LoadJointsError::Io(_) => bvh_ErrorKind::ERROR_KIND_IO,
+                    LoadJointsError::MissingRoot => bvh_ErrorKind::ERROR_KIND_MISSING_ROOT,
+                    LoadJointsError::MissingJointName { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MISSING_JOINT_NAME
+                    }
+                    LoadJointsError::UnexpectedChannelsSection { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_UNEXPECTED_CHANNELS_SECTION
+                    }
+                    LoadJointsError::ParseNumChannelsError { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_PARSE_NUM_CHANNELS
+                    }
+                    LoadJointsError::ParseChannelError { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_PARSE_CHANNEL
+                    }
+                    LoadJointsError::UnexpectedOffsetSection { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_UNEXPECTED_OFFSET_SECTION
+                    }
+                    LoadJointsError::ParseOffsetError { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_PARSE_OFFSET
+                    }
+                    LoadJointsError::MissingOffsetAxis { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MISSING_OFFSET_AXIS
+                    }
+                };
+                (bvh_ErrorCategory::ERROR_CATEGORY_JOINTS, kind)
+            }
+            LoadErrorKind::Motion(ref e) => {
+                let kind = match *e {
+                    LoadMotionError::Io(_) => bvh_ErrorKind::ERROR_KIND_IO,
+                    LoadMotionError::MissingMotionSection { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MISSING_MOTION_SECTION
+                    }
+                    LoadMotionError::MissingNumFrames { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MISSING_NUM_FRAMES
+                    }
+                    LoadMotionError::MissingFrameTime { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MISSING_FRAME_TIME
+                    }
+                    LoadMotionError::ParseMotionSection { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_PARSE_MOTION_SECTION
+                    }
+                    LoadMotionError::MotionCountMismatch { .. } => {
+                        bvh_ErrorKind::ERROR_KIND_MOTION_COUNT_MISMATCH
+                    }
+                };
+                (bvh_ErrorCategory::ERROR_CATEGORY_MOTION, kind)
+            }
+        };
+
+        LastError {
+            category,
+            kind,
+            line: error.line(),
+            message: error.to_string(),
+        }
+    }
+}
+
 /// An error type returned from [`bvh_AllocCallbacks::validate`]
 /// [`bvh_AllocCallbacks::validate`].
 ///
@@ -0,0 +1,598 @@
+//! Time-domain smoothing filters for the motion of a `Bvh`.
+//!
+//! Position channels are filtered directly. The rotation channels of each
+//! joint are converted to quaternions, filtered component-wise, normalised,
+//! and then converted back to euler angles in the rotation order of the
+//! joint, which avoids the artifacts of filtering euler angles near `±180°`.
+//!
+//! Filters are applied to each channel over the whole clip. The start and end
+//! of the clip are extended by reflecting the motion around the first and
+//! last frames, so that a clip moving at a steady rate is not pulled back
+//! towards a standstill at its ends.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, filter::{Filter, FilterOptions}};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 1 Xposition
+//!         JOINT Tip
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!             CHANNELS 1 Zrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 1.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 5
+//!     Frame Time: 0.033333333
+//!     0.0 0.0
+//!     0.0 0.0
+//!     3.0 0.0
+//!     0.0 0.0
+//!     0.0 0.0
+//! };
+//!
+//! FilterOptions::new()
+//!     .with_filter(Filter::MovingAverage { window: 3 })
+//!     .apply(&mut bvh)
+//!     .unwrap();
+//!
+//! let values = bvh.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+//! assert_eq!(values, vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+//! ```
+
+use crate::{
+    duration_to_fractional_seconds,
+    errors::FilterError,
+    kinematics::{pose_preserved, rotation_from_channels, set_rotation_channels},
+    mask::{self, JointMask},
+    math::{Quat, Vec3},
+    Bvh, Channel, JointName,
+};
+use bstr::{BString, ByteSlice};
+use smallvec::SmallVec;
+use std::f64::consts::PI;
+
+/// A smoothing filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Replaces each value with the mean of the `window` values centred on
+    /// it. Even windows are rounded up to the next odd number of frames.
+    MovingAverage {
+        /// The number of frames to average.
+        window: usize,
+    },
+    /// Convolves the motion with a gaussian kernel.
+    Gaussian {
+        /// The standard deviation of the kernel, in frames.
+        sigma: f32,
+    },
+    /// Fits a polynomial of degree `order` to the `window` values centred on
+    /// each value, which preserves the peaks of the motion better than a
+    /// moving average. Even windows are rounded up to the next odd number of
+    /// frames.
+    SavitzkyGolay {
+        /// The number of frames to fit the polynomial to.
+        window: usize,
+        /// The degree of the polynomial, which must be less than the window.
+        order: usize,
+    },
+    /// A Butterworth low-pass filter, which is applied forwards and then
+    /// backwards so that the motion is not delayed. As the filter is applied
+    /// twice, the motion at the cutoff frequency is attenuated by half.
+    Butterworth {
+        /// The cutoff frequency, in Hz.
+        cutoff: f32,
+        /// The order of the filter.
+        order: usize,
+    },
+}
+
+impl Default for Filter {
+    #[inline]
+    fn default() -> Self {
+        Filter::Gaussian { sigma: 1.0 }
+    }
+}
+
+/// Specify options for filtering the motion of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterOptions {
+    /// The filter to apply.
+    pub filter: Filter,
+    /// Whether to filter position channels.
+    pub positions: bool,
+    /// Whether to filter rotation channels.
+    pub rotations: bool,
+    /// The joints to filter, and how much of the filtered motion to use for
+    /// each, or `None` to fully filter every joint.
+    pub mask: Option<JointMask>,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for FilterOptions {
+    #[inline]
+    fn default() -> Self {
+        FilterOptions {
+            filter: Default::default(),
+            positions: true,
+            rotations: true,
+            mask: None,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl FilterOptions {
+    /// Create a new `FilterOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `filter` on `self` to the new `Filter`.
+    #[inline]
+    pub fn with_filter(self, filter: Filter) -> Self {
+        FilterOptions { filter, ..self }
+    }
+
+    /// Sets `positions` on `self` to the new `positions`.
+    #[inline]
+    pub fn with_positions(self, positions: bool) -> Self {
+        FilterOptions { positions, ..self }
+    }
+
+    /// Sets `rotations` on `self` to the new `rotations`.
+    #[inline]
+    pub fn with_rotations(self, rotations: bool) -> Self {
+        FilterOptions { rotations, ..self }
+    }
+
+    /// Only filter the joints called `names`.
+    #[inline]
+    pub fn with_joints<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<JointName>,
+    {
+        self.with_mask(JointMask::from_names(names))
+    }
+
+    /// Only filter the joints selected by `mask`, blending between the
+    /// original and filtered motion by the weight of each joint.
+    #[inline]
+    pub fn with_mask(self, mask: JointMask) -> Self {
+        FilterOptions {
+            mask: Some(mask),
+            ..self
+        }
+    }
+
+    /// Filters the motion of `bvh` in place.
+    ///
+    /// Returns an error without changing `bvh` if a selected joint does not
+    /// exist, if the filter parameters are not valid for `bvh`, or if the
+    /// rotation channels of a joint cannot describe its filtered rotation.
+    pub fn apply(&self, bvh: &mut Bvh) -> Result<(), FilterError> {
+        let weights = mask::resolve(self.mask.as_ref(), &bvh.joints)?;
+
+        let design = Design::new(
+            &self.filter,
+            duration_to_fractional_seconds(&bvh.frame_time),
+        )?;
+        if bvh.num_frames < 2 {
+            return Ok(());
+        }
+
+        let mut edited = bvh.clone();
+        for (joint_index, &weight) in weights.iter().enumerate() {
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let channels = edited.joints[joint_index]
+                .channels()
+                .iter()
+                .cloned()
+                .collect::<SmallVec<[Channel; 6]>>();
+
+            let (rotations, positions): (SmallVec<[Channel; 6]>, SmallVec<[Channel; 6]>) = channels
+                .into_iter()
+                .partition(|c| c.channel_type().is_rotation());
+
+            if self.positions {
+                for channel in &positions {
+                    let original = channel_values(&edited, channel);
+                    let mut signal = original.clone();
+                    design.apply(&mut signal);
+                    if weight < 1.0 {
+                        for (value, &original) in signal.iter_mut().zip(original.iter()) {
+                            *value = original + (*value - original) * weight;
+                        }
+                    }
+                    set_channel_values(&mut edited, channel, &signal);
+                }
+            }
+
+            if self.rotations && !rotations.is_empty() {
+                filter_rotations(&mut edited, joint_index, &rotations, &design, weight)?;
+            }
+        }
+
+        *bvh = edited;
+        Ok(())
+    }
+}
+
+/// Filters the rotation `channels` of a joint in quaternion space, and blends
+/// between the original and filtered rotations by `weight`.
+///
+/// Returns an error if the rotation channels of the joint cannot describe a
+/// filtered rotation, such as when the joint has fewer than three rotation
+/// channels.
+fn filter_rotations(
+    bvh: &mut Bvh,
+    joint_index: usize,
+    channels: &[Channel],
+    design: &Design,
+    weight: f32,
+) -> Result<(), FilterError> {
+    let num_frames = bvh.num_frames;
+    let num_channels = bvh.num_channels;
+
+    let mut quats = Vec::with_capacity(num_frames);
+    for frame in bvh.motion_values.chunks(num_channels).take(num_frames) {
+        let q = rotation_from_channels(channels.iter().cloned(), frame);
+        let q = match quats.last() {
+            Some(&previous) => q.aligned_with(previous),
+            None => q,
+        };
+        quats.push(q);
+    }
+
+    let mut components = [
+        quats.iter().map(|q| q.x).collect::<Vec<_>>(),
+        quats.iter().map(|q| q.y).collect::<Vec<_>>(),
+        quats.iter().map(|q| q.z).collect::<Vec<_>>(),
+        quats.iter().map(|q| q.w).collect::<Vec<_>>(),
+    ];
+    for component in components.iter_mut() {
+        design.apply(component);
+    }
+
+    let frames = bvh.motion_values.chunks_mut(num_channels).take(num_frames);
+    for (frame, values) in frames.enumerate() {
+        let q = Quat::new(
+            components[0][frame],
+            components[1][frame],
+            components[2][frame],
+            components[3][frame],
+        );
+        let q = q.normalize();
+        let q = if weight < 1.0 {
+            quats[frame].slerp(q, weight)
+        } else {
+            q
+        };
+        set_rotation_channels(channels, values, q);
+        if !pose_preserved(channels, values, q, Vec3::default()) {
+            return Err(FilterError::PoseNotPreserved {
+                name: BString::from(bvh.joints[joint_index].name().as_bytes()),
+                frame,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the values of `channel` in each frame of `bvh`.
+fn channel_values(bvh: &Bvh, channel: &Channel) -> Vec<f32> {
+    (0..bvh.num_frames)
+        .map(|frame| bvh.motion_values[frame * bvh.num_channels + channel.motion_index()])
+        .collect()
+}
+
+/// Sets the values of `channel` in each frame of `bvh` to `values`.
+fn set_channel_values(bvh: &mut Bvh, channel: &Channel, values: &[f32]) {
+    for (frame, &value) in values.iter().enumerate() {
+        bvh.motion_values[frame * bvh.num_channels + channel.motion_index()] = value;
+    }
+}
+
+/// A `Filter` which has been prepared for the sample rate of a `Bvh`.
+#[derive(Clone, Debug)]
+enum Design {
+    /// A symmetric convolution kernel with an odd number of taps.
+    Convolution(Vec<f64>),
+    /// A cascade of second order sections, applied forwards and backwards,
+    /// and the number of values to pad the signal with to let the filter
+    /// settle.
+    ZeroPhase(Vec<Biquad>, usize),
+}
+
+impl Design {
+    fn new(filter: &Filter, frame_time: f64) -> Result<Self, FilterError> {
+        match *filter {
+            Filter::MovingAverage { window } => {
+                if window == 0 {
+                    return Err(FilterError::InvalidParameters);
+                }
+                let taps = window / 2 * 2 + 1;
+                Ok(Design::Convolution(vec![1.0 / taps as f64; taps]))
+            }
+            Filter::Gaussian { sigma } => {
+                if sigma <= 0.0 || !sigma.is_finite() {
+                    return Err(FilterError::InvalidParameters);
+                }
+                let sigma = f64::from(sigma);
+                let radius = (3.0 * sigma).ceil() as isize;
+                let kernel = (-radius..=radius)
+                    .map(|i| (-0.5 * (i as f64 / sigma).powi(2)).exp())
+                    .collect::<Vec<_>>();
+                let sum = kernel.iter().sum::<f64>();
+                Ok(Design::Convolution(
+                    kernel.iter().map(|k| k / sum).collect(),
+                ))
+            }
+            Filter::SavitzkyGolay { window, order } => {
+                let taps = window / 2 * 2 + 1;
+                if window == 0 || order >= taps {
+                    return Err(FilterError::InvalidParameters);
+                }
+                savitzky_golay(taps, order)
+                    .map(Design::Convolution)
+                    .ok_or(FilterError::InvalidParameters)
+            }
+            Filter::Butterworth { cutoff, order } => {
+                if order == 0 {
+                    return Err(FilterError::InvalidParameters);
+                }
+                if frame_time <= 0.0 {
+                    return Err(FilterError::InvalidFrameTime);
+                }
+                let nyquist = 0.5 / frame_time;
+                if cutoff <= 0.0 || !cutoff.is_finite() || f64::from(cutoff) >= nyquist {
+                    return Err(FilterError::InvalidCutoff {
+                        cutoff,
+                        nyquist: nyquist as f32,
+                    });
+                }
+                let cutoff = f64::from(cutoff) * frame_time;
+                let pad = ((3.0 / cutoff).ceil() as usize).max(3 * (order + 1));
+                Ok(Design::ZeroPhase(butterworth(order, cutoff), pad))
+            }
+        }
+    }
+
+    /// Filters `signal` in place.
+    fn apply(&self, signal: &mut [f32]) {
+        let n = signal.len();
+        if n < 2 {
+            return;
+        }
+
+        match *self {
+            Design::Convolution(ref kernel) => {
+                let radius = (kernel.len() / 2) as isize;
+                let padded = reflect_pad(signal, radius as usize);
+                for (i, value) in signal.iter_mut().enumerate() {
+                    *value = kernel
+                        .iter()
+                        .zip(&padded[i..i + kernel.len()])
+                        .map(|(k, x)| k * x)
+                        .sum::<f64>() as f32;
+                }
+            }
+            Design::ZeroPhase(ref sections, pad) => {
+                let pad = pad.min(n - 1);
+                let mut padded = reflect_pad(signal, pad);
+
+                for section in sections {
+                    section.run(&mut padded);
+                }
+                padded.reverse();
+                for section in sections {
+                    section.run(&mut padded);
+                }
+                padded.reverse();
+
+                for (value, filtered) in signal.iter_mut().zip(&padded[pad..]) {
+                    *value = *filtered as f32;
+                }
+            }
+        }
+    }
+}
+
+/// Extends `signal` by `pad` values at each end, by reflecting it through its
+/// first and last values.
+fn reflect_pad(signal: &[f32], pad: usize) -> Vec<f64> {
+    let n = signal.len();
+    let (first, last) = (f64::from(signal[0]), f64::from(signal[n - 1]));
+    let at = |i: usize| f64::from(signal[i.min(n - 1)]);
+
+    let mut padded = Vec::with_capacity(n + 2 * pad);
+    padded.extend((1..=pad).rev().map(|i| 2.0 * first - at(i)));
+    padded.extend(signal.iter().map(|&x| f64::from(x)));
+    padded.extend((1..=pad).map(|i| 2.0 * last - at((n - 1).saturating_sub(i))));
+    padded
+}
+
+/// Returns the convolution kernel which evaluates a least-squares polynomial
+/// fit of degree `order` over `taps` values at the centre value.
+fn savitzky_golay(taps: usize, order: usize) -> Option<Vec<f64>> {
+    let m = (taps / 2) as f64;
+    let size = order + 1;
+
+    // Solve (A^T A) y = e_0, where A[i][j] = (i - m)^j.
+    let mut system = vec![vec![0.0f64; size + 1]; size];
+    for (row, equation) in system.iter_mut().enumerate() {
+        for (col, value) in equation[..size].iter_mut().enumerate() {
+            *value = (0..taps)
+                .map(|i| (i as f64 - m).powi((row + col) as i32))
+                .sum();
+        }
+        equation[size] = if row == 0 { 1.0 } else { 0.0 };
+    }
+
+    for col in 0..size {
+        let pivot = (col..size).max_by(|&a, &b| {
+            system[a][col]
+                .abs()
+                .partial_cmp(&system[b][col].abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if system[pivot][col].abs() < 1.0e-12 {
+            return None;
+        }
+        system.swap(col, pivot);
+
+        let pivot_row = system[col].clone();
+        for (row, equation) in system.iter_mut().enumerate() {
+            if row != col {
+                let factor = equation[col] / pivot_row[col];
+                for (value, pivot_value) in equation[col..].iter_mut().zip(&pivot_row[col..]) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let y = (0..size)
+        .map(|row| system[row][size] / system[row][row])
+        .collect::<Vec<_>>();
+
+    Some(
+        (0..taps)
+            .map(|i| {
+                let t = i as f64 - m;
+                y.iter()
+                    .enumerate()
+                    .map(|(j, coefficient)| coefficient * t.powi(j as i32))
+                    .sum()
+            })
+            .collect(),
+    )
+}
+
+/// A second order IIR filter section, with normalised coefficients.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Filters `signal` in place. The state is initialised as if the first
+    /// value had been repeated forever, to avoid a transient at the start.
+    fn run(&self, signal: &mut [f64]) {
+        let Biquad { b, a } = *self;
+        let x0 = signal[0];
+        let mut s2 = (b[2] - a[1]) * x0;
+        let mut s1 = (b[1] - a[0]) * x0 + s2;
+
+        for value in signal.iter_mut() {
+            let x = *value;
+            let y = b[0] * x + s1;
+            s1 = b[1] * x - a[0] * y + s2;
+            s2 = b[2] * x - a[1] * y;
+            *value = y;
+        }
+    }
+}
+
+/// Designs a Butterworth low-pass filter of `order` as a cascade of second
+/// order sections, with a cutoff of `cutoff` cycles per sample.
+fn butterworth(order: usize, cutoff: f64) -> Vec<Biquad> {
+    let w0 = 2.0 * PI * cutoff;
+    let (sin, cos) = w0.sin_cos();
+    let mut sections = Vec::with_capacity(order / 2 + order % 2);
+
+    for k in 0..order / 2 {
+        let q = 1.0 / (2.0 * ((2 * k + 1) as f64 * PI / (2 * order) as f64).sin());
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        sections.push(Biquad {
+            b: [
+                (1.0 - cos) / 2.0 / a0,
+                (1.0 - cos) / a0,
+                (1.0 - cos) / 2.0 / a0,
+            ],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        });
+    }
+
+    if order % 2 == 1 {
+        let k = (w0 / 2.0).tan();
+        let b0 = k / (1.0 + k);
+        sections.push(Biquad {
+            b: [b0, b0, 0.0],
+            a: [(k - 1.0) / (k + 1.0), 0.0],
+        });
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{butterworth, savitzky_golay, Design};
+    use crate::filter::Filter;
+
+    #[test]
+    fn savitzky_golay_coefficients() {
+        // The well known quadratic coefficients for a window of 5.
+        let expected = [-3.0, 12.0, 17.0, 12.0, -3.0];
+        let kernel = savitzky_golay(5, 2).unwrap();
+        for (k, e) in kernel.iter().zip(expected.iter()) {
+            assert!((k - e / 35.0).abs() < 1.0e-9, "{:?}", kernel);
+        }
+    }
+
+    #[test]
+    fn butterworth_unit_dc_gain() {
+        for order in 1..6 {
+            let mut signal = vec![2.0; 50];
+            for section in butterworth(order, 0.1) {
+                section.run(&mut signal);
+            }
+            assert!(signal.iter().all(|x| (x - 2.0).abs() < 1.0e-9));
+        }
+    }
+
+    #[test]
+    fn filters_preserve_lines() {
+        let filters = [
+            Filter::MovingAverage { window: 5 },
+            Filter::Gaussian { sigma: 2.0 },
+            Filter::SavitzkyGolay {
+                window: 7,
+                order: 2,
+            },
+            Filter::Butterworth {
+                cutoff: 5.0,
+                order: 2,
+            },
+        ];
+
+        for filter in &filters {
+            let design = Design::new(filter, 1.0 / 60.0).unwrap();
+            let mut signal = (0..40).map(|i| i as f32 * 0.5 - 3.0).collect::<Vec<_>>();
+            let original = signal.clone();
+            design.apply(&mut signal);
+            for (a, b) in signal.iter().zip(original.iter()) {
+                assert!((a - b).abs() < 1.0e-2, "{:?}: {} != {}", filter, a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! Editing the joint hierarchy of a `Bvh`.
+//!
+//! Joints can be renamed, removed, inserted and moved to a new parent. After
+//! each edit, the joints are stored in order again, so that every parent
+//! comes before its children, and the motion values are rearranged to match
+//! the new channels. The indices of the joints and the motion indices of
+//! their channels may change, so any `Channel` or index taken from the `Bvh`
+//! before an edit should be looked up again afterwards.
+//!
+//! A joint which is left without children is given an end site at the
+//! position of the joint which it lost, so that the hierarchy can still be
+//! written.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, hierarchy::InsertOptions};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 3 Zrotation Xrotation Yrotation
+//!         JOINT Arm
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             JOINT Hand
+//!             {
+//!                 OFFSET 0.0 10.0 0.0
+//!                 CHANNELS 3 Zrotation Xrotation Yrotation
+//!                 End Site
+//!                 {
+//!                     OFFSET 0.0 5.0 0.0
+//!                 }
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! // Add a twist joint halfway along the arm, and rename the hand.
+//! let twist = InsertOptions::new()
+//!     .with_offset([0.0, 5.0, 0.0].into())
+//!     .with_children(vec!["Hand"]);
+//! bvh.insert_joint("Arm", "ArmTwist", &twist).unwrap();
+//! bvh.rename_joint("Hand", "LeftHand").unwrap();
+//!
+//! let names = bvh.joints().map(|j| j.data().name().to_string()).collect::<Vec<_>>();
+//! assert_eq!(names, vec!["Hips", "Arm", "ArmTwist", "LeftHand"]);
+//! assert_eq!(bvh.num_channels(), 12);
+//!
+//! let hand = bvh.joints().find_by_name("LeftHand").unwrap();
+//! assert_eq!(hand.data().offset(), &[0.0, 5.0, 0.0].into());
+//! ```
+
+use crate::{
+    errors::HierarchyError,
+    joint::{joint_index_by_name, JointPrivateData},
+    kinematics::{
+        local_rotation, pose_preserved, position_channels, set_position_channels,
+        set_rotation_channels, world_transforms,
+    },
+    math::Vec3,
+    Bvh, Channel, ChannelType, JointData, JointName,
+};
+use bstr::{BString, ByteSlice};
+use mint::Vector3;
+use smallvec::SmallVec;
+
+/// Specify options for inserting a new joint into a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InsertOptions {
+    /// The offset of the new joint from its parent. Defaults to zero.
+    pub offset: Vector3<f32>,
+    /// The channels of the new joint, in order. Defaults to `Zrotation
+    /// Xrotation Yrotation`.
+    pub channels: Vec<ChannelType>,
+    /// The names of the children of the parent which are moved under the new
+    /// joint. Their offsets are adjusted so that they stay in place.
+    /// Defaults to no children.
+    pub children: Vec<JointName>,
+    /// The end site of the new joint, if it has no children. Defaults to
+    /// `None`, which places the end site at the joint itself.
+    pub end_site: Option<Vector3<f32>>,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for InsertOptions {
+    #[inline]
+    fn default() -> Self {
+        InsertOptions {
+            offset: [0.0, 0.0, 0.0].into(),
+            channels: vec![
+                ChannelType::RotationZ,
+                ChannelType::RotationX,
+                ChannelType::RotationY,
+            ],
+            children: Vec::new(),
+            end_site: None,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl InsertOptions {
+    /// Create a new `InsertOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `offset` on `self` to the new offset.
+    #[inline]
+    pub fn with_offset(self, offset: Vector3<f32>) -> Self {
+        InsertOptions { offset, ..self }
+    }
+
+    /// Sets `channels` on `self` to the new channel types.
+    #[inline]
+    pub fn with_channels(self, channels: Vec<ChannelType>) -> Self {
+        InsertOptions { channels, ..self }
+    }
+
+    /// Sets `children` on `self` to the joints called `names`.
+    pub fn with_children<I, S>(self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<JointName>,
+    {
+        InsertOptions {
+            children: names.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets `end_site` on `self` to the new end site.
+    #[inline]
+    pub fn with_end_site(self, end_site: Option<Vector3<f32>>) -> Self {
+        InsertOptions { end_site, ..self }
+    }
+}
+
+impl Bvh {
+    /// Renames the joint called `name` to `new_name`.
+    ///
+    /// Returns an error if there is no joint called `name`, if `new_name` is
+    /// empty or contains whitespace, or if another joint is already called
+    /// `new_name`.
+    pub fn rename_joint<S: Into<JointName>>(
+        &mut self,
+        name: &str,
+        new_name: S,
+    ) -> Result<(), HierarchyError> {
+        let new_name = new_name.into();
+        let index = find_joint(&self.joints, name)?;
+        check_name(&new_name)?;
+        let taken = self
+            .joints
+            .iter()
+            .enumerate()
+            .any(|(i, joint)| i != index && new_name == joint.name());
+        if taken {
+            return Err(HierarchyError::DuplicateJoint {
+                name: BString::from(new_name.to_vec()),
+            });
+        }
+
+        self.joints[index].set_name(new_name);
+        Ok(())
+    }
+
+    /// Removes the joint called `name`, and moves its children to its parent.
+    ///
+    /// The offsets of the children are adjusted so that the rest pose of the
+    /// skeleton is unchanged. If `bake` is `true`, the motion of the removed
+    /// joint is baked into the motion of each child, so that the world
+    /// rotations and positions of the children are unchanged. Otherwise, the
+    /// motion of the removed joint is discarded.
+    ///
+    /// Returns an error without changing the `Bvh` if there is no joint called
+    /// `name`, if it is the root joint, or if `bake` is `true` and the
+    /// channels of a child cannot describe its new pose in a frame. This is
+    /// the case for a child without position channels, when the removed
+    /// joint rotates or moves it.
+    pub fn remove_joint(&mut self, name: &str, bake: bool) -> Result<(), HierarchyError> {
+        let index = find_joint(&self.joints, name)?;
+        let mut hierarchy = Hierarchy::new(&self.joints);
+        let parent = hierarchy.nodes[index]
+            .parent
+            .ok_or(HierarchyError::RootJoint)?;
+        let children = hierarchy.children(index);
+
+        let joint = &self.joints[index];
+        let offset = Vec3::from(joint.offset());
+        if bake {
+            let mut motion_values = self.motion_values.clone();
+            let frames = motion_values
+                .chunks_mut(self.num_channels.max(1))
+                .take(self.num_frames);
+            for (f, frame) in frames.enumerate() {
+                let rotation = local_rotation(joint, frame);
+                let translation = offset + position_channels(joint, frame);
+                for &child in &children {
+                    let child = &self.joints[child];
+                    let child_translation =
+                        Vec3::from(child.offset()) + position_channels(child, frame);
+                    let child_rotation = local_rotation(child, frame);
+
+                    let new_offset = offset + Vec3::from(child.offset());
+                    let new_rotation = rotation * child_rotation;
+                    let new_position =
+                        translation + rotation.rotate(child_translation) - new_offset;
+                    set_rotation_channels(child.channels(), frame, new_rotation);
+                    set_position_channels(child.channels(), frame, new_position);
+                    if !pose_preserved(child.channels(), frame, new_rotation, new_position) {
+                        return Err(HierarchyError::PoseNotPreserved {
+                            name: BString::from(child.name().as_bytes()),
+                            frame: f,
+                        });
+                    }
+                }
+            }
+            self.motion_values = motion_values;
+        }
+
+        for &child in &children {
+            let node = &mut hierarchy.nodes[child];
+            node.offset = offset + node.offset;
+            node.parent = Some(parent);
+        }
+        hierarchy.nodes[index].removed = true;
+        hierarchy.cap_leaf(parent, offset);
+        hierarchy.build(self);
+        Ok(())
+    }
+
+    /// Inserts a new joint called `name` as a child of the joint called
+    /// `parent`, as described by `options`.
+    ///
+    /// The channels of the new joint are zero in every frame, so that the
+    /// joint does not change the motion of the skeleton. The children of
+    /// `parent` which are listed in `options` are moved under the new joint.
+    ///
+    /// Returns an error if there is no joint called `parent`, if `name` is
+    /// empty or contains whitespace, if a joint is already called `name`, or
+    /// if a listed child is not a child of `parent`.
+    pub fn insert_joint<S: Into<JointName>>(
+        &mut self,
+        parent: &str,
+        name: S,
+        options: &InsertOptions,
+    ) -> Result<(), HierarchyError> {
+        let name = name.into();
+        let parent_index = find_joint(&self.joints, parent)?;
+        check_name(&name)?;
+        if self.joints.iter().any(|joint| name == joint.name()) {
+            return Err(HierarchyError::DuplicateJoint {
+                name: BString::from(name.to_vec()),
+            });
+        }
+
+        let mut hierarchy = Hierarchy::new(&self.joints);
+        let mut children = Vec::with_capacity(options.children.len());
+        for child in &options.children {
+            let index = joint_index_by_name(&self.joints, &child[..], |name| {
+                HierarchyError::MissingJoint { name }
+            })?;
+            if hierarchy.nodes[index].parent != Some(parent_index) {
+                return Err(HierarchyError::NotAChild {
+                    name: BString::from(child.to_vec()),
+                    parent: BString::from(parent),
+                });
+            }
+            children.push(index);
+        }
+
+        let offset = Vec3::from(options.offset);
+        let index = hierarchy.nodes.len();
+        hierarchy.nodes.push(Node {
+            name,
+            offset,
+            end_site: options.end_site,
+            parent: Some(parent_index),
+            channels: options.channels.iter().map(|&ty| (ty, None)).collect(),
+            removed: false,
+        });
+        for child in children {
+            let node = &mut hierarchy.nodes[child];
+            node.offset = node.offset - offset;
+            node.parent = Some(index);
+        }
+
+        hierarchy.build(self);
+        Ok(())
+    }
+
+    /// Moves the joint called `name`, and all of its descendants, to become
+    /// a child of the joint called `new_parent`.
+    ///
+    /// The offset of the joint is changed so that its position in the rest
+    /// pose is unchanged. In each frame, its rotation and position channels
+    /// are changed so that its world rotation and position are unchanged.
+    ///
+    /// Returns an error without changing the `Bvh` if either joint does not
+    /// exist, if the joint is the root joint, if `new_parent` is the joint or
+    /// one of its descendants, or if the channels of the joint cannot describe
+    /// its new pose in a frame. This is the case for a joint without position
+    /// channels, when its position relative to `new_parent` changes.
+    pub fn reparent_joint(&mut self, name: &str, new_parent: &str) -> Result<(), HierarchyError> {
+        let index = find_joint(&self.joints, name)?;
+        let parent = find_joint(&self.joints, new_parent)?;
+        let mut hierarchy = Hierarchy::new(&self.joints);
+        let old_parent = hierarchy.nodes[index]
+            .parent
+            .ok_or(HierarchyError::RootJoint)?;
+        if hierarchy.is_descendant(parent, index) {
+            return Err(HierarchyError::InvalidParent {
+                name: BString::from(name),
+                parent: BString::from(new_parent),
+            });
+        }
+
+        let rest = world_transforms(&self.joints, &vec![0.0; self.num_channels]);
+        let offset = rest[index].translation - rest[parent].translation;
+
+        let joint = &self.joints[index];
+        let mut motion_values = self.motion_values.clone();
+        let frames = motion_values
+            .chunks_mut(self.num_channels.max(1))
+            .take(self.num_frames);
+        for (f, frame) in frames.enumerate() {
+            let world = world_transforms(&self.joints, frame);
+            let local = world[parent].inverse() * world[index];
+            let position = local.translation - offset;
+            set_rotation_channels(joint.channels(), frame, local.rotation);
+            set_position_channels(joint.channels(), frame, position);
+            if !pose_preserved(joint.channels(), frame, local.rotation, position) {
+                return Err(HierarchyError::PoseNotPreserved {
+                    name: BString::from(name),
+                    frame: f,
+                });
+            }
+        }
+        self.motion_values = motion_values;
+
+        let old_offset = hierarchy.nodes[index].offset;
+        let node = &mut hierarchy.nodes[index];
+        node.offset = offset;
+        node.parent = Some(parent);
+        hierarchy.cap_leaf(old_parent, old_offset);
+        hierarchy.build(self);
+        Ok(())
+    }
+}
+
+/// Returns the index of the joint called `name` in `joints`.
+fn find_joint(joints: &[JointData], name: &str) -> Result<usize, HierarchyError> {
+    joint_index_by_name(joints, name.as_bytes(), |name| {
+        HierarchyError::MissingJoint { name }
+    })
+}
+
+/// Returns an error if `name` cannot be written as the name of a joint.
+fn check_name(name: &JointName) -> Result<(), HierarchyError> {
+    if name.is_empty() || name.iter().any(u8::is_ascii_whitespace) {
+        return Err(HierarchyError::InvalidName {
+            name: BString::from(name.to_vec()),
+        });
+    }
+    Ok(())
+}
+
+/// A joint of a hierarchy which is being edited.
+#[derive(Clone, Debug)]
+pub(crate) struct Node {
+    pub(crate) name: JointName,
+    pub(crate) offset: Vec3,
+    pub(crate) end_site: Option<Vector3<f32>>,
+    pub(crate) parent: Option<usize>,
+    /// The type of each channel, and the motion index of its values in the
+    /// original `Bvh`, or `None` if it is a new channel.
+    pub(crate) channels: SmallVec<[(ChannelType, Option<usize>); 6]>,
+    pub(crate) removed: bool,
+}
+
+/// The joints of a `Bvh`, in a form which can be freely edited, and then
+/// rebuilt into a `Bvh`.
+#[derive(Clone, Debug)]
+pub(crate) struct Hierarchy {
+    pub(crate) nodes: Vec<Node>,
+}
+
+impl Hierarchy {
+    pub(crate) fn new(joints: &[JointData]) -> Self {
+        let nodes = joints
+            .iter()
+            .map(|joint| Node {
+                name: JointName::from(joint.name().as_bytes()),
+                offset: Vec3::from(joint.offset()),
+                end_site: joint.end_site().cloned(),
+                parent: joint.parent_index(),
+                channels: joint
+                    .channels()
+                    .iter()
+                    .map(|c| (c.channel_type(), Some(c.motion_index())))
+                    .collect(),
+                removed: false,
+            })
+            .collect();
+        Hierarchy { nodes }
+    }
+
+    /// Returns the indices of the children of the node at `index`.
+    fn children(&self, index: usize) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.removed && node.parent == Some(index))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Returns `true` if the node at `index` is `ancestor` or one of its
+    /// descendants.
+    pub(crate) fn is_descendant(&self, index: usize, ancestor: usize) -> bool {
+        let mut current = Some(index);
+        while let Some(i) = current {
+            if i == ancestor {
+                return true;
+            }
+            current = self.nodes[i].parent;
+        }
+        false
+    }
+
+    /// Gives the node at `index` an end site at `tip` if it has no children
+    /// left and is not the root.
+    fn cap_leaf(&mut self, index: usize, tip: Vec3) {
+        let node = &self.nodes[index];
+        if node.parent.is_some() && node.end_site.is_none() && self.children(index).is_empty() {
+            self.nodes[index].end_site = Some(tip.into());
+        }
+    }
+
+    /// Replaces the joints and motion values of `bvh` with the edited
+    /// hierarchy.
+    pub(crate) fn build(self, bvh: &mut Bvh) {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        if let Some(root) = self
+            .nodes
+            .iter()
+            .position(|node| !node.removed && node.parent.is_none())
+        {
+            self.visit(root, &mut order);
+        }
+
+        let mut new_indices = vec![0; self.nodes.len()];
+        for (new_index, &index) in order.iter().enumerate() {
+            new_indices[index] = new_index;
+        }
+
+        let mut joints: Vec<JointData> = Vec::with_capacity(order.len());
+        let mut columns = Vec::new();
+        for (new_index, &index) in order.iter().enumerate() {
+            let node = &self.nodes[index];
+            let mut joint = match node.parent {
+                Some(parent) => {
+                    let parent = new_indices[parent];
+                    let depth = joints[parent].depth() + 1;
+                    let mut joint = JointData::empty_child();
+                    if let Some(private) = joint.private_data_mut() {
+                        *private = JointPrivateData::new(new_index, parent, depth);
+                    }
+                    joint
+                }
+                None => JointData::empty_root(),
+            };
+
+            let channels = node
+                .channels
+                .iter()
+                .map(|&(ty, column)| {
+                    columns.push(column);
+                    Channel::new(ty, columns.len() - 1)
+                })
+                .collect();
+
+            joint.set_name(node.name.clone());
+            joint.set_offset(node.offset.into(), false);
+            if joint.is_child() && self.children(index).is_empty() {
+                let end_site = node.end_site.unwrap_or_else(|| [0.0, 0.0, 0.0].into());
+                joint.set_offset(end_site, true);
+            }
+            joint.set_channels(channels);
+            joints.push(joint);
+        }
+
+        let mut motion_values = Vec::with_capacity(bvh.num_frames * columns.len());
+        for frame in 0..bvh.num_frames {
+            let frame = &bvh.motion_values[frame * bvh.num_channels..][..bvh.num_channels];
+            motion_values.extend(columns.iter().map(|c| c.map(|c| frame[c]).unwrap_or(0.0)));
+        }
+
+        bvh.joints = joints;
+        bvh.num_channels = columns.len();
+        bvh.motion_values = motion_values;
+    }
+
+    /// Pushes the node at `index`, followed by its descendants, onto `order`.
+    fn visit(&self, index: usize, order: &mut Vec<usize>) {
+        order.push(index);
+        for child in self.children(index) {
+            self.visit(child, order);
+        }
+    }
+}
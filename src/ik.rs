@@ -0,0 +1,533 @@
+//! Inverse kinematics solvers for chains of joints.
+//!
+//! A chain is identified by the names of its first and last joints, where
+//! the last joint must be a descendant of the first. The solvers rotate the
+//! joints of the chain so that the last joint reaches a target position in
+//! world space, and produce new values for the rotation channels of each
+//! joint, in the order in which the channels are listed.
+//!
+//! Three solvers are available:
+//!
+//! * `Solver::TwoBone` solves chains of exactly three joints, such as a
+//!   hip, knee and foot, analytically.
+//! * `Solver::Ccd` (cyclic coordinate descent) rotates each joint in turn,
+//!   from the end of the chain to the start, to point the end at the target.
+//! * `Solver::Fabrik` (forward and backward reaching inverse kinematics)
+//!   moves the joint positions towards the target while keeping the bone
+//!   lengths, and then rotates each joint to match.
+//!
+//! The rotation channels of each joint can be limited with `RotationLimit`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, ik::{IkOptions, Solver}};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT UpLeg
+//!         {
+//!             OFFSET 0.0 0.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             JOINT Leg
+//!             {
+//!                 OFFSET 0.0 -10.0 1.0
+//!                 CHANNELS 3 Zrotation Xrotation Yrotation
+//!                 JOINT Foot
+//!                 {
+//!                     OFFSET 0.0 -10.0 -1.0
+//!                     CHANNELS 3 Zrotation Xrotation Yrotation
+//!                     End Site
+//!                     {
+//!                         OFFSET 0.0 0.0 2.0
+//!                     }
+//!                 }
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 20.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! IkOptions::new()
+//!     .with_chain("UpLeg", "Foot")
+//!     .with_solver(Solver::TwoBone)
+//!     .apply(&mut bvh, 0, [0.0, 5.0, 5.0].into())
+//!     .unwrap();
+//!
+//! let foot = bvh.world_positions(0).unwrap()[3];
+//! assert!((foot.y - 5.0).abs() < 1.0e-3);
+//! assert!((foot.z - 5.0).abs() < 1.0e-3);
+//! ```
+
+use crate::{
+    errors::IkError,
+    joint::joint_index_by_name,
+    kinematics::{set_rotation_channels, world_transforms},
+    math::{wrap_degrees, Quat, Transform, Vec3, EPSILON},
+    Axis, Bvh, JointData, JointName,
+};
+use bstr::BString;
+use mint::Vector3;
+
+/// The algorithm used to solve a chain of joints.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Solver {
+    /// Analytic two-bone inverse kinematics, for chains of exactly three
+    /// joints. The chain bends in the plane in which it is already bent.
+    TwoBone,
+    /// Cyclic coordinate descent.
+    Ccd,
+    /// Forward and backward reaching inverse kinematics.
+    Fabrik,
+}
+
+impl Default for Solver {
+    #[inline]
+    fn default() -> Self {
+        Solver::Fabrik
+    }
+}
+
+/// Limits on the values of the rotation channels of a joint, in degrees.
+///
+/// Each rotation channel of the joint is wrapped into the range `(-180, 180]`,
+/// and then clamped to the range given for its axis. The minimum of each axis
+/// must not be greater than its maximum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RotationLimit {
+    /// The name of the joint.
+    pub joint: JointName,
+    /// The smallest value of the `X`, `Y` and `Z` rotation channels.
+    pub min: Vector3<f32>,
+    /// The largest value of the `X`, `Y` and `Z` rotation channels.
+    pub max: Vector3<f32>,
+}
+
+impl RotationLimit {
+    /// Create a new `RotationLimit` for the joint named `joint`.
+    #[inline]
+    pub fn new<S: Into<JointName>>(joint: S, min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        RotationLimit {
+            joint: joint.into(),
+            min,
+            max,
+        }
+    }
+}
+
+/// Specify options for solving a chain of joints of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IkOptions {
+    /// The name of the first joint of the chain.
+    pub start: JointName,
+    /// The name of the last joint of the chain, which is moved to the target.
+    pub end: JointName,
+    /// The algorithm used to solve the chain. Defaults to `Solver::Fabrik`.
+    pub solver: Solver,
+    /// The greatest number of iterations of the `Ccd` and `Fabrik` solvers.
+    /// Defaults to `20`.
+    pub iterations: usize,
+    /// The distance from the target at which the `Ccd` and `Fabrik` solvers
+    /// stop iterating. Defaults to `1.0e-3`.
+    pub tolerance: f32,
+    /// Limits on the rotations of the joints of the chain.
+    pub limits: Vec<RotationLimit>,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for IkOptions {
+    #[inline]
+    fn default() -> Self {
+        IkOptions {
+            start: Default::default(),
+            end: Default::default(),
+            solver: Default::default(),
+            iterations: 20,
+            tolerance: 1.0e-3,
+            limits: Vec::new(),
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl IkOptions {
+    /// Create a new `IkOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `start` and `end` on `self` to the names of the first and last
+    /// joints of the chain.
+    #[inline]
+    pub fn with_chain<S, E>(self, start: S, end: E) -> Self
+    where
+        S: Into<JointName>,
+        E: Into<JointName>,
+    {
+        IkOptions {
+            start: start.into(),
+            end: end.into(),
+            ..self
+        }
+    }
+
+    /// Sets `solver` on `self` to the new `Solver`.
+    #[inline]
+    pub fn with_solver(self, solver: Solver) -> Self {
+        IkOptions { solver, ..self }
+    }
+
+    /// Sets `iterations` on `self` to the new number of iterations.
+    #[inline]
+    pub fn with_iterations(self, iterations: usize) -> Self {
+        IkOptions { iterations, ..self }
+    }
+
+    /// Sets `tolerance` on `self` to the new distance.
+    #[inline]
+    pub fn with_tolerance(self, tolerance: f32) -> Self {
+        IkOptions { tolerance, ..self }
+    }
+
+    /// Adds `limit` to the `limits` of `self`.
+    #[inline]
+    pub fn with_limit(mut self, limit: RotationLimit) -> Self {
+        self.limits.push(limit);
+        self
+    }
+
+    /// Solves the chain so that its last joint reaches `target` at `frame`
+    /// of `bvh`.
+    ///
+    /// Returns a copy of the motion values of `frame`, in which the rotation
+    /// channels of the joints of the chain have been changed. Targets out of
+    /// reach are approached as closely as possible.
+    pub fn solve(
+        &self,
+        bvh: &Bvh,
+        frame: usize,
+        target: Vector3<f32>,
+    ) -> Result<Vec<f32>, IkError> {
+        let chain = self.find_chain(bvh)?;
+        let limits = self.find_limits(bvh)?;
+        let mut values = bvh
+            .frames()
+            .nth(frame)
+            .ok_or(IkError::FrameOutOfBounds { frame })?
+            .as_slice()
+            .to_vec();
+
+        let world = world_transforms(&bvh.joints, &values);
+        let mut solver = ChainSolver {
+            joints: &bvh.joints,
+            chain: &chain,
+            limits: &limits,
+            frame: &mut values,
+            world,
+        };
+        let target = Vec3::from(target);
+
+        match self.solver {
+            Solver::TwoBone => {
+                if chain.len() != 3 {
+                    return Err(IkError::InvalidChainLength { len: chain.len() });
+                }
+                solver.two_bone(target);
+            }
+            Solver::Ccd => solver.ccd(target, self.iterations, self.tolerance),
+            Solver::Fabrik => solver.fabrik(target, self.iterations, self.tolerance),
+        }
+
+        Ok(values)
+    }
+
+    /// Solves the chain as in [`solve`][`solve`], and writes the new
+    /// motion values to `frame` of `bvh`.
+    ///
+    /// [`solve`]: #method.solve
+    pub fn apply(&self, bvh: &mut Bvh, frame: usize, target: Vector3<f32>) -> Result<(), IkError> {
+        let values = self.solve(bvh, frame, target)?;
+        let num_channels = bvh.num_channels;
+        bvh.motion_values[frame * num_channels..][..num_channels].copy_from_slice(&values);
+        Ok(())
+    }
+
+    /// Returns the indices of the joints of the chain, from `start` to `end`.
+    fn find_chain(&self, bvh: &Bvh) -> Result<Vec<usize>, IkError> {
+        let start = find_joint(bvh, &self.start)?;
+        let end = find_joint(bvh, &self.end)?;
+
+        let mut chain = vec![end];
+        let mut current = end;
+        while current != start {
+            current = bvh.joints[current]
+                .parent_index()
+                .ok_or_else(|| IkError::NotAChain {
+                    start: BString::from(self.start.to_vec()),
+                    end: BString::from(self.end.to_vec()),
+                })?;
+            chain.push(current);
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// Returns the limits of each joint, indexed by joint index.
+    fn find_limits(&self, bvh: &Bvh) -> Result<Vec<Option<(Vec3, Vec3)>>, IkError> {
+        let mut limits = vec![None; bvh.joints.len()];
+        for limit in &self.limits {
+            let joint = find_joint(bvh, &limit.joint)?;
+            let (min, max) = (Vec3::from(limit.min), Vec3::from(limit.max));
+            let ordered = [Axis::X, Axis::Y, Axis::Z]
+                .iter()
+                .all(|&axis| min.get(axis) <= max.get(axis));
+            if !ordered {
+                return Err(IkError::InvalidLimit {
+                    name: BString::from(limit.joint.to_vec()),
+                });
+            }
+            limits[joint] = Some((min, max));
+        }
+        Ok(limits)
+    }
+}
+
+/// Returns the index of the joint called `name`.
+fn find_joint(bvh: &Bvh, name: &JointName) -> Result<usize, IkError> {
+    joint_index_by_name(&bvh.joints, &name[..], |name| IkError::MissingJoint {
+        name,
+    })
+}
+
+/// The state of a chain while it is being solved.
+struct ChainSolver<'a> {
+    joints: &'a [JointData],
+    chain: &'a [usize],
+    limits: &'a [Option<(Vec3, Vec3)>],
+    frame: &'a mut [f32],
+    /// The world transform of each joint in `frame`, which is updated
+    /// whenever a joint is rotated.
+    world: Vec<Transform>,
+}
+
+impl ChainSolver<'_> {
+    fn positions(&self) -> Vec<Vec3> {
+        self.chain
+            .iter()
+            .map(|&j| self.world[j].translation)
+            .collect()
+    }
+
+    fn end_position(&self) -> Vec3 {
+        let end = self.chain[self.chain.len() - 1];
+        self.world[end].translation
+    }
+
+    /// Applies the world-space rotation `delta` to the joint `joint`,
+    /// respecting its limits.
+    fn rotate(&mut self, joint: usize, delta: Quat) {
+        let parent = self.joints[joint]
+            .parent_index()
+            .map(|parent| self.world[parent].rotation)
+            .unwrap_or_else(Quat::identity);
+        let rotation = delta * self.world[joint].rotation;
+        set_rotation_channels(
+            self.joints[joint].channels(),
+            self.frame,
+            parent.conjugate() * rotation,
+        );
+
+        if let Some((min, max)) = self.limits[joint] {
+            for channel in self.joints[joint].channels() {
+                let ty = channel.channel_type();
+                if ty.is_rotation() {
+                    let value = &mut self.frame[channel.motion_index()];
+                    *value = wrap_degrees(*value).clamp(min.get(ty.axis()), max.get(ty.axis()));
+                }
+            }
+        }
+
+        self.world = world_transforms(self.joints, self.frame);
+    }
+
+    fn two_bone(&mut self, target: Vec3) {
+        let positions = self.positions();
+        let (upper, lower) = two_bone(positions[0], positions[1], positions[2], target);
+
+        let lower_rotation = lower * self.world[self.chain[1]].rotation;
+        self.rotate(self.chain[0], upper);
+
+        let delta = lower_rotation * self.world[self.chain[1]].rotation.conjugate();
+        self.rotate(self.chain[1], delta);
+    }
+
+    fn ccd(&mut self, target: Vec3, iterations: usize, tolerance: f32) {
+        for _ in 0..iterations {
+            for i in (0..self.chain.len() - 1).rev() {
+                let joint = self.chain[i];
+                let pivot = self.world[joint].translation;
+                let end = self.end_position();
+                self.rotate(joint, Quat::from_to(end - pivot, target - pivot));
+            }
+
+            if self.end_position().distance(target) <= tolerance {
+                break;
+            }
+        }
+    }
+
+    fn fabrik(&mut self, target: Vec3, iterations: usize, tolerance: f32) {
+        for _ in 0..iterations {
+            let mut positions = self.positions();
+            let root = positions[0];
+            let lengths = positions
+                .windows(2)
+                .map(|bone| bone[0].distance(bone[1]))
+                .collect::<Vec<_>>();
+            let last = positions.len() - 1;
+
+            if target.distance(root) >= lengths.iter().sum::<f32>() {
+                for i in 0..last {
+                    let direction = (target - positions[i]).normalize();
+                    positions[i + 1] = positions[i] + direction * lengths[i];
+                }
+            } else {
+                positions[last] = target;
+                for i in (0..last).rev() {
+                    let direction = (positions[i] - positions[i + 1]).normalize();
+                    positions[i] = positions[i + 1] + direction * lengths[i];
+                }
+
+                positions[0] = root;
+                for i in 0..last {
+                    let direction = (positions[i + 1] - positions[i]).normalize();
+                    positions[i + 1] = positions[i] + direction * lengths[i];
+                }
+            }
+
+            for i in 0..last {
+                let (start, end) = (self.chain[i], self.chain[i + 1]);
+                let current = self.world[start].translation;
+                let from = self.world[end].translation - current;
+                let to = positions[i + 1] - current;
+                if from.length_squared() > EPSILON && to.length_squared() > EPSILON {
+                    self.rotate(self.chain[i], Quat::from_to(from, to));
+                }
+            }
+
+            if self.end_position().distance(target) <= tolerance {
+                break;
+            }
+        }
+    }
+}
+
+/// Solves a chain of two bones, from `root` through `mid` to `end`, so that
+/// `end` reaches `target`.
+///
+/// Returns the world-space rotations which must be applied to the world
+/// rotations of the upper and lower joints of the chain, so that the new
+/// world rotation of the upper joint is `upper * old_upper`, and of the lower
+/// joint is `lower * old_lower`. Targets out of reach are approached as
+/// closely as possible by straightening the chain.
+pub(crate) fn two_bone(root: Vec3, mid: Vec3, end: Vec3, target: Vec3) -> (Quat, Quat) {
+    let upper_length = mid.distance(root);
+    let lower_length = end.distance(mid);
+    if upper_length <= EPSILON || lower_length <= EPSILON {
+        return (Quat::identity(), Quat::identity());
+    }
+
+    let margin = 1.0e-4 * (upper_length + lower_length);
+    let shortest = (upper_length - lower_length).abs() + margin;
+    let longest = (upper_length + lower_length - margin).max(shortest);
+    let target_length = target.distance(root).clamp(shortest, longest);
+
+    let current_root = angle_between(end - root, mid - root);
+    let current_mid = angle_between(root - mid, end - mid);
+    let desired_root = law_of_cosines(upper_length, target_length, lower_length);
+    let desired_mid = law_of_cosines(upper_length, lower_length, target_length);
+
+    let mut axis = (end - root).cross(mid - root);
+    if axis.length_squared() <= EPSILON {
+        axis = (end - root).cross(Vec3::axis(Axis::Z));
+        if axis.length_squared() <= EPSILON {
+            axis = (end - root).cross(Vec3::axis(Axis::X));
+        }
+    }
+    let axis = axis.normalize();
+
+    let bend_root = Quat::from_axis_angle(axis, desired_root - current_root);
+    let bend_mid = Quat::from_axis_angle(axis, desired_mid - current_mid);
+
+    let new_mid = root + bend_root.rotate(mid - root);
+    let new_end = new_mid + (bend_root * bend_mid).rotate(end - mid);
+    let swing = Quat::from_to(new_end - root, target - root);
+
+    let upper = swing * bend_root;
+    (upper, upper * bend_mid)
+}
+
+/// Returns the angle in radians between `a` and `b`.
+#[inline]
+fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    a.normalize().dot(b.normalize()).clamp(-1.0, 1.0).acos()
+}
+
+/// Returns the angle in radians between the sides `a` and `b` of a triangle
+/// whose third side is `c`.
+#[inline]
+fn law_of_cosines(a: f32, b: f32, c: f32) -> f32 {
+    ((a * a + b * b - c * c) / (2.0 * a * b))
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::two_bone;
+    use crate::math::Vec3;
+
+    fn solve(root: Vec3, mid: Vec3, end: Vec3, target: Vec3) -> (Vec3, Vec3) {
+        let (upper, lower) = two_bone(root, mid, end, target);
+        let new_mid = root + upper.rotate(mid - root);
+        let new_end = new_mid + lower.rotate(end - mid);
+        (new_mid, new_end)
+    }
+
+    #[test]
+    fn two_bone_reaches_target() {
+        let root = Vec3::new(0.0, 10.0, 0.0);
+        let mid = Vec3::new(0.0, 5.0, 0.5);
+        let end = Vec3::new(0.0, 0.0, 0.0);
+
+        for &target in &[
+            Vec3::new(1.0, 1.0, 2.0),
+            Vec3::new(-2.0, 3.0, 0.0),
+            Vec3::new(0.0, 2.0, -3.0),
+        ] {
+            let (new_mid, new_end) = solve(root, mid, end, target);
+            assert!(new_end.distance(target) < 1.0e-3, "{:?}", new_end);
+            assert!((new_mid.distance(root) - mid.distance(root)).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn two_bone_straightens_towards_distant_target() {
+        let root = Vec3::new(0.0, 0.0, 0.0);
+        let mid = Vec3::new(1.0, 0.0, 0.0);
+        let end = Vec3::new(2.0, 0.0, 0.0);
+        let target = Vec3::new(0.0, 10.0, 0.0);
+
+        let (_, new_end) = solve(root, mid, end, target);
+        assert!(new_end.distance(Vec3::new(0.0, 2.0, 0.0)) < 1.0e-2);
+    }
+}
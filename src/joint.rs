@@ -334,9 +334,10 @@ impl JointData {
         }
     }
 
-    /// Get the depth of the `JointData` in the heirarchy.
+    /// Get the depth of the `JointData` in the heirarchy, where the root
+    /// joint has a depth of `0`.
     #[inline]
-    pub(crate) fn depth(&self) -> usize {
+    pub fn depth(&self) -> usize {
         match *self {
             JointData::Child { ref private, .. } => private.depth,
             _ => 0,
@@ -401,6 +402,25 @@ impl JointData {
     }
 }
 
+/// Returns the index of the joint called `name` in `joints`, or the error
+/// returned by `missing` for `name` if there is no such joint.
+///
+/// Names are compared byte by byte, so joints whose names are not valid UTF-8
+/// can still be found.
+pub(crate) fn joint_index_by_name<E, F>(
+    joints: &[JointData],
+    name: &[u8],
+    missing: F,
+) -> Result<usize, E>
+where
+    F: FnOnce(BString) -> E,
+{
+    joints
+        .iter()
+        .position(|joint| joint.name().as_bytes() == name)
+        .ok_or_else(|| missing(BString::from(name)))
+}
+
 /// A string type for the `Joint` name. A `SmallVec` is used for
 /// better data locality.
 pub type JointNameInner = SmallVec<[u8; mem::size_of::<String>()]>;
@@ -577,7 +597,9 @@ pub struct Joints<'a> {
     pub(crate) joints: &'a [JointData],
     // pub(crate) motion_values: &'a [f32],
     pub(crate) current_joint: usize,
-    pub(crate) from_child: Option<usize>,
+    /// If this is `Some`, only the direct children of the joint at this
+    /// index are yielded.
+    pub(crate) parent: Option<usize>,
 }
 
 impl fmt::Debug for Joints<'_> {
@@ -595,7 +617,7 @@ impl<'a> Joints<'a> {
             joints,
             // clips,
             current_joint: 0,
-            from_child: None,
+            parent: None,
         }
     }
 
@@ -607,24 +629,13 @@ impl<'a> Joints<'a> {
     /// need to iterate through to the end sites of all children, you will
     /// need to continually call `iter_children` on each `Joint` in the iterator.
     pub(crate) fn iter_children(joint: &Joint<'a>) -> Self {
-        let first_child = joint
-            .joints
-            .iter()
-            .find(|jd| {
-                if let Some(p) = jd.private_data() {
-                    p.parent_index == joint.index
-                } else {
-                    false
-                }
-            })
-            .map(JointData::index)
-            .unwrap();
-
+        // Joints are stored depth first, so all of the children of `joint`
+        // come after it.
         Joints {
             joints: joint.joints,
             // clips: joint.clips,
-            current_joint: joint.data().index(),
-            from_child: Some(first_child),
+            current_joint: joint.index + 1,
+            parent: Some(joint.index),
         }
     }
 
@@ -645,22 +656,31 @@ impl<'a> Joints<'a> {
 impl<'a> Iterator for Joints<'a> {
     type Item = Joint<'a>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_joint >= self.joints.len() {
-            return None;
-        }
+        while self.current_joint < self.joints.len() {
+            let index = self.current_joint;
+            self.current_joint += 1;
 
-        let joint = Some(Joint {
-            index: self.current_joint,
-            joints: self.joints,
-        });
+            if let Some(parent) = self.parent {
+                let joint = &self.joints[index];
+                if joint.depth() <= self.joints[parent].depth() {
+                    // We have left the subtree of `parent`, so there are
+                    // no more children.
+                    self.current_joint = self.joints.len();
+                    return None;
+                }
 
-        if self.from_child.is_none() {
-            self.current_joint += 1;
-        } else {
-            unimplemented!()
+                if joint.parent_index() != Some(parent) {
+                    continue;
+                }
+            }
+
+            return Some(Joint {
+                index,
+                joints: self.joints,
+            });
         }
 
-        joint
+        None
     }
 }
 
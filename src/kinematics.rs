@@ -0,0 +1,416 @@
+//! Forward kinematics for evaluating the world-space pose of a `Bvh` skeleton.
+//!
+//! The local transform of each joint is built by translating by the joint's
+//! `OFFSET` plus the values of any position channels, and then applying each
+//! rotation channel in the order in which it is listed. A joint's world
+//! transform is the world transform of its parent multiplied by its local
+//! transform.
+//!
+//! Rotation values are interpreted as degrees.
+
+use crate::{
+    duration_to_fractional_seconds,
+    euler::{closest_euler, is_tait_bryan, unwrap_angle},
+    math::{Quat, Transform, Vec3},
+    Axis, Bvh, Channel, ChannelType, JointData,
+};
+use mint::{ColumnMatrix4, Vector3};
+use smallvec::SmallVec;
+use std::time::Duration;
+
+/// The largest difference in angle, in radians, or in position between a
+/// pose and the pose which is stored in the channels of a joint.
+pub(crate) const TOLERANCE: f32 = 1.0e-3;
+
+impl Bvh {
+    /// Computes the world-space transform of each joint at `frame`, as
+    /// column-major homogeneous matrices.
+    ///
+    /// The returned array is indexed by [`JointData::index`][`JointData::index`].
+    /// Returns `None` if `frame` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bvh_anim::bvh;
+    /// let bvh = bvh! {
+    ///     HIERARCHY
+    ///     ROOT Base
+    ///     {
+    ///         OFFSET 0.0 0.0 0.0
+    ///         CHANNELS 3 Xposition Yposition Zposition
+    ///         JOINT Tip
+    ///         {
+    ///             OFFSET 0.0 10.0 0.0
+    ///             CHANNELS 0
+    ///             End Site
+    ///             {
+    ///                 OFFSET 0.0 5.0 0.0
+    ///             }
+    ///         }
+    ///     }
+    ///     MOTION
+    ///     Frames: 1
+    ///     Frame Time: 0.033333333
+    ///     1.0 2.0 3.0
+    /// };
+    ///
+    /// let transforms = bvh.world_transforms(0).unwrap();
+    /// assert_eq!(transforms[1].w, [1.0, 12.0, 3.0, 1.0].into());
+    /// ```
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn world_transforms(&self, frame: usize) -> Option<Vec<ColumnMatrix4<f32>>> {
+        let frame = self.frames().nth(frame)?;
+        Some(
+            world_transforms(&self.joints, frame.as_slice())
+                .into_iter()
+                .map(Transform::to_column_matrix)
+                .collect(),
+        )
+    }
+
+    /// Computes the world-space position of each joint at `frame`.
+    ///
+    /// The returned array is indexed by [`JointData::index`][`JointData::index`].
+    /// Returns `None` if `frame` is out of bounds.
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn world_positions(&self, frame: usize) -> Option<Vec<Vector3<f32>>> {
+        let frame = self.frames().nth(frame)?;
+        Some(
+            world_transforms(&self.joints, frame.as_slice())
+                .iter()
+                .map(|t| t.translation.into())
+                .collect(),
+        )
+    }
+
+    /// Returns an iterator over the world-space position of each joint in
+    /// every frame of the clip, in order.
+    ///
+    /// Each item is indexed by [`JointData::index`][`JointData::index`].
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn frames_world_positions(&self) -> impl Iterator<Item = Vec<Vector3<f32>>> + '_ {
+        self.frames().map(move |frame| {
+            world_transforms(&self.joints, frame.as_slice())
+                .iter()
+                .map(|t| t.translation.into())
+                .collect()
+        })
+    }
+
+    /// Computes the world-space transform of each joint at `time` from the
+    /// start of the clip, as column-major homogeneous matrices.
+    ///
+    /// Poses between two frames are interpolated, with rotations blended
+    /// using spherical linear interpolation, and positions blended linearly.
+    /// Times past the end of the clip are clamped to the last frame.
+    ///
+    /// The returned array is indexed by [`JointData::index`][`JointData::index`].
+    /// Returns `None` if the `Bvh` has no frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bvh_anim::bvh;
+    /// # use std::time::Duration;
+    /// let bvh = bvh! {
+    ///     HIERARCHY
+    ///     ROOT Base
+    ///     {
+    ///         OFFSET 0.0 0.0 0.0
+    ///         CHANNELS 3 Xposition Yposition Zposition
+    ///         JOINT Tip
+    ///         {
+    ///             OFFSET 0.0 10.0 0.0
+    ///             CHANNELS 0
+    ///             End Site
+    ///             {
+    ///                 OFFSET 0.0 5.0 0.0
+    ///             }
+    ///         }
+    ///     }
+    ///     MOTION
+    ///     Frames: 2
+    ///     Frame Time: 0.5
+    ///     0.0 0.0 0.0
+    ///     4.0 0.0 0.0
+    /// };
+    ///
+    /// let transforms = bvh.world_transforms_at_time(Duration::from_millis(125)).unwrap();
+    /// assert_eq!(transforms[1].w, [1.0, 10.0, 0.0, 1.0].into());
+    /// ```
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn world_transforms_at_time(&self, time: Duration) -> Option<Vec<ColumnMatrix4<f32>>> {
+        Some(
+            self.interpolated_world_transforms(time)?
+                .into_iter()
+                .map(Transform::to_column_matrix)
+                .collect(),
+        )
+    }
+
+    /// Computes the world-space position of each joint at `time` from the
+    /// start of the clip, interpolating between frames in the same way as
+    /// [`world_transforms_at_time`][`world_transforms_at_time`].
+    ///
+    /// The returned array is indexed by [`JointData::index`][`JointData::index`].
+    /// Returns `None` if the `Bvh` has no frames.
+    ///
+    /// [`world_transforms_at_time`]: #method.world_transforms_at_time
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn world_positions_at_time(&self, time: Duration) -> Option<Vec<Vector3<f32>>> {
+        Some(
+            self.interpolated_world_transforms(time)?
+                .iter()
+                .map(|t| t.translation.into())
+                .collect(),
+        )
+    }
+
+    fn interpolated_world_transforms(&self, time: Duration) -> Option<Vec<Transform>> {
+        let (from, to, t) = sample_frames(
+            self.num_frames,
+            duration_to_fractional_seconds(&self.frame_time),
+            duration_to_fractional_seconds(&time),
+        )?;
+        let from = self.frames().nth(from)?;
+        let to = self.frames().nth(to)?;
+        Some(interpolated_world_transforms(
+            &self.joints,
+            from.as_slice(),
+            to.as_slice(),
+            t,
+        ))
+    }
+}
+
+/// Returns the local rotation of `joint` encoded in `frame`.
+#[inline]
+pub(crate) fn local_rotation(joint: &JointData, frame: &[f32]) -> Quat {
+    rotation_from_channels(joint.channels().iter().cloned(), frame)
+}
+
+/// Returns the sum of the position channels of `joint` in `frame`.
+#[inline]
+pub(crate) fn position_channels(joint: &JointData, frame: &[f32]) -> Vec3 {
+    position_from_channels(joint.channels().iter().cloned(), frame)
+}
+
+/// Returns the product of the rotation `channels`, applied in order, in `frame`.
+pub(crate) fn rotation_from_channels<I>(channels: I, frame: &[f32]) -> Quat
+where
+    I: IntoIterator<Item = Channel>,
+{
+    channels
+        .into_iter()
+        .filter(|c| c.channel_type().is_rotation())
+        .fold(Quat::identity(), |acc, c| {
+            acc * Quat::from_axis_degrees(c.channel_type().axis(), frame[c.motion_index()])
+        })
+}
+
+/// Sets the rotation `channels` in `frame` to the euler angles of `rotation`.
+///
+/// Of the sets of angles which describe `rotation`, the one closest to the
+/// values already in `frame` is chosen, so that the motion stays continuous.
+pub(crate) fn set_rotation_channels(channels: &[Channel], frame: &mut [f32], rotation: Quat) {
+    let channels = channels
+        .iter()
+        .filter(|c| c.channel_type().is_rotation())
+        .cloned()
+        .collect::<SmallVec<[Channel; 3]>>();
+    let order = channels
+        .iter()
+        .map(|c| c.channel_type().axis())
+        .collect::<SmallVec<[Axis; 3]>>();
+    let original = channels
+        .iter()
+        .map(|c| frame[c.motion_index()])
+        .collect::<SmallVec<[f32; 3]>>();
+
+    let angles = rotation.to_euler(&order);
+    let angles = if is_tait_bryan(&channels) {
+        closest_euler(&original, &angles)
+    } else {
+        angles
+            .iter()
+            .zip(original.iter())
+            .map(|(&angle, &original)| unwrap_angle(original, angle))
+            .collect()
+    };
+
+    for (channel, &angle) in channels.iter().zip(angles.iter()) {
+        frame[channel.motion_index()] = angle;
+    }
+}
+
+/// Returns the sum of the position `channels` in `frame`.
+pub(crate) fn position_from_channels<I>(channels: I, frame: &[f32]) -> Vec3
+where
+    I: IntoIterator<Item = Channel>,
+{
+    let mut position = Vec3::zero();
+    for channel in channels {
+        let ty = channel.channel_type();
+        if ty.is_position() {
+            let value = position.get(ty.axis()) + frame[channel.motion_index()];
+            position.set(ty.axis(), value);
+        }
+    }
+    position
+}
+
+/// Sets the position `channels` in `frame` so that they sum to `position`.
+///
+/// The first channel for each axis is set to the component of `position`
+/// along that axis, and any other channels for the same axis are set to zero.
+/// Components along axes without a channel are dropped.
+pub(crate) fn set_position_channels(channels: &[Channel], frame: &mut [f32], position: Vec3) {
+    let mut seen = [false; 3];
+    for channel in channels {
+        let ty = channel.channel_type();
+        if !ty.is_position() {
+            continue;
+        }
+
+        let axis = ty.axis();
+        let index = axis as usize;
+        frame[channel.motion_index()] = if seen[index] { 0.0 } else { position.get(axis) };
+        seen[index] = true;
+    }
+}
+
+/// Returns `true` if the `channels` in `frame` describe `rotation` and
+/// `position`, within `TOLERANCE`.
+///
+/// Positions are compared relative to their length once it is greater than
+/// one, so that large translations are not held to a tighter tolerance than
+/// their precision allows.
+pub(crate) fn pose_preserved(
+    channels: &[Channel],
+    frame: &[f32],
+    rotation: Quat,
+    position: Vec3,
+) -> bool {
+    let rotation_error =
+        rotation_from_channels(channels.iter().cloned(), frame) * rotation.conjugate();
+    let position_error = position_from_channels(channels.iter().cloned(), frame) - position;
+    rotation_error.angle() <= TOLERANCE
+        && position_error.length() <= TOLERANCE * position.length().max(1.0)
+}
+
+/// Returns the position `ChannelType` along `axis`.
+#[inline]
+pub(crate) fn position_channel(axis: Axis) -> ChannelType {
+    match axis {
+        Axis::X => ChannelType::PositionX,
+        Axis::Y => ChannelType::PositionY,
+        Axis::Z => ChannelType::PositionZ,
+    }
+}
+
+/// Returns the rotation `ChannelType` around `axis`.
+#[inline]
+pub(crate) fn rotation_channel(axis: Axis) -> ChannelType {
+    match axis {
+        Axis::X => ChannelType::RotationX,
+        Axis::Y => ChannelType::RotationY,
+        Axis::Z => ChannelType::RotationZ,
+    }
+}
+
+/// Returns the transform of `joint` relative to its parent in `frame`.
+#[inline]
+pub(crate) fn local_transform(joint: &JointData, frame: &[f32]) -> Transform {
+    Transform::new(
+        local_rotation(joint, frame),
+        Vec3::from(joint.offset()) + position_channels(joint, frame),
+    )
+}
+
+/// Interpolates between two local transforms, using `slerp` for the
+/// rotation and a linear blend for the translation.
+#[inline]
+pub(crate) fn lerp_transform(from: &Transform, to: &Transform, t: f32) -> Transform {
+    Transform::new(
+        from.rotation.slerp(to.rotation, t),
+        from.translation.lerp(to.translation, t),
+    )
+}
+
+/// Accumulates the local transform of each joint into its world transform.
+///
+/// `locals` yields the parent index and local transform of each joint, in
+/// the order in which the joints appear in the file, so that every parent
+/// comes before its children.
+pub(crate) fn compose_world_transforms<I>(locals: I) -> Vec<Transform>
+where
+    I: IntoIterator<Item = (Option<usize>, Transform)>,
+{
+    let locals = locals.into_iter();
+    let mut transforms: Vec<Transform> = Vec::with_capacity(locals.size_hint().0);
+    for (parent, local) in locals {
+        let world = match parent {
+            Some(parent) => transforms[parent] * local,
+            None => local,
+        };
+        transforms.push(world);
+    }
+    transforms
+}
+
+/// Computes the world transform of every joint in `joints` from the motion
+/// values of a single `frame`.
+pub(crate) fn world_transforms(joints: &[JointData], frame: &[f32]) -> Vec<Transform> {
+    compose_world_transforms(
+        joints
+            .iter()
+            .map(|joint| (joint.parent_index(), local_transform(joint, frame))),
+    )
+}
+
+/// Computes the world transform of every joint in `joints` at the fraction
+/// `t` of the way between the motion values `from` and `to`.
+pub(crate) fn interpolated_world_transforms(
+    joints: &[JointData],
+    from: &[f32],
+    to: &[f32],
+    t: f32,
+) -> Vec<Transform> {
+    compose_world_transforms(joints.iter().map(|joint| {
+        let local = lerp_transform(
+            &local_transform(joint, from),
+            &local_transform(joint, to),
+            t,
+        );
+        (joint.parent_index(), local)
+    }))
+}
+
+/// Returns the two frames either side of `time` seconds from the start of
+/// a clip, and the fraction of the way between them.
+///
+/// Times before the first frame or after the last frame are clamped. Returns
+/// `None` if the clip has no frames.
+pub(crate) fn sample_frames(
+    num_frames: usize,
+    frame_time: f64,
+    time: f64,
+) -> Option<(usize, usize, f32)> {
+    let last = num_frames.checked_sub(1)?;
+    if frame_time.is_nan() || frame_time <= 0.0 || time.is_nan() || time <= 0.0 {
+        return Some((0, 0, 0.0));
+    }
+
+    let position = time / frame_time;
+    if position >= last as f64 {
+        return Some((last, last, 0.0));
+    }
+
+    let frame = position.floor() as usize;
+    Some((frame, frame + 1, (position - frame as f64) as f32))
+}
@@ -0,0 +1,297 @@
+//! Changing the channels of the joints of a `Bvh`.
+//!
+//! The channels of a joint can be replaced with a new list of channels, such
+//! as a different rotation order, or with position channels added or
+//! removed. The motion values of every frame are converted to the new
+//! channels, so that the pose of the skeleton is unchanged, and the motion
+//! indices of all channels are updated to match.
+//!
+//! When position channels are removed from a joint, their values are moved
+//! into the offset of the joint, which is only possible if they are the same
+//! in every frame. If the new channels cannot describe the pose of a joint in
+//! some frame, such as a rotation around an axis which has no channel, the
+//! change fails and the `Bvh` is left unchanged.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, layout::LayoutOptions, Axis, ChannelType};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Yrotation Xrotation
+//!         JOINT Head
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Xrotation Yrotation Zrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     1.0 2.0 3.0 90.0 0.0 0.0 0.0 0.0 45.0
+//! };
+//!
+//! LayoutOptions::new()
+//!     .with_rotation_order([Axis::Z, Axis::X, Axis::Y])
+//!     .apply(&mut bvh)
+//!     .unwrap();
+//!
+//! let head = bvh.joints().find_by_name("Head").unwrap();
+//! let channels = head.data().channels().iter().map(|c| c.channel_type()).collect::<Vec<_>>();
+//! assert_eq!(
+//!     channels,
+//!     vec![ChannelType::RotationZ, ChannelType::RotationX, ChannelType::RotationY]
+//! );
+//! assert!((bvh.frames().next().unwrap().as_slice()[6] - 45.0).abs() < 1.0e-4);
+//! ```
+
+use crate::{
+    errors::LayoutError,
+    hierarchy::Hierarchy,
+    joint::joint_index_by_name,
+    kinematics::{
+        local_rotation, pose_preserved, position_channel, position_channels, rotation_channel,
+        set_position_channels, set_rotation_channels, TOLERANCE,
+    },
+    math::{Quat, Vec3},
+    Axis, Bvh, ChannelType,
+};
+use bstr::{BString, ByteSlice};
+use smallvec::SmallVec;
+
+/// Which joints have position channels after changing the layout.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PositionChannels {
+    /// Joints with position channels keep them.
+    Keep,
+    /// Only the root joint has position channels.
+    Root,
+    /// Every joint has position channels.
+    All,
+    /// No joint has position channels.
+    None,
+}
+
+impl Default for PositionChannels {
+    #[inline]
+    fn default() -> Self {
+        PositionChannels::Keep
+    }
+}
+
+/// Specify options for changing the channels of every joint of a `Bvh`.
+///
+/// Each joint is given `Xposition Yposition Zposition` channels, as chosen
+/// by `positions`, followed by rotation channels in `rotation_order` if it
+/// already had rotation channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutOptions {
+    /// The order of the rotation channels. Defaults to `[Axis::Z, Axis::X,
+    /// Axis::Y]`.
+    pub rotation_order: [Axis; 3],
+    /// Which joints have position channels. Defaults to
+    /// `PositionChannels::Keep`.
+    pub positions: PositionChannels,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for LayoutOptions {
+    #[inline]
+    fn default() -> Self {
+        LayoutOptions {
+            rotation_order: [Axis::Z, Axis::X, Axis::Y],
+            positions: Default::default(),
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl LayoutOptions {
+    /// Create a new `LayoutOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `rotation_order` on `self` to the new order.
+    #[inline]
+    pub fn with_rotation_order(self, rotation_order: [Axis; 3]) -> Self {
+        LayoutOptions {
+            rotation_order,
+            ..self
+        }
+    }
+
+    /// Sets `positions` on `self` to the new `PositionChannels`.
+    #[inline]
+    pub fn with_positions(self, positions: PositionChannels) -> Self {
+        LayoutOptions { positions, ..self }
+    }
+
+    /// Changes the channels of every joint of `bvh`, keeping the same pose.
+    ///
+    /// Returns an error without changing `bvh` if the new channels cannot
+    /// describe the pose of a joint.
+    pub fn apply(&self, bvh: &mut Bvh) -> Result<(), LayoutError> {
+        let layouts = bvh
+            .joints
+            .iter()
+            .map(|joint| {
+                let channels = joint.channels();
+                let has_positions = match self.positions {
+                    PositionChannels::Keep => {
+                        channels.iter().any(|c| c.channel_type().is_position())
+                    }
+                    PositionChannels::Root => joint.is_root(),
+                    PositionChannels::All => true,
+                    PositionChannels::None => false,
+                };
+                let has_rotations = channels.iter().any(|c| c.channel_type().is_rotation());
+
+                let mut layout = Layout::new();
+                if has_positions {
+                    layout.extend(
+                        [Axis::X, Axis::Y, Axis::Z]
+                            .iter()
+                            .map(|&a| position_channel(a)),
+                    );
+                }
+                if has_rotations {
+                    layout.extend(self.rotation_order.iter().map(|&a| rotation_channel(a)));
+                }
+                Some(layout)
+            })
+            .collect::<Vec<_>>();
+
+        relayout(bvh, &layouts)
+    }
+}
+
+impl Bvh {
+    /// Changes the channels of the joint called `name` to `channels`, in
+    /// order, keeping the same pose.
+    ///
+    /// Returns an error without changing the `Bvh` if there is no joint
+    /// called `name`, or if `channels` cannot describe the pose of the joint.
+    pub fn set_joint_channels(
+        &mut self,
+        name: &str,
+        channels: &[ChannelType],
+    ) -> Result<(), LayoutError> {
+        let index = joint_index_by_name(&self.joints, name.as_bytes(), |name| {
+            LayoutError::MissingJoint { name }
+        })?;
+
+        let mut layouts = vec![None; self.joints.len()];
+        layouts[index] = Some(channels.iter().cloned().collect());
+        relayout(self, &layouts)
+    }
+}
+
+/// The channel types of a joint, in order.
+type Layout = SmallVec<[ChannelType; 6]>;
+
+/// Changes the channels of each joint of `bvh` which has a layout in
+/// `layouts` to that layout, keeping the same pose.
+fn relayout(bvh: &mut Bvh, layouts: &[Option<Layout>]) -> Result<(), LayoutError> {
+    let num_channels = bvh.num_channels;
+    let frame = |f: usize| &bvh.motion_values[f * num_channels..][..num_channels];
+    let pose_not_preserved = |index: usize, frame: usize| LayoutError::PoseNotPreserved {
+        name: BString::from(bvh.joints[index].name().as_bytes()),
+        frame,
+    };
+
+    let mut hierarchy = Hierarchy::new(&bvh.joints);
+    let mut poses: Vec<Option<Vec<(Quat, Vec3)>>> = vec![None; bvh.joints.len()];
+    for (index, (joint, layout)) in bvh.joints.iter().zip(layouts.iter()).enumerate() {
+        let layout = match layout {
+            Some(layout) => layout,
+            None => continue,
+        };
+        // Joints which keep their channels keep their values exactly.
+        if layout
+            .iter()
+            .cloned()
+            .eq(joint.channels().iter().map(|c| c.channel_type()))
+        {
+            continue;
+        }
+
+        let mut pose = (0..bvh.num_frames)
+            .map(|f| {
+                (
+                    local_rotation(joint, frame(f)),
+                    position_channels(joint, frame(f)),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Positions along axes which lose their channels are moved into the
+        // offset, if they do not change.
+        let node = &mut hierarchy.nodes[index];
+        for &axis in &[Axis::X, Axis::Y, Axis::Z] {
+            if layout.contains(&position_channel(axis)) {
+                continue;
+            }
+
+            let first = pose.first().map(|(_, p)| p.get(axis)).unwrap_or(0.0);
+            let changed = pose
+                .iter()
+                .position(|(_, p)| (p.get(axis) - first).abs() > TOLERANCE);
+            if let Some(frame) = changed {
+                return Err(pose_not_preserved(index, frame));
+            }
+
+            node.offset.set(axis, node.offset.get(axis) + first);
+            for (_, position) in pose.iter_mut() {
+                position.set(axis, 0.0);
+            }
+        }
+
+        node.channels = layout.iter().map(|&ty| (ty, None)).collect();
+        poses[index] = Some(pose);
+    }
+
+    let mut edited = bvh.clone();
+    hierarchy.build(&mut edited);
+
+    let num_channels = edited.num_channels;
+    for (index, pose) in poses.iter().enumerate() {
+        let pose = match pose {
+            Some(pose) => pose,
+            None => continue,
+        };
+
+        let channels = edited.joints[index].channels();
+        for (f, &(rotation, position)) in pose.iter().enumerate() {
+            // Start from the angles of the previous frame, so that the
+            // closest angles are chosen and the motion stays continuous.
+            if f > 0 {
+                for channel in channels {
+                    let i = channel.motion_index();
+                    edited.motion_values[f * num_channels + i] =
+                        edited.motion_values[(f - 1) * num_channels + i];
+                }
+            }
+
+            let values = &mut edited.motion_values[f * num_channels..][..num_channels];
+            set_rotation_channels(channels, values, rotation);
+            set_position_channels(channels, values, position);
+
+            if !pose_preserved(channels, values, rotation, position) {
+                return Err(pose_not_preserved(index, f));
+            }
+        }
+    }
+
+    *bvh = edited;
+    Ok(())
+}
@@ -197,15 +197,38 @@
 #[macro_use]
 mod macros;
 
+pub mod additive;
+pub mod binary;
+pub mod blend;
 pub mod builder;
+pub mod contact;
+pub mod csv;
+pub mod derivative;
 pub mod errors;
+pub mod euler;
+pub mod filter;
+pub mod hierarchy;
 
 #[cfg(feature = "ffi")]
 pub mod ffi;
 
+#[cfg(all(feature = "ffi", target_arch = "wasm32", target_os = "unknown"))]
+compile_error!("The `ffi` feature requires `libc`, which is not available on `wasm32-unknown-unknown`");
+
+pub mod ik;
+pub mod kinematics;
+pub mod layout;
+pub mod looping;
+pub mod mask;
+pub mod reduce;
+pub mod rest_pose;
+pub mod root_motion;
+pub mod render;
+pub mod validate;
 pub mod write;
 
 mod joint;
+mod math;
 mod parse;
 
 use bstr::{
@@ -1085,12 +1108,14 @@ impl IndexMut<&Channel> for Frame {
 
 const NSEC_FACTOR: f64 = 1000_000_000.0;
 
+/// Converts a number of fractional seconds, such as the `Frame Time` of a
+/// bvh file, to a `Duration`.
 #[inline]
-fn fraction_seconds_to_duration(x: f64) -> Duration {
+pub fn fraction_seconds_to_duration(x: f64) -> Duration {
     Duration::from_nanos((x * NSEC_FACTOR) as u64)
 }
 
 #[inline]
-fn duation_to_fractional_seconds(duration: &Duration) -> f64 {
-    duration.subsec_nanos() as f64 / NSEC_FACTOR
+fn duration_to_fractional_seconds(duration: &Duration) -> f64 {
+    duration.as_secs_f64()
 }
@@ -0,0 +1,350 @@
+//! Finds the best loop in a `Bvh` clip, and makes it seamless.
+//!
+//! Every pair of frames in the clip is compared, using the positions of the
+//! joints and end sites relative to the root, and how those positions are
+//! changing. The pair with the smallest difference becomes the start and end
+//! of the loop, where the end frame is the first frame after the loop, which
+//! should match the start frame.
+//!
+//! To remove the remaining difference, the last frames of the loop are
+//! blended so that the end of the loop leads into the start. The translation
+//! of the root across the ground is not blended, because a looping clip
+//! usually moves forwards. The distance which it moves in each cycle is
+//! returned as the root offset, and should be added to the root every time
+//! the loop repeats.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, looping::LoopOptions};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Arm
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 6
+//!     Frame Time: 0.1
+//!     0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     1.0 0.0 0.0 0.0 0.0 0.0 30.0 0.0 0.0
+//!     2.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     3.0 0.0 0.0 0.0 0.0 0.0 -30.0 0.0 0.0
+//!     4.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//!     5.0 0.0 0.0 0.0 0.0 0.0 30.0 0.0 0.0
+//! };
+//!
+//! let (looped, range) = LoopOptions::new()
+//!     .with_min_length(3)
+//!     .make_loop(&bvh)
+//!     .unwrap();
+//!
+//! assert_eq!((range.start(), range.end()), (0, 4));
+//! assert_eq!(looped.num_frames(), 4);
+//! assert_eq!(range.root_offset(), [4.0, 0.0, 0.0].into());
+//! ```
+
+use crate::{
+    blend::blend_joint,
+    kinematics::{
+        position_channels, rotation_from_channels, set_rotation_channels, world_transforms,
+    },
+    math::Vec3,
+    Axis, Bvh,
+};
+use mint::Vector3;
+
+/// The range of frames of a loop, and how well its end matches its start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopRange {
+    start: usize,
+    end: usize,
+    error: f32,
+    root_offset: Vector3<f32>,
+}
+
+impl LoopRange {
+    /// The first frame of the loop.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The frame after the last frame of the loop, which matches `start`.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The number of frames in the loop.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the loop has no frames.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.end == self.start
+    }
+
+    /// The difference between the start and end of the loop, before it was
+    /// blended. This is the mean squared distance between the positions of
+    /// each joint and end site relative to the root, plus the mean squared
+    /// distance between how far each of them moves per frame, scaled by
+    /// `velocity_weight`.
+    #[inline]
+    pub fn error(&self) -> f32 {
+        self.error
+    }
+
+    /// The translation of the root across the ground from the start to the
+    /// end of the loop, which should be added to the root each time the
+    /// loop repeats.
+    #[inline]
+    pub fn root_offset(&self) -> Vector3<f32> {
+        self.root_offset
+    }
+}
+
+/// Specify options for finding and creating loops.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopOptions {
+    /// The smallest number of frames in a loop. Defaults to `10`.
+    pub min_length: usize,
+    /// The largest number of frames in a loop, or `None` for no limit.
+    /// Defaults to `None`.
+    pub max_length: Option<usize>,
+    /// How much the difference in velocity counts, compared to the
+    /// difference in pose. Defaults to `1.0`.
+    pub velocity_weight: f32,
+    /// The number of frames at the end of the loop which are blended into
+    /// the start. The last frame of the loop is blended fully, so that it
+    /// leads into the start as it led into the end. Defaults to `5`.
+    pub blend_frames: usize,
+    /// The axis which points up, away from the ground. Defaults to `Axis::Y`.
+    pub up_axis: Axis,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for LoopOptions {
+    #[inline]
+    fn default() -> Self {
+        LoopOptions {
+            min_length: 10,
+            max_length: None,
+            velocity_weight: 1.0,
+            blend_frames: 5,
+            up_axis: Axis::Y,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl LoopOptions {
+    /// Create a new `LoopOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `min_length` on `self` to the new number of frames.
+    #[inline]
+    pub fn with_min_length(self, min_length: usize) -> Self {
+        LoopOptions { min_length, ..self }
+    }
+
+    /// Sets `max_length` on `self` to the new number of frames.
+    #[inline]
+    pub fn with_max_length(self, max_length: Option<usize>) -> Self {
+        LoopOptions { max_length, ..self }
+    }
+
+    /// Sets `velocity_weight` on `self` to the new weight.
+    #[inline]
+    pub fn with_velocity_weight(self, velocity_weight: f32) -> Self {
+        LoopOptions {
+            velocity_weight,
+            ..self
+        }
+    }
+
+    /// Sets `blend_frames` on `self` to the new number of frames.
+    #[inline]
+    pub fn with_blend_frames(self, blend_frames: usize) -> Self {
+        LoopOptions {
+            blend_frames,
+            ..self
+        }
+    }
+
+    /// Sets `up_axis` on `self` to the new `Axis`.
+    #[inline]
+    pub fn with_up_axis(self, up_axis: Axis) -> Self {
+        LoopOptions { up_axis, ..self }
+    }
+
+    /// Finds the range of frames of `bvh` which loops best.
+    ///
+    /// Returns `None` if `bvh` is too short for a loop of `min_length`
+    /// frames.
+    pub fn find(&self, bvh: &Bvh) -> Option<LoopRange> {
+        let poses = bvh
+            .frames()
+            .map(|frame| {
+                let world = world_transforms(&bvh.joints, frame.as_slice());
+                let root = world
+                    .first()
+                    .map(|t| t.translation)
+                    .unwrap_or_else(Vec3::zero);
+                let end_sites = bvh
+                    .joints
+                    .iter()
+                    .zip(world.iter())
+                    .filter_map(|(joint, t)| {
+                        joint
+                            .end_site()
+                            .map(|end_site| t.transform_point(Vec3::from(*end_site)))
+                    });
+                world
+                    .iter()
+                    .map(|t| t.translation)
+                    .chain(end_sites)
+                    .map(|position| position - root)
+                    .collect()
+            })
+            .collect::<Vec<Vec<Vec3>>>();
+
+        let num_frames = poses.len();
+        let velocities = (0..num_frames)
+            .map(|frame| {
+                let from = frame.saturating_sub(1);
+                let to = (frame + 1).min(num_frames - 1);
+                let scale = 1.0 / (to - from).max(1) as f32;
+                poses[to]
+                    .iter()
+                    .zip(poses[from].iter())
+                    .map(|(&to, &from)| (to - from) * scale)
+                    .collect()
+            })
+            .collect::<Vec<Vec<Vec3>>>();
+
+        let min_length = self.min_length.max(1);
+        let max_length = self.max_length.unwrap_or(num_frames);
+        let mut best: Option<(usize, usize, f32)> = None;
+        for start in 0..num_frames {
+            for end in (start + min_length)..num_frames {
+                if end - start > max_length {
+                    break;
+                }
+
+                let error = mean_squared_distance(&poses[start], &poses[end])
+                    + self.velocity_weight
+                        * mean_squared_distance(&velocities[start], &velocities[end]);
+                if best.map(|(_, _, best)| error < best).unwrap_or(true) {
+                    best = Some((start, end, error));
+                }
+            }
+        }
+
+        let (start, end, error) = best?;
+        let mut root_offset = match bvh.joints.first() {
+            Some(root) => {
+                let frame = |f: usize| bvh.frames().nth(f).map(|f| f.as_slice()).unwrap_or(&[]);
+                position_channels(root, frame(end)) - position_channels(root, frame(start))
+            }
+            None => Vec3::zero(),
+        };
+        root_offset.set(self.up_axis, 0.0);
+
+        Some(LoopRange {
+            start,
+            end,
+            error,
+            root_offset: root_offset.into(),
+        })
+    }
+
+    /// Finds the range of frames of `bvh` which loops best, as in
+    /// [`find`][`find`], and returns a new clip of those frames in which the
+    /// end has been blended into the start.
+    ///
+    /// Returns `None` if `bvh` is too short for a loop of `min_length`
+    /// frames.
+    ///
+    /// [`find`]: #method.find
+    pub fn make_loop(&self, bvh: &Bvh) -> Option<(Bvh, LoopRange)> {
+        let range = self.find(bvh)?;
+        let num_channels = bvh.num_channels;
+        let frame = |f: usize| &bvh.motion_values[f * num_channels..][..num_channels];
+        let start = frame(range.start);
+        let end = frame(range.end);
+
+        let len = range.len();
+        let blend_frames = self.blend_frames.min(len - 1);
+        let mut motion_values =
+            bvh.motion_values[range.start * num_channels..range.end * num_channels].to_vec();
+
+        for step in 0..blend_frames {
+            let index = len - blend_frames + step;
+            let t = (step + 1) as f32 / blend_frames as f32;
+            let values = &mut motion_values[index * num_channels..][..num_channels];
+
+            // The pose this frame would have if the motion continued from
+            // the start of the loop instead of the end.
+            let mut target = values.to_vec();
+            for (joint_index, joint) in bvh.joints.iter().enumerate() {
+                let channels = joint.channels();
+                for channel in channels {
+                    let ty = channel.channel_type();
+                    let index = channel.motion_index();
+                    let is_ground = joint_index == 0 && ty.axis() != self.up_axis;
+                    if ty.is_position() && !is_ground {
+                        target[index] += start[index] - end[index];
+                    }
+                }
+
+                if channels.iter().any(|c| c.channel_type().is_rotation()) {
+                    let current = rotation_from_channels(channels.iter().cloned(), values);
+                    let delta = rotation_from_channels(channels.iter().cloned(), start)
+                        * rotation_from_channels(channels.iter().cloned(), end).conjugate();
+                    set_rotation_channels(channels, &mut target, delta * current);
+                }
+            }
+
+            for joint in &bvh.joints {
+                blend_joint(joint.channels(), values, &target, t);
+            }
+        }
+
+        let mut looped = bvh.clone();
+        looped.num_frames = len;
+        looped.motion_values = motion_values;
+        Some((looped, range))
+    }
+}
+
+/// Returns the mean squared distance between corresponding points of `a` and
+/// `b`.
+fn mean_squared_distance(a: &[Vec3], b: &[Vec3]) -> f32 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    let sum = a
+        .iter()
+        .zip(b.iter())
+        .map(|(&a, &b)| (a - b).length_squared())
+        .sum::<f32>();
+    sum / a.len() as f32
+}
@@ -0,0 +1,190 @@
+//! Masks which select the joints affected by a motion edit.
+//!
+//! A `JointMask` gives each joint a weight between `0.0` and `1.0`, where
+//! `1.0` applies an edit fully, `0.0` leaves the joint unchanged, and values
+//! in between apply part of it. Joints which are not in the mask have a
+//! weight of `0.0`.
+//!
+//! Masks refer to joints by name, so the same mask can be used with any clip
+//! which has joints of those names. The weight of a joint applies to each of
+//! its channels.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, mask::JointMask};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 3 Xposition Yposition Zposition
+//!         JOINT Chest
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 1 Zrotation
+//!             JOINT Arm
+//!             {
+//!                 OFFSET 5.0 0.0 0.0
+//!                 CHANNELS 1 Zrotation
+//!                 End Site
+//!                 {
+//!                     OFFSET 5.0 0.0 0.0
+//!                 }
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! let chest = bvh.joints().find_by_name("Chest").unwrap();
+//! let upper_body = JointMask::from_subtree(&chest).with_joint("Arm", 0.5);
+//!
+//! assert_eq!(upper_body.joint_weights(&bvh).unwrap(), vec![0.0, 1.0, 0.5]);
+//! assert_eq!(
+//!     upper_body.channel_weights(&bvh).unwrap(),
+//!     vec![0.0, 0.0, 0.0, 1.0, 0.5]
+//! );
+//! ```
+
+use crate::{errors::MaskError, joint::joint_index_by_name, Bvh, Joint, JointData, JointName};
+use bstr::ByteSlice;
+
+/// A weight for each joint, which selects the joints affected by a motion
+/// edit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JointMask {
+    weights: Vec<(JointName, f32)>,
+}
+
+impl JointMask {
+    /// Create a new, empty `JointMask`, which selects no joints.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a `JointMask` which fully selects the joints called `names`.
+    pub fn from_names<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<JointName>,
+    {
+        JointMask::from_weights(names.into_iter().map(|name| (name, 1.0)))
+    }
+
+    /// Create a `JointMask` which fully selects `joint` and all of its
+    /// descendants.
+    #[inline]
+    pub fn from_subtree(joint: &Joint<'_>) -> Self {
+        JointMask::new().with_subtree(joint, 1.0)
+    }
+
+    /// Create a `JointMask` from the name and weight of each joint.
+    pub fn from_weights<I, S>(weights: I) -> Self
+    where
+        I: IntoIterator<Item = (S, f32)>,
+        S: Into<JointName>,
+    {
+        weights
+            .into_iter()
+            .fold(JointMask::new(), |mask, (name, weight)| {
+                mask.with_joint(name, weight)
+            })
+    }
+
+    /// Sets the weight of the joint called `name` on `self` to `weight`.
+    pub fn with_joint<S: Into<JointName>>(mut self, name: S, weight: f32) -> Self {
+        self.set_weight(name.into(), weight);
+        self
+    }
+
+    /// Sets the weight of `joint` and all of its descendants on `self` to
+    /// `weight`.
+    pub fn with_subtree(mut self, joint: &Joint<'_>, weight: f32) -> Self {
+        self.set_subtree(joint, weight);
+        self
+    }
+
+    /// Returns the weight of the joint called `name`, or `0.0` if it is not
+    /// in the mask.
+    pub fn weight<S: AsRef<[u8]>>(&self, name: S) -> f32 {
+        self.weights
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, weight)| weight)
+            .unwrap_or(0.0)
+    }
+
+    /// Returns `true` if the mask does not select any joints.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.weights.iter().all(|&(_, weight)| weight <= 0.0)
+    }
+
+    /// Returns the weight of each joint of `bvh`, in the order of
+    /// `Bvh::joints`.
+    ///
+    /// Returns an error if a joint in the mask does not exist in `bvh`.
+    #[inline]
+    pub fn joint_weights(&self, bvh: &Bvh) -> Result<Vec<f32>, MaskError> {
+        self.resolve(&bvh.joints)
+    }
+
+    /// Returns the weight of each channel of `bvh`, indexed by
+    /// `Channel::motion_index`. Each channel has the weight of the joint
+    /// which it belongs to.
+    ///
+    /// Returns an error if a joint in the mask does not exist in `bvh`.
+    pub fn channel_weights(&self, bvh: &Bvh) -> Result<Vec<f32>, MaskError> {
+        let joint_weights = self.resolve(&bvh.joints)?;
+        let mut weights = vec![0.0; bvh.num_channels];
+        for (joint, &weight) in bvh.joints.iter().zip(joint_weights.iter()) {
+            for channel in joint.channels() {
+                weights[channel.motion_index()] = weight;
+            }
+        }
+        Ok(weights)
+    }
+
+    /// Returns the weight of each of `joints`, clamped between `0.0` and
+    /// `1.0`.
+    pub(crate) fn resolve(&self, joints: &[JointData]) -> Result<Vec<f32>, MaskError> {
+        let mut weights = vec![0.0; joints.len()];
+        for (name, weight) in &self.weights {
+            let index =
+                joint_index_by_name(joints, &name[..], |name| MaskError::MissingJoint { name })?;
+            weights[index] = weight.clamp(0.0, 1.0);
+        }
+        Ok(weights)
+    }
+
+    fn set_weight(&mut self, name: JointName, weight: f32) {
+        match self.weights.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = weight,
+            None => self.weights.push((name, weight)),
+        }
+    }
+
+    fn set_subtree(&mut self, joint: &Joint<'_>, weight: f32) {
+        self.set_weight(JointName::from(joint.data().name().as_bytes()), weight);
+        for child in joint.children() {
+            self.set_subtree(&child, weight);
+        }
+    }
+}
+
+/// Returns the weight of each of `joints` in `mask`, or `1.0` for every joint
+/// if there is no mask.
+pub(crate) fn resolve(
+    mask: Option<&JointMask>,
+    joints: &[JointData],
+) -> Result<Vec<f32>, MaskError> {
+    match mask {
+        Some(mask) => mask.resolve(joints),
+        None => Ok(vec![1.0; joints.len()]),
+    }
+}
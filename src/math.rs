@@ -0,0 +1,607 @@
+//! Small linear algebra helpers used internally to evaluate poses. The public
+//! API always exposes `mint` types, so these never leak out of the crate.
+
+#![allow(dead_code)]
+
+use crate::Axis;
+use mint::{ColumnMatrix4, Quaternion, Vector3, Vector4};
+use smallvec::SmallVec;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// A 3-component vector.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Vec3 {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) z: f32,
+}
+
+impl Vec3 {
+    #[inline]
+    pub(crate) const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    #[inline]
+    pub(crate) const fn zero() -> Self {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Returns the unit vector along `axis`.
+    #[inline]
+    pub(crate) fn axis(axis: Axis) -> Self {
+        axis.vector::<f32>().into()
+    }
+
+    /// Returns the component of `self` along `axis`.
+    #[inline]
+    pub(crate) fn get(&self, axis: Axis) -> f32 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+
+    /// Sets the component of `self` along `axis` to `value`.
+    #[inline]
+    pub(crate) fn set(&mut self, axis: Axis, value: f32) {
+        match axis {
+            Axis::X => self.x = value,
+            Axis::Y => self.y = value,
+            Axis::Z => self.z = value,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn dot(&self, rhs: Vec3) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    #[inline]
+    pub(crate) fn cross(&self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    #[inline]
+    pub(crate) fn length_squared(&self) -> f32 {
+        self.dot(*self)
+    }
+
+    #[inline]
+    pub(crate) fn length(&self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    #[inline]
+    pub(crate) fn distance(&self, rhs: Vec3) -> f32 {
+        (*self - rhs).length()
+    }
+
+    /// Returns the normalized vector, or the zero vector if `self` is too
+    /// short to be normalized.
+    #[inline]
+    pub(crate) fn normalize(&self) -> Vec3 {
+        let len = self.length();
+        if len <= EPSILON {
+            Vec3::zero()
+        } else {
+            *self * (1.0 / len)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn lerp(&self, rhs: Vec3, t: f32) -> Vec3 {
+        *self + (rhs - *self) * t
+    }
+
+    #[inline]
+    pub(crate) fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl AddAssign for Vec3 {
+    #[inline]
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn mul(self, rhs: f32) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl From<Vector3<f32>> for Vec3 {
+    #[inline]
+    fn from(v: Vector3<f32>) -> Self {
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<&'_ Vector3<f32>> for Vec3 {
+    #[inline]
+    fn from(v: &'_ Vector3<f32>) -> Self {
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Vec3> for Vector3<f32> {
+    #[inline]
+    fn from(v: Vec3) -> Self {
+        Vector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+/// Tolerance used to guard against divisions by zero.
+pub(crate) const EPSILON: f32 = 1.0e-6;
+
+/// A rotation quaternion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Quat {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) z: f32,
+    pub(crate) w: f32,
+}
+
+impl Default for Quat {
+    #[inline]
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+
+impl Quat {
+    #[inline]
+    pub(crate) const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    #[inline]
+    pub(crate) const fn identity() -> Self {
+        Quat::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Creates a rotation of `radians` around the (unit) `axis`.
+    #[inline]
+    pub(crate) fn from_axis_angle(axis: Vec3, radians: f32) -> Self {
+        let (s, c) = (radians * 0.5).sin_cos();
+        Quat::new(axis.x * s, axis.y * s, axis.z * s, c)
+    }
+
+    /// Creates a rotation of `degrees` around a coordinate `axis`.
+    #[inline]
+    pub(crate) fn from_axis_degrees(axis: Axis, degrees: f32) -> Self {
+        Quat::from_axis_angle(Vec3::axis(axis), degrees.to_radians())
+    }
+
+    /// Creates the rotation which results from rotating around each
+    /// axis in `order` by the corresponding angle in `degrees`, using the
+    /// `bvh` convention that the rotations compose from left to right.
+    pub(crate) fn from_euler(order: &[Axis], degrees: &[f32]) -> Self {
+        order
+            .iter()
+            .zip(degrees.iter())
+            .fold(Quat::identity(), |acc, (&axis, &angle)| {
+                acc * Quat::from_axis_degrees(axis, angle)
+            })
+    }
+
+    /// Decomposes the rotation into angles in degrees around each axis
+    /// of `order`, such that `Quat::from_euler(order, &angles)` yields the
+    /// same rotation.
+    ///
+    /// If `order` does not name all three axes, then the missing axes are
+    /// treated as having been appended to the order, and their angles are
+    /// discarded. Repeated axes after the first occurrence receive an angle
+    /// of `0`.
+    pub(crate) fn to_euler(self, order: &[Axis]) -> SmallVec<[f32; 3]> {
+        let mut full_order: SmallVec<[Axis; 3]> = SmallVec::new();
+        for &axis in order.iter().chain([Axis::X, Axis::Y, Axis::Z].iter()) {
+            if !full_order.contains(&axis) {
+                full_order.push(axis);
+            }
+        }
+
+        let angles = self.to_euler_tait_bryan([full_order[0], full_order[1], full_order[2]]);
+
+        let mut seen: SmallVec<[Axis; 3]> = SmallVec::new();
+        order
+            .iter()
+            .map(|axis| {
+                if seen.contains(axis) {
+                    0.0
+                } else {
+                    seen.push(*axis);
+                    let pos = full_order.iter().position(|a| a == axis).unwrap_or(0);
+                    angles[pos]
+                }
+            })
+            .collect()
+    }
+
+    fn to_euler_tait_bryan(self, order: [Axis; 3]) -> [f32; 3] {
+        let idx = |axis: Axis| match axis {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        };
+
+        let (i, j, k) = (idx(order[0]), idx(order[1]), idx(order[2]));
+        let sign = if (j + 3 - i) % 3 == 1 { 1.0 } else { -1.0 };
+        let m = self.to_rotation_matrix();
+
+        let sin_beta = (sign * m[i][k]).clamp(-1.0, 1.0);
+        let beta = sin_beta.asin();
+
+        if sin_beta.abs() < 1.0 - 1.0e-6 {
+            let alpha = (-sign * m[j][k]).atan2(m[k][k]);
+            let gamma = (-sign * m[i][j]).atan2(m[i][i]);
+            [alpha.to_degrees(), beta.to_degrees(), gamma.to_degrees()]
+        } else {
+            // Gimbal lock: the first and last axes coincide, so attribute the
+            // whole remaining rotation to the first axis.
+            let rest = self * Quat::from_axis_angle(Vec3::axis(order[1]), -beta);
+            let component = Vec3::new(rest.x, rest.y, rest.z).get(order[0]);
+            let alpha = 2.0 * component.atan2(rest.w);
+            [wrap_degrees(alpha.to_degrees()), beta.to_degrees(), 0.0]
+        }
+    }
+
+    /// Returns the row-major rotation matrix of `self`.
+    pub(crate) fn to_rotation_matrix(self) -> [[f32; 3]; 3] {
+        let Quat { x, y, z, w } = self.normalize();
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
+    /// Creates the shortest rotation which maps the direction `from` onto the
+    /// direction `to`.
+    pub(crate) fn from_to(from: Vec3, to: Vec3) -> Self {
+        let (from, to) = (from.normalize(), to.normalize());
+        let d = from.dot(to);
+        if d >= 1.0 - EPSILON {
+            return Quat::identity();
+        }
+
+        if d <= -1.0 + EPSILON {
+            let mut axis = Vec3::axis(Axis::X).cross(from);
+            if axis.length_squared() <= EPSILON {
+                axis = Vec3::axis(Axis::Y).cross(from);
+            }
+            return Quat::from_axis_angle(axis.normalize(), std::f32::consts::PI);
+        }
+
+        let c = from.cross(to);
+        Quat::new(c.x, c.y, c.z, 1.0 + d).normalize()
+    }
+
+    #[inline]
+    pub(crate) fn conjugate(&self) -> Self {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    #[inline]
+    pub(crate) fn dot(&self, rhs: Quat) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    #[inline]
+    pub(crate) fn normalize(&self) -> Self {
+        let len = self.dot(*self).sqrt();
+        if len <= EPSILON {
+            Quat::identity()
+        } else {
+            let inv = 1.0 / len;
+            Quat::new(self.x * inv, self.y * inv, self.z * inv, self.w * inv)
+        }
+    }
+
+    /// Returns `-self`, which represents the same rotation.
+    #[inline]
+    pub(crate) fn negate(&self) -> Self {
+        Quat::new(-self.x, -self.y, -self.z, -self.w)
+    }
+
+    /// Returns whichever of `self` or `-self` is in the same hemisphere as
+    /// `reference`.
+    #[inline]
+    pub(crate) fn aligned_with(&self, reference: Quat) -> Self {
+        if self.dot(reference) < 0.0 {
+            self.negate()
+        } else {
+            *self
+        }
+    }
+
+    /// Rotates the vector `v` by `self`.
+    #[inline]
+    pub(crate) fn rotate(&self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    /// The angle of the rotation in radians, in the range `[0, pi]`.
+    #[inline]
+    pub(crate) fn angle(&self) -> f32 {
+        let q = self.normalize();
+        2.0 * Vec3::new(q.x, q.y, q.z).length().atan2(q.w.abs())
+    }
+
+    /// Returns the rotation vector (axis multiplied by angle in radians) of
+    /// the shortest arc equivalent to `self`.
+    pub(crate) fn to_scaled_axis(self) -> Vec3 {
+        let q = self.normalize();
+        let q = if q.w < 0.0 { q.negate() } else { q };
+        let v = Vec3::new(q.x, q.y, q.z);
+        let sin_half = v.length();
+        if sin_half <= EPSILON {
+            v * 2.0
+        } else {
+            let angle = 2.0 * sin_half.atan2(q.w);
+            v * (angle / sin_half)
+        }
+    }
+
+    /// The inverse of `to_scaled_axis`.
+    pub(crate) fn from_scaled_axis(v: Vec3) -> Self {
+        let angle = v.length();
+        if angle <= EPSILON {
+            Quat::new(v.x * 0.5, v.y * 0.5, v.z * 0.5, 1.0).normalize()
+        } else {
+            Quat::from_axis_angle(v * (1.0 / angle), angle)
+        }
+    }
+
+    /// Spherical linear interpolation along the shortest path.
+    pub(crate) fn slerp(&self, rhs: Quat, t: f32) -> Quat {
+        let rhs = rhs.aligned_with(*self);
+        let d = self.dot(rhs).min(1.0);
+        if d > 1.0 - EPSILON {
+            return self.nlerp(rhs, t);
+        }
+
+        let theta = d.acos();
+        let sin_theta = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+        Quat::new(
+            self.x * a + rhs.x * b,
+            self.y * a + rhs.y * b,
+            self.z * a + rhs.z * b,
+            self.w * a + rhs.w * b,
+        )
+        .normalize()
+    }
+
+    /// Normalized linear interpolation along the shortest path.
+    #[inline]
+    pub(crate) fn nlerp(&self, rhs: Quat, t: f32) -> Quat {
+        let rhs = rhs.aligned_with(*self);
+        Quat::new(
+            self.x + (rhs.x - self.x) * t,
+            self.y + (rhs.y - self.y) * t,
+            self.z + (rhs.z - self.z) * t,
+            self.w + (rhs.w - self.w) * t,
+        )
+        .normalize()
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+    #[inline]
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+impl From<Quat> for Quaternion<f32> {
+    #[inline]
+    fn from(q: Quat) -> Self {
+        Quaternion {
+            v: Vector3 {
+                x: q.x,
+                y: q.y,
+                z: q.z,
+            },
+            s: q.w,
+        }
+    }
+}
+
+impl From<Quaternion<f32>> for Quat {
+    #[inline]
+    fn from(q: Quaternion<f32>) -> Self {
+        Quat::new(q.v.x, q.v.y, q.v.z, q.s)
+    }
+}
+
+/// A rigid transformation, composed of a rotation followed by a translation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Transform {
+    pub(crate) rotation: Quat,
+    pub(crate) translation: Vec3,
+}
+
+impl Transform {
+    #[inline]
+    pub(crate) const fn new(rotation: Quat, translation: Vec3) -> Self {
+        Transform {
+            rotation,
+            translation,
+        }
+    }
+
+    #[inline]
+    pub(crate) const fn identity() -> Self {
+        Transform::new(Quat::identity(), Vec3::zero())
+    }
+
+    /// Applies `self` to the point `p`.
+    #[inline]
+    pub(crate) fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.rotation.rotate(p) + self.translation
+    }
+
+    /// Returns the inverse transformation.
+    #[inline]
+    pub(crate) fn inverse(&self) -> Transform {
+        let rotation = self.rotation.conjugate();
+        Transform::new(rotation, -rotation.rotate(self.translation))
+    }
+
+    /// Converts the transform into a column-major homogeneous matrix.
+    pub(crate) fn to_column_matrix(self) -> ColumnMatrix4<f32> {
+        let m = self.rotation.to_rotation_matrix();
+        let column = |c: usize| Vector4 {
+            x: m[0][c],
+            y: m[1][c],
+            z: m[2][c],
+            w: 0.0,
+        };
+        ColumnMatrix4 {
+            x: column(0),
+            y: column(1),
+            z: column(2),
+            w: Vector4 {
+                x: self.translation.x,
+                y: self.translation.y,
+                z: self.translation.z,
+                w: 1.0,
+            },
+        }
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+    #[inline]
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform::new(
+            self.rotation * rhs.rotation,
+            self.transform_point(rhs.translation),
+        )
+    }
+}
+
+/// Wraps an angle in degrees into the range `(-180, 180]`.
+#[inline]
+pub(crate) fn wrap_degrees(degrees: f32) -> f32 {
+    let wrapped = (degrees + 180.0).rem_euclid(360.0) - 180.0;
+    if wrapped <= -180.0 {
+        wrapped + 360.0
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quat, Vec3};
+    use crate::Axis;
+
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        for &v in &[
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ] {
+            let (ra, rb) = (a.rotate(v), b.rotate(v));
+            assert!(ra.distance(rb) < 1.0e-4, "{:?} != {:?}", ra, rb);
+        }
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let orders = [
+            [Axis::X, Axis::Y, Axis::Z],
+            [Axis::X, Axis::Z, Axis::Y],
+            [Axis::Y, Axis::X, Axis::Z],
+            [Axis::Y, Axis::Z, Axis::X],
+            [Axis::Z, Axis::X, Axis::Y],
+            [Axis::Z, Axis::Y, Axis::X],
+        ];
+
+        let angles = [
+            [10.0, 20.0, 30.0],
+            [-170.0, 45.0, 95.0],
+            [35.0, 90.0, -20.0],
+        ];
+
+        for order in &orders {
+            for a in &angles {
+                let q = Quat::from_euler(order, a);
+                let decomposed = q.to_euler(order);
+                assert_same_rotation(q, Quat::from_euler(order, &decomposed));
+            }
+        }
+    }
+
+    #[test]
+    fn slerp_endpoints() {
+        let a = Quat::from_axis_degrees(Axis::Y, 10.0);
+        let b = Quat::from_axis_degrees(Axis::Y, 90.0);
+        assert_same_rotation(a.slerp(b, 0.0), a);
+        assert_same_rotation(a.slerp(b, 1.0), b);
+        assert_same_rotation(a.slerp(b, 0.5), Quat::from_axis_degrees(Axis::Y, 50.0));
+    }
+}
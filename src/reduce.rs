@@ -0,0 +1,449 @@
+//! Error-bounded keyframe reduction of `Bvh` motion.
+//!
+//! The dense motion of a `Bvh` is converted into a set of sparse [`Track`][`Track`]s,
+//! each of which only stores the keyframes which are needed to reconstruct the
+//! original motion by interpolation within a tolerance. The sparse motion can be
+//! baked back into a dense `Bvh` at any time.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, reduce::ReduceOptions};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 1 Xposition
+//!         End Site
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 5
+//!     Frame Time: 0.033333333
+//!     0.0
+//!     1.0
+//!     2.0
+//!     3.0
+//!     4.0
+//! };
+//!
+//! let sparse = ReduceOptions::new().reduce(&bvh);
+//! assert_eq!(sparse.num_keys(), 2);
+//! assert_eq!(sparse.to_bvh(), bvh);
+//! ```
+//!
+//! [`Track`]: struct.Track.html
+
+use crate::{
+    math::{wrap_degrees, Quat},
+    Axis, Bvh, Channel, JointData,
+};
+use smallvec::SmallVec;
+use std::time::Duration;
+
+/// How the channels of a `Bvh` are grouped into tracks.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ReductionMode {
+    /// Every channel is reduced independently, and interpolated linearly.
+    /// The rotation tolerance is compared against the difference of the
+    /// euler angles of each rotation channel.
+    PerChannel,
+    /// The rotation channels of each joint are reduced together, and
+    /// interpolated with spherical linear interpolation. The rotation
+    /// tolerance is compared against the angle between the original and
+    /// the reconstructed rotations. Position channels are reduced independently.
+    PerJointRotation,
+}
+
+impl Default for ReductionMode {
+    #[inline]
+    fn default() -> Self {
+        ReductionMode::PerChannel
+    }
+}
+
+/// The method used to reconstruct values between two keyframes of a `Track`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Interpolation {
+    /// Each value is interpolated linearly.
+    Linear,
+    /// The values are the euler angles of a rotation, which is interpolated
+    /// with spherical linear interpolation.
+    Slerp,
+}
+
+/// Specify options for reducing the keyframes of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReduceOptions {
+    /// The maximum allowed error of rotations, in degrees.
+    pub rotation_tolerance: f32,
+    /// The maximum allowed error of positions, in the units of the `Bvh`.
+    pub position_tolerance: f32,
+    /// How channels are grouped into tracks.
+    pub mode: ReductionMode,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for ReduceOptions {
+    #[inline]
+    fn default() -> Self {
+        ReduceOptions {
+            rotation_tolerance: 0.5,
+            position_tolerance: 0.01,
+            mode: Default::default(),
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl ReduceOptions {
+    /// Create a new `ReduceOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `rotation_tolerance` on `self` to the new `rotation_tolerance`.
+    #[inline]
+    pub fn with_rotation_tolerance(self, rotation_tolerance: f32) -> Self {
+        ReduceOptions {
+            rotation_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets `position_tolerance` on `self` to the new `position_tolerance`.
+    #[inline]
+    pub fn with_position_tolerance(self, position_tolerance: f32) -> Self {
+        ReduceOptions {
+            position_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets `mode` on `self` to the new `ReductionMode`.
+    #[inline]
+    pub fn with_mode(self, mode: ReductionMode) -> Self {
+        ReduceOptions { mode, ..self }
+    }
+
+    /// Reduces the motion of `bvh` to sparse keyframes.
+    pub fn reduce(&self, bvh: &Bvh) -> SparseMotion {
+        let mut tracks = Vec::new();
+
+        for joint in &bvh.joints {
+            let (rotations, positions): (SmallVec<[Channel; 6]>, SmallVec<[Channel; 6]>) = joint
+                .channels()
+                .iter()
+                .cloned()
+                .partition(|c| c.channel_type().is_rotation());
+
+            if self.mode == ReductionMode::PerJointRotation && !rotations.is_empty() {
+                tracks.push(self.reduce_track(bvh, &rotations, Interpolation::Slerp));
+            } else {
+                for channel in rotations {
+                    tracks.push(self.reduce_track(bvh, &[channel], Interpolation::Linear));
+                }
+            }
+
+            for channel in positions {
+                tracks.push(self.reduce_track(bvh, &[channel], Interpolation::Linear));
+            }
+        }
+
+        let mut sparse = SparseMotion {
+            joints: bvh.joints.clone(),
+            tracks,
+            num_frames: bvh.num_frames,
+            num_channels: bvh.num_channels,
+            frame_time: bvh.frame_time,
+            max_error: Default::default(),
+        };
+
+        sparse.max_error = sparse.measure_error(bvh);
+        sparse
+    }
+
+    fn reduce_track(&self, bvh: &Bvh, channels: &[Channel], interpolation: Interpolation) -> Track {
+        let original = |frame: usize| -> SmallVec<[f32; 3]> {
+            channels
+                .iter()
+                .map(|c| bvh.motion_values[frame * bvh.num_channels + c.motion_index()])
+                .collect()
+        };
+
+        let tolerance = if channels[0].channel_type().is_rotation() {
+            self.rotation_tolerance
+        } else {
+            self.position_tolerance
+        };
+
+        let mut track = Track {
+            channels: channels.iter().cloned().collect(),
+            interpolation,
+            keys: Vec::new(),
+        };
+
+        let num_frames = bvh.num_frames;
+        if num_frames == 0 {
+            return track;
+        }
+
+        let mut is_key = vec![false; num_frames];
+        is_key[0] = true;
+        is_key[num_frames - 1] = true;
+
+        let mut segments = vec![(0, num_frames - 1)];
+        while let Some((start, end)) = segments.pop() {
+            if end <= start + 1 {
+                continue;
+            }
+
+            let (first, last) = (original(start), original(end));
+            let mut worst = (0.0f32, start);
+            for frame in start + 1..end {
+                let t = (frame - start) as f32 / (end - start) as f32;
+                let value = track.interpolate(&first, &last, t);
+                let error = track.error(&original(frame), &value);
+                if error > worst.0 {
+                    worst = (error, frame);
+                }
+            }
+
+            if worst.0 > tolerance {
+                is_key[worst.1] = true;
+                segments.push((start, worst.1));
+                segments.push((worst.1, end));
+            }
+        }
+
+        track.keys = is_key
+            .iter()
+            .enumerate()
+            .filter(|&(_, &is_key)| is_key)
+            .map(|(frame, _)| Keyframe {
+                frame,
+                values: original(frame),
+            })
+            .collect();
+
+        track
+    }
+}
+
+/// A single keyframe of a `Track`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    frame: usize,
+    values: SmallVec<[f32; 3]>,
+}
+
+impl Keyframe {
+    /// Returns the index of the frame of this keyframe.
+    #[inline]
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Returns the values of the channels of the track at this keyframe.
+    #[inline]
+    pub fn values(&self) -> &[f32] {
+        &self.values[..]
+    }
+}
+
+/// The sparse keyframes of one or more channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    channels: SmallVec<[Channel; 3]>,
+    interpolation: Interpolation,
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    /// Returns the channels animated by this track.
+    #[inline]
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels[..]
+    }
+
+    /// Returns the interpolation used between the keyframes of this track.
+    #[inline]
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Returns the keyframes of the track, ordered by frame.
+    #[inline]
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys[..]
+    }
+
+    /// Reconstructs the values of the channels of the track at `frame`.
+    ///
+    /// Frames outside of the range of the keyframes take the value of the
+    /// nearest keyframe.
+    pub fn sample(&self, frame: usize) -> SmallVec<[f32; 3]> {
+        let next = match self.keys.binary_search_by_key(&frame, |k| k.frame) {
+            Ok(index) => return self.keys[index].values.clone(),
+            Err(next) => next,
+        };
+
+        if next == 0 {
+            return self
+                .keys
+                .first()
+                .map(|k| k.values.clone())
+                .unwrap_or_else(|| self.channels.iter().map(|_| 0.0).collect());
+        }
+
+        if next == self.keys.len() {
+            return self.keys[next - 1].values.clone();
+        }
+
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (frame - a.frame) as f32 / (b.frame - a.frame) as f32;
+        self.interpolate(&a.values, &b.values, t)
+    }
+
+    fn axes(&self) -> SmallVec<[Axis; 3]> {
+        self.channels
+            .iter()
+            .map(|c| c.channel_type().axis())
+            .collect()
+    }
+
+    fn interpolate(&self, a: &[f32], b: &[f32], t: f32) -> SmallVec<[f32; 3]> {
+        let lerp = a.iter().zip(b).map(|(&a, &b)| a + (b - a) * t);
+        match self.interpolation {
+            Interpolation::Linear => lerp.collect(),
+            Interpolation::Slerp => {
+                let axes = self.axes();
+                let rotation = Quat::from_euler(&axes, a).slerp(Quat::from_euler(&axes, b), t);
+                // Choose the equivalent angles which are closest to the linear
+                // interpolation, to keep the baked channels continuous.
+                rotation
+                    .to_euler(&axes)
+                    .into_iter()
+                    .zip(lerp)
+                    .map(|(angle, reference)| reference + wrap_degrees(angle - reference))
+                    .collect()
+            }
+        }
+    }
+
+    fn error(&self, original: &[f32], reconstructed: &[f32]) -> f32 {
+        match self.interpolation {
+            Interpolation::Linear => original
+                .iter()
+                .zip(reconstructed)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max),
+            Interpolation::Slerp => {
+                let axes = self.axes();
+                let a = Quat::from_euler(&axes, original);
+                let b = Quat::from_euler(&axes, reconstructed);
+                (a.conjugate() * b).angle().to_degrees()
+            }
+        }
+    }
+}
+
+/// The largest differences between a reduced motion and the original motion.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MaxError {
+    /// The largest rotation error, in degrees.
+    pub rotation: f32,
+    /// The largest position error, in the units of the `Bvh`.
+    pub position: f32,
+}
+
+/// The motion of a `Bvh` stored as sparse keyframes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMotion {
+    joints: Vec<JointData>,
+    tracks: Vec<Track>,
+    num_frames: usize,
+    num_channels: usize,
+    frame_time: Duration,
+    max_error: MaxError,
+}
+
+impl SparseMotion {
+    /// Returns the tracks of the motion.
+    #[inline]
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks[..]
+    }
+
+    /// Returns the total number of keyframes in all tracks.
+    #[inline]
+    pub fn num_keys(&self) -> usize {
+        self.tracks.iter().map(|t| t.keys.len()).sum()
+    }
+
+    /// Returns the number of frames of the dense motion.
+    #[inline]
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Returns the time it takes to play one frame.
+    #[inline]
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Returns the largest error between the baked motion and the motion
+    /// from which `self` was reduced.
+    #[inline]
+    pub fn max_error(&self) -> MaxError {
+        self.max_error
+    }
+
+    /// Bakes the sparse motion back into a dense `Bvh`.
+    pub fn to_bvh(&self) -> Bvh {
+        let mut motion_values = vec![0.0; self.num_frames * self.num_channels];
+        for track in &self.tracks {
+            for frame in 0..self.num_frames {
+                let row = frame * self.num_channels;
+                for (channel, value) in track.channels.iter().zip(track.sample(frame)) {
+                    motion_values[row + channel.motion_index()] = value;
+                }
+            }
+        }
+
+        Bvh {
+            joints: self.joints.clone(),
+            motion_values,
+            num_frames: self.num_frames,
+            num_channels: self.num_channels,
+            frame_time: self.frame_time,
+        }
+    }
+
+    fn measure_error(&self, bvh: &Bvh) -> MaxError {
+        let mut max_error = MaxError::default();
+        for track in &self.tracks {
+            for frame in 0..self.num_frames {
+                let original = track
+                    .channels
+                    .iter()
+                    .map(|c| bvh.motion_values[frame * bvh.num_channels + c.motion_index()])
+                    .collect::<SmallVec<[f32; 3]>>();
+                let error = track.error(&original, &track.sample(frame));
+
+                if track.channels[0].channel_type().is_rotation() {
+                    max_error.rotation = max_error.rotation.max(error);
+                } else {
+                    max_error.position = max_error.position.max(error);
+                }
+            }
+        }
+        max_error
+    }
+}
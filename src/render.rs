@@ -0,0 +1,991 @@
+//! Software rendering of skeleton poses to `svg` documents and raster images.
+//!
+//! The renderer runs entirely on the CPU, so it can be used to generate
+//! thumbnails and contact sheets on machines without a GPU. Each bone is drawn
+//! as a line between the world-space positions of a joint and its parent, or
+//! between a joint and its end site, and each joint is drawn as a dot.
+//!
+//! Encoding raster images as `png` files requires the `png` feature, and
+//! exporting animated previews as `gif` files requires the `gif` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, render::{Camera, RenderOptions}};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 3 Xposition Yposition Zposition
+//!         JOINT Tip
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 0
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 0.0 0.0
+//! };
+//!
+//! let options = RenderOptions::new()
+//!     .with_size(64, 64)
+//!     .with_camera(Camera::orthographic([0.0, 5.0, 50.0], [0.0, 5.0, 0.0], 20.0));
+//!
+//! let svg = options.render_svg(&bvh, 0).unwrap();
+//! assert!(svg.contains("<line"));
+//!
+//! let image = options.render_image(&bvh, 0).unwrap();
+//! assert_eq!((image.width(), image.height()), (64, 64));
+//! ```
+
+use crate::{
+    duration_to_fractional_seconds, fraction_seconds_to_duration, kinematics,
+    math::{Vec3, EPSILON},
+    Bvh, Frame,
+};
+use mint::Vector3;
+#[cfg(feature = "png")]
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use std::{
+    convert::TryFrom,
+    fmt::Write as _,
+    io::{self, Write},
+    ops::Range,
+    time::Duration,
+};
+
+/// An `RGBA` colour, with 8 bits per component and straight alpha.
+pub type Rgba = [u8; 4];
+
+/// The projection used by a `Camera`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// An orthographic projection.
+    Orthographic {
+        /// The height of the visible area, in the units of the `Bvh`.
+        height: f32,
+    },
+    /// A perspective projection.
+    Perspective {
+        /// The vertical field of view, in degrees.
+        fov_y: f32,
+    },
+}
+
+/// A camera which views the skeleton.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// The position of the camera.
+    pub eye: Vector3<f32>,
+    /// The point at which the camera is looking.
+    pub target: Vector3<f32>,
+    /// The direction which is up in the rendered image.
+    pub up: Vector3<f32>,
+    /// The projection of the camera.
+    pub projection: Projection,
+}
+
+impl Default for Camera {
+    #[inline]
+    fn default() -> Self {
+        Camera::perspective([0.0, 100.0, 400.0], [0.0, 100.0, 0.0], 45.0)
+    }
+}
+
+impl Camera {
+    /// Create a new camera with an orthographic projection, looking from `eye`
+    /// towards `target`, and showing an area which is `height` units tall.
+    #[inline]
+    pub fn orthographic<V: Into<Vector3<f32>>>(eye: V, target: V, height: f32) -> Self {
+        Camera {
+            eye: eye.into(),
+            target: target.into(),
+            up: [0.0, 1.0, 0.0].into(),
+            projection: Projection::Orthographic { height },
+        }
+    }
+
+    /// Create a new camera with a perspective projection, looking from `eye`
+    /// towards `target`, with a vertical field of view of `fov_y` degrees.
+    #[inline]
+    pub fn perspective<V: Into<Vector3<f32>>>(eye: V, target: V, fov_y: f32) -> Self {
+        Camera {
+            eye: eye.into(),
+            target: target.into(),
+            up: [0.0, 1.0, 0.0].into(),
+            projection: Projection::Perspective { fov_y },
+        }
+    }
+
+    /// Sets `up` on `self` to the new `up`.
+    #[inline]
+    pub fn with_up<V: Into<Vector3<f32>>>(self, up: V) -> Self {
+        Camera {
+            up: up.into(),
+            ..self
+        }
+    }
+
+    /// Projects the world-space `point` onto an image of `width` by `height`
+    /// pixels. Returns `None` if the point is behind a perspective camera.
+    pub fn project(&self, point: Vector3<f32>, width: u32, height: u32) -> Option<[f32; 2]> {
+        let (eye, target) = (Vec3::from(self.eye), Vec3::from(self.target));
+        let forward = (target - eye).normalize();
+        let (right, up) = self.image_axes(forward);
+
+        let d = Vec3::from(point) - eye;
+        let (x, y, z) = (d.dot(right), d.dot(up), d.dot(forward));
+        let (half_width, half_height) = (width as f32 * 0.5, height as f32 * 0.5);
+
+        let (x, y) = match self.projection {
+            Projection::Orthographic { height: view_height } => {
+                let scale = height as f32 / view_height.max(EPSILON);
+                (x * scale, y * scale)
+            }
+            Projection::Perspective { fov_y } => {
+                if z <= EPSILON {
+                    return None;
+                }
+                let scale = half_height / (fov_y.to_radians() * 0.5).tan();
+                (x / z * scale, y / z * scale)
+            }
+        };
+
+        Some([half_width + x, half_height - y])
+    }
+
+    /// Returns a camera which looks at `bounds` from the same direction as
+    /// `self`, positioned so that `bounds` fills an image with the aspect
+    /// ratio `aspect` (width divided by height), with a small margin.
+    pub fn framing(&self, bounds: &Bounds, aspect: f32) -> Camera {
+        const MARGIN: f32 = 1.1;
+
+        let (min, max) = (Vec3::from(bounds.min), Vec3::from(bounds.max));
+        let center = Vec3::from(bounds.center());
+        let radius = ((max - min) * 0.5).length().max(EPSILON);
+        let aspect = aspect.max(EPSILON);
+
+        let mut direction = (Vec3::from(self.eye) - Vec3::from(self.target)).normalize();
+        if direction == Vec3::zero() {
+            direction = Vec3::new(0.0, 0.0, 1.0);
+        }
+
+        match self.projection {
+            Projection::Orthographic { .. } => {
+                let forward = -direction;
+                let (right, up) = self.image_axes(forward);
+
+                let (mut width, mut height) = (0.0f32, 0.0f32);
+                for &x in &[min.x, max.x] {
+                    for &y in &[min.y, max.y] {
+                        for &z in &[min.z, max.z] {
+                            let d = Vec3::new(x, y, z) - center;
+                            width = width.max(d.dot(right).abs() * 2.0);
+                            height = height.max(d.dot(up).abs() * 2.0);
+                        }
+                    }
+                }
+
+                Camera {
+                    eye: (center + direction * radius * 2.0).into(),
+                    target: center.into(),
+                    projection: Projection::Orthographic {
+                        height: height.max(width / aspect).max(EPSILON) * MARGIN,
+                    },
+                    ..*self
+                }
+            }
+            Projection::Perspective { fov_y } => {
+                let half_y = fov_y.to_radians() * 0.5;
+                let half_x = (half_y.tan() * aspect).atan();
+                let distance = radius * MARGIN / half_y.min(half_x).sin().max(EPSILON);
+
+                Camera {
+                    eye: (center + direction * distance).into(),
+                    target: center.into(),
+                    ..*self
+                }
+            }
+        }
+    }
+
+    /// Returns the directions which are right and up in the image, for a
+    /// camera looking along `forward`.
+    ///
+    /// If `up` is parallel to `forward`, the world axis which is furthest from
+    /// `forward` is used as the up direction instead.
+    fn image_axes(&self, forward: Vec3) -> (Vec3, Vec3) {
+        let mut right = forward.cross(Vec3::from(self.up));
+        if right.length() <= EPSILON {
+            let (x, y, z) = (forward.x.abs(), forward.y.abs(), forward.z.abs());
+            let fallback = if y <= x && y <= z {
+                Vec3::new(0.0, 1.0, 0.0)
+            } else if z <= x {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            right = forward.cross(fallback);
+        }
+
+        let right = right.normalize();
+        (right, right.cross(forward))
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    /// The corner of the box with the smallest coordinates.
+    pub min: Vector3<f32>,
+    /// The corner of the box with the largest coordinates.
+    pub max: Vector3<f32>,
+}
+
+impl Bounds {
+    /// Computes the bounds of the world-space positions of every joint and
+    /// end site of `bvh` over the range of `frames`.
+    ///
+    /// Returns `None` if the range does not contain any frames of `bvh`, or
+    /// if `bvh` has no joints.
+    pub fn of_motion(bvh: &Bvh, frames: Range<usize>) -> Option<Self> {
+        let mut bounds: Option<(Vec3, Vec3)> = None;
+        let frames = bvh
+            .frames()
+            .skip(frames.start)
+            .take(frames.end.saturating_sub(frames.start));
+        for frame in frames {
+            for p in world_points(bvh, frame) {
+                bounds = Some(match bounds {
+                    Some((min, max)) => (
+                        Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                        Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                    ),
+                    None => (p, p),
+                });
+            }
+        }
+
+        bounds.map(|(min, max)| Bounds {
+            min: min.into(),
+            max: max.into(),
+        })
+    }
+
+    /// Returns the center of the bounds.
+    #[inline]
+    pub fn center(&self) -> Vector3<f32> {
+        ((Vec3::from(self.min) + Vec3::from(self.max)) * 0.5).into()
+    }
+}
+
+/// Specify options for rendering a `Bvh` skeleton.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// The width of a rendered frame, in pixels.
+    pub width: u32,
+    /// The height of a rendered frame, in pixels.
+    pub height: u32,
+    /// The camera from which the skeleton is viewed.
+    pub camera: Camera,
+    /// The colour of the background.
+    pub background: Rgba,
+    /// The colour of the bones.
+    pub bone_color: Rgba,
+    /// The colour of the joints.
+    pub joint_color: Rgba,
+    /// The colour of the end sites.
+    pub end_site_color: Rgba,
+    /// The width of the bones, in pixels.
+    pub line_width: f32,
+    /// The radius of the joints and end sites, in pixels.
+    pub joint_radius: f32,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for RenderOptions {
+    #[inline]
+    fn default() -> Self {
+        RenderOptions {
+            width: 256,
+            height: 256,
+            camera: Default::default(),
+            background: [255, 255, 255, 255],
+            bone_color: [40, 40, 40, 255],
+            joint_color: [200, 40, 40, 255],
+            end_site_color: [40, 90, 200, 255],
+            line_width: 2.0,
+            joint_radius: 2.5,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Create a new `RenderOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `width` and `height` on `self` to the new `width` and `height`.
+    #[inline]
+    pub const fn with_size(self, width: u32, height: u32) -> Self {
+        RenderOptions {
+            width,
+            height,
+            ..self
+        }
+    }
+
+    /// Sets `camera` on `self` to the new `Camera`.
+    #[inline]
+    pub fn with_camera(self, camera: Camera) -> Self {
+        RenderOptions { camera, ..self }
+    }
+
+    /// Replaces the camera of `self` with one which keeps the whole motion of
+    /// `bvh` in view, looking from the same direction as the current camera.
+    pub fn with_camera_framing(self, bvh: &Bvh) -> Self {
+        match Bounds::of_motion(bvh, 0..bvh.num_frames()) {
+            Some(bounds) => {
+                let aspect = self.width as f32 / self.height.max(1) as f32;
+                RenderOptions {
+                    camera: self.camera.framing(&bounds, aspect),
+                    ..self
+                }
+            }
+            None => self,
+        }
+    }
+
+    /// Sets `background` on `self` to the new `background`.
+    #[inline]
+    pub fn with_background(self, background: Rgba) -> Self {
+        RenderOptions { background, ..self }
+    }
+
+    /// Sets `bone_color` on `self` to the new `bone_color`.
+    #[inline]
+    pub fn with_bone_color(self, bone_color: Rgba) -> Self {
+        RenderOptions { bone_color, ..self }
+    }
+
+    /// Sets `joint_color` on `self` to the new `joint_color`.
+    #[inline]
+    pub fn with_joint_color(self, joint_color: Rgba) -> Self {
+        RenderOptions {
+            joint_color,
+            ..self
+        }
+    }
+
+    /// Sets `end_site_color` on `self` to the new `end_site_color`.
+    #[inline]
+    pub fn with_end_site_color(self, end_site_color: Rgba) -> Self {
+        RenderOptions {
+            end_site_color,
+            ..self
+        }
+    }
+
+    /// Sets `line_width` on `self` to the new `line_width`.
+    #[inline]
+    pub fn with_line_width(self, line_width: f32) -> Self {
+        RenderOptions { line_width, ..self }
+    }
+
+    /// Sets `joint_radius` on `self` to the new `joint_radius`.
+    #[inline]
+    pub fn with_joint_radius(self, joint_radius: f32) -> Self {
+        RenderOptions {
+            joint_radius,
+            ..self
+        }
+    }
+
+    /// Renders the pose of `bvh` at `frame` as an `svg` document.
+    ///
+    /// Returns `None` if `frame` is out of bounds.
+    pub fn render_svg(&self, bvh: &Bvh, frame: usize) -> Option<String> {
+        let pose = self.project_pose(bvh, bvh.frames().nth(frame)?);
+        let mut svg = self.svg_header(self.width);
+        self.svg_pose(&pose, 0.0, &mut svg);
+        svg.push_str("</svg>\n");
+        Some(svg)
+    }
+
+    /// Renders `count` evenly spaced frames of `bvh` side by side as an `svg`
+    /// document.
+    ///
+    /// Returns an error of kind `InvalidInput` if the filmstrip would be
+    /// wider than `u32::MAX` pixels.
+    pub fn render_filmstrip_svg(&self, bvh: &Bvh, count: usize) -> io::Result<String> {
+        let mut svg = self.svg_header(self.filmstrip_width(count)?);
+        for (i, frame) in filmstrip_frames(bvh, count).enumerate() {
+            let pose = self.project_pose(bvh, frame);
+            self.svg_pose(&pose, (i as u32 * self.width) as f32, &mut svg);
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    /// Renders the pose of `bvh` at `frame` as an `svg` document to the `writer`.
+    ///
+    /// Returns an error of kind `InvalidInput` if `frame` is out of bounds.
+    pub fn write_svg<W: Write>(&self, bvh: &Bvh, frame: usize, writer: &mut W) -> io::Result<()> {
+        let svg = self
+            .render_svg(bvh, frame)
+            .ok_or_else(|| frame_out_of_bounds(frame))?;
+        writer.write_all(svg.as_bytes())?;
+        writer.flush()
+    }
+
+    /// Renders the pose of `bvh` at `frame` into an `Image`.
+    ///
+    /// Returns `None` if `frame` is out of bounds.
+    pub fn render_image(&self, bvh: &Bvh, frame: usize) -> Option<Image> {
+        Some(self.render_frame(bvh, bvh.frames().nth(frame)?))
+    }
+
+    /// Renders `count` evenly spaced frames of `bvh` side by side into an `Image`.
+    ///
+    /// Returns an error of kind `InvalidInput` if the filmstrip would be
+    /// wider than `u32::MAX` pixels.
+    pub fn render_filmstrip_image(&self, bvh: &Bvh, count: usize) -> io::Result<Image> {
+        let width = self.filmstrip_width(count)?;
+        let mut image = Image::new(width, self.height, self.background);
+        for (i, frame) in filmstrip_frames(bvh, count).enumerate() {
+            let pose = self.project_pose(bvh, frame);
+            self.draw_pose(&mut image, &pose, (i as u32 * self.width) as f32);
+        }
+        Ok(image)
+    }
+
+    /// Renders the pose of `bvh` at `frame` as a `png` image to the `writer`.
+    ///
+    /// Returns an error of kind `InvalidInput` if `frame` is out of bounds.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, bvh: &Bvh, frame: usize, writer: W) -> io::Result<()> {
+        self.render_image(bvh, frame)
+            .ok_or_else(|| frame_out_of_bounds(frame))?
+            .write_png(writer)
+    }
+
+    fn render_frame(&self, bvh: &Bvh, frame: &Frame) -> Image {
+        let pose = self.project_pose(bvh, frame);
+        let mut image = Image::new(self.width, self.height, self.background);
+        self.draw_pose(&mut image, &pose, 0.0);
+        image
+    }
+
+    /// Returns the width of a filmstrip of `count` frames.
+    fn filmstrip_width(&self, count: usize) -> io::Result<u32> {
+        u32::try_from(count)
+            .ok()
+            .and_then(|count| self.width.checked_mul(count))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the filmstrip is too wide"))
+    }
+
+    fn project_pose(&self, bvh: &Bvh, frame: &Frame) -> ProjectedPose {
+        let transforms = kinematics::world_transforms(&bvh.joints, frame.as_slice());
+        let project = |p: Vec3| self.camera.project(p.into(), self.width, self.height);
+
+        let mut pose = ProjectedPose::default();
+        for (joint, transform) in bvh.joints.iter().zip(&transforms) {
+            let position = project(transform.translation);
+
+            if let (Some(parent), Some(position)) = (joint.parent_index(), position) {
+                if let Some(parent_position) = project(transforms[parent].translation) {
+                    pose.bones.push([parent_position, position]);
+                }
+            }
+
+            if let Some(end_site) = joint.end_site() {
+                let end_position = project(transform.transform_point(end_site.into()));
+                if let (Some(position), Some(end_position)) = (position, end_position) {
+                    pose.bones.push([position, end_position]);
+                    pose.end_sites.push(end_position);
+                }
+            }
+
+            pose.joints.extend(position);
+        }
+
+        pose
+    }
+
+    fn svg_header(&self, width: u32) -> String {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = width,
+            h = self.height,
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" {}/>"#,
+            svg_paint("fill", self.background)
+        );
+        svg
+    }
+
+    fn svg_pose(&self, pose: &ProjectedPose, x_offset: f32, svg: &mut String) {
+        let _ = writeln!(
+            svg,
+            r#"<g {} stroke-width="{}" stroke-linecap="round">"#,
+            svg_paint("stroke", self.bone_color),
+            self.line_width
+        );
+        for [a, b] in &pose.bones {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"/>"#,
+                a[0] + x_offset,
+                a[1],
+                b[0] + x_offset,
+                b[1]
+            );
+        }
+        svg.push_str("</g>\n");
+
+        for (points, color) in &[
+            (&pose.joints, self.joint_color),
+            (&pose.end_sites, self.end_site_color),
+        ] {
+            let _ = writeln!(svg, "<g {}>", svg_paint("fill", *color));
+            for p in points.iter() {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{:.2}" cy="{:.2}" r="{}"/>"#,
+                    p[0] + x_offset,
+                    p[1],
+                    self.joint_radius
+                );
+            }
+            svg.push_str("</g>\n");
+        }
+    }
+
+    fn draw_pose(&self, image: &mut Image, pose: &ProjectedPose, x_offset: f32) {
+        let offset = |p: [f32; 2]| [p[0] + x_offset, p[1]];
+        for [a, b] in &pose.bones {
+            image.draw_line(offset(*a), offset(*b), self.line_width, self.bone_color);
+        }
+
+        for p in &pose.joints {
+            image.draw_dot(offset(*p), self.joint_radius, self.joint_color);
+        }
+
+        for p in &pose.end_sites {
+            image.draw_dot(offset(*p), self.joint_radius, self.end_site_color);
+        }
+    }
+}
+
+/// Specify options for exporting a `Bvh` clip as an animated preview.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewOptions {
+    /// The options used to render each frame.
+    pub render: RenderOptions,
+    /// The first frame of the clip to export.
+    pub start_frame: usize,
+    /// The frame after the last frame of the clip to export, or `None` to
+    /// export up to the end of the clip.
+    pub end_frame: Option<usize>,
+    /// The number of frames per second of the preview, or `None` to use the
+    /// frame time of the clip.
+    pub frame_rate: Option<f64>,
+    /// Whether to replace the camera with one which keeps the whole exported
+    /// range of the clip in view.
+    pub auto_frame: bool,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for PreviewOptions {
+    #[inline]
+    fn default() -> Self {
+        PreviewOptions {
+            render: Default::default(),
+            start_frame: 0,
+            end_frame: None,
+            frame_rate: None,
+            auto_frame: true,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl PreviewOptions {
+    /// Create a new `PreviewOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `render` on `self` to the new `RenderOptions`.
+    #[inline]
+    pub fn with_render_options(self, render: RenderOptions) -> Self {
+        PreviewOptions { render, ..self }
+    }
+
+    /// Sets the range of frames to export to `start_frame..end_frame`.
+    #[inline]
+    pub fn with_frame_range(self, start_frame: usize, end_frame: usize) -> Self {
+        PreviewOptions {
+            start_frame,
+            end_frame: Some(end_frame),
+            ..self
+        }
+    }
+
+    /// Sets `frame_rate` on `self` to the new `frame_rate`, in frames per second.
+    #[inline]
+    pub fn with_frame_rate(self, frame_rate: f64) -> Self {
+        PreviewOptions {
+            frame_rate: Some(frame_rate),
+            ..self
+        }
+    }
+
+    /// Sets `auto_frame` on `self` to the new `auto_frame`.
+    #[inline]
+    pub fn with_auto_frame(self, auto_frame: bool) -> Self {
+        PreviewOptions { auto_frame, ..self }
+    }
+
+    /// Returns the indices of the frames of `bvh` which are shown by the
+    /// preview, in order.
+    pub fn frames(&self, bvh: &Bvh) -> Vec<usize> {
+        let range = self.frame_range(bvh);
+        let frame_time = duration_to_fractional_seconds(bvh.frame_time());
+
+        match self.frame_rate {
+            Some(rate) if rate > 0.0 && frame_time > 0.0 && !range.is_empty() => {
+                let duration = range.len() as f64 * frame_time;
+                let count = ((duration * rate).round() as usize).max(1);
+                (0..count)
+                    .map(|i| {
+                        let offset = (i as f64 / rate / frame_time).floor() as usize;
+                        (range.start + offset).min(range.end - 1)
+                    })
+                    .collect()
+            }
+            _ => range.collect(),
+        }
+    }
+
+    /// Returns the time for which each frame of the preview is shown.
+    pub fn frame_delay(&self, bvh: &Bvh) -> Duration {
+        match self.frame_rate {
+            Some(rate) if rate > 0.0 => fraction_seconds_to_duration(1.0 / rate),
+            _ => *bvh.frame_time(),
+        }
+    }
+
+    /// Renders every frame of the preview.
+    pub fn render_images(&self, bvh: &Bvh) -> Vec<Image> {
+        let options = self.render_options(bvh);
+        let frames = bvh.frames().collect::<Vec<_>>();
+        self.frames(bvh)
+            .into_iter()
+            .map(|frame| options.render_frame(bvh, frames[frame]))
+            .collect()
+    }
+
+    /// Writes every frame of the preview as a numbered sequence of `png`
+    /// files into `directory`, and returns the paths of the written files.
+    ///
+    /// The files are named `<prefix><number>.png`, where the number is padded
+    /// with zeroes to at least four digits.
+    #[cfg(feature = "png")]
+    pub fn write_png_sequence<P: AsRef<Path>>(
+        &self,
+        bvh: &Bvh,
+        directory: P,
+        prefix: &str,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for (i, image) in self.render_images(bvh).iter().enumerate() {
+            let path = directory.as_ref().join(format!("{}{:04}.png", prefix, i));
+            image.write_png(BufWriter::new(File::create(&path)?))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Writes the preview as a looping animated `gif` to the `writer`.
+    #[cfg(feature = "gif")]
+    pub fn write_gif<W: Write>(&self, bvh: &Bvh, writer: W) -> io::Result<()> {
+        use gif::SetParameter;
+
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "the gif is too large");
+        let width = to_u16(self.render.width).ok_or_else(too_large)?;
+        let height = to_u16(self.render.height).ok_or_else(too_large)?;
+
+        let delay = self.frame_delay(bvh);
+        let centiseconds = delay.as_secs() as f64 * 100.0 + f64::from(delay.subsec_nanos()) / 1.0e7;
+        let delay = centiseconds.round().clamp(1.0, f64::from(u16::MAX)) as u16;
+
+        let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+        encoder.set(gif::Repeat::Infinite)?;
+
+        for image in self.render_images(bvh) {
+            let mut pixels = image.pixels;
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+            frame.delay = delay;
+            encoder.write_frame(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn frame_range(&self, bvh: &Bvh) -> Range<usize> {
+        let end = self
+            .end_frame
+            .unwrap_or_else(|| bvh.num_frames())
+            .min(bvh.num_frames());
+        self.start_frame.min(end)..end
+    }
+
+    fn render_options(&self, bvh: &Bvh) -> RenderOptions {
+        if !self.auto_frame {
+            return self.render.clone();
+        }
+
+        match Bounds::of_motion(bvh, self.frame_range(bvh)) {
+            Some(bounds) => {
+                let aspect = self.render.width as f32 / self.render.height.max(1) as f32;
+                RenderOptions {
+                    camera: self.render.camera.framing(&bounds, aspect),
+                    ..self.render.clone()
+                }
+            }
+            None => self.render.clone(),
+        }
+    }
+}
+
+/// A pose of a skeleton projected into image space.
+#[derive(Debug, Default)]
+struct ProjectedPose {
+    bones: Vec<[[f32; 2]; 2]>,
+    joints: Vec<[f32; 2]>,
+    end_sites: Vec<[f32; 2]>,
+}
+
+/// An `RGBA` raster image, with 8 bits per component.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Create a new image of `width` by `height` pixels, filled with `color`.
+    pub fn new(width: u32, height: u32, color: Rgba) -> Self {
+        let num_pixels = width as usize * height as usize;
+        let mut pixels = Vec::with_capacity(num_pixels * 4);
+        for _ in 0..num_pixels {
+            pixels.extend_from_slice(&color);
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns the width of the image in pixels.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the pixels of the image, in row-major order.
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..]
+    }
+
+    /// Returns the colour of the pixel at `x` and `y`, or `None` if it
+    /// is outside of the image.
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.pixels[i..i + 4];
+        Some([p[0], p[1], p[2], p[3]])
+    }
+
+    /// Encodes the image as a `png` file to the `writer`.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()?
+            .write_image_data(&self.pixels)
+            .map_err(From::from)
+    }
+
+    /// Draws an antialiased line from `a` to `b`.
+    fn draw_line(&mut self, a: [f32; 2], b: [f32; 2], width: f32, color: Rgba) {
+        let radius = width * 0.5;
+        self.fill_coverage(
+            [a[0].min(b[0]) - radius, a[1].min(b[1]) - radius],
+            [a[0].max(b[0]) + radius, a[1].max(b[1]) + radius],
+            color,
+            |p| radius + 0.5 - distance_to_segment(p, a, b),
+        );
+    }
+
+    /// Draws an antialiased filled circle at `center`.
+    fn draw_dot(&mut self, center: [f32; 2], radius: f32, color: Rgba) {
+        self.fill_coverage(
+            [center[0] - radius, center[1] - radius],
+            [center[0] + radius, center[1] + radius],
+            color,
+            |p| radius + 0.5 - (p[0] - center[0]).hypot(p[1] - center[1]),
+        );
+    }
+
+    /// Blends `color` into every pixel between `min` and `max`, weighted by
+    /// `coverage` evaluated at the center of the pixel.
+    fn fill_coverage<F>(&mut self, min: [f32; 2], max: [f32; 2], color: Rgba, coverage: F)
+    where
+        F: Fn([f32; 2]) -> f32,
+    {
+        let clamp_x = |v: f32| v.clamp(0.0, self.width as f32) as u32;
+        let clamp_y = |v: f32| v.clamp(0.0, self.height as f32) as u32;
+        let (x0, x1) = (clamp_x(min[0].floor() - 1.0), clamp_x(max[0].ceil() + 1.0));
+        let (y0, y1) = (clamp_y(min[1].floor() - 1.0), clamp_y(max[1].ceil() + 1.0));
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let c = coverage([x as f32 + 0.5, y as f32 + 0.5]).clamp(0.0, 1.0);
+                if c > 0.0 {
+                    self.blend(x, y, color, c);
+                }
+            }
+        }
+    }
+
+    fn blend(&mut self, x: u32, y: u32, color: Rgba, coverage: f32) {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let dst = &mut self.pixels[i..i + 4];
+
+        let src_a = color[3] as f32 / 255.0 * coverage;
+        let dst_a = dst[3] as f32 / 255.0;
+        let out_a = src_a + dst_a * (1.0 - src_a);
+        if out_a <= 0.0 {
+            return;
+        }
+
+        for c in 0..3 {
+            let src = color[c] as f32;
+            let value = (src * src_a + dst[c] as f32 * dst_a * (1.0 - src_a)) / out_a;
+            dst[c] = value.round().clamp(0.0, 255.0) as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+    }
+}
+
+/// Returns the world-space positions of every joint and end site of `bvh`
+/// in `frame`.
+fn world_points(bvh: &Bvh, frame: &Frame) -> Vec<Vec3> {
+    let transforms = kinematics::world_transforms(&bvh.joints, frame.as_slice());
+    let mut points = Vec::with_capacity(transforms.len() * 2);
+    for (joint, transform) in bvh.joints.iter().zip(&transforms) {
+        points.push(transform.translation);
+        if let Some(end_site) = joint.end_site() {
+            points.push(transform.transform_point(end_site.into()));
+        }
+    }
+    points
+}
+
+#[cfg(feature = "gif")]
+#[inline]
+fn to_u16(value: u32) -> Option<u16> {
+    if value <= u32::from(u16::MAX) {
+        Some(value as u16)
+    } else {
+        None
+    }
+}
+
+/// Returns `count` evenly spaced frames of `bvh`, including the first and
+/// last frames.
+fn filmstrip_frames(bvh: &Bvh, count: usize) -> impl Iterator<Item = &Frame> {
+    let frames = bvh.frames().collect::<Vec<_>>();
+    let last = frames.len().saturating_sub(1);
+    (0..count)
+        .map(move |i| {
+            if count <= 1 {
+                0
+            } else {
+                ((i * last) as f32 / (count - 1) as f32).round() as usize
+            }
+        })
+        .filter_map(move |i| frames.get(i).copied())
+}
+
+#[inline]
+fn distance_to_segment(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let (abx, aby) = (b[0] - a[0], b[1] - a[1]);
+    let (apx, apy) = (p[0] - a[0], p[1] - a[1]);
+    let len_sq = abx * abx + aby * aby;
+    let t = if len_sq <= EPSILON {
+        0.0
+    } else {
+        ((apx * abx + apy * aby) / len_sq).clamp(0.0, 1.0)
+    };
+    (apx - t * abx).hypot(apy - t * aby)
+}
+
+#[inline]
+fn svg_paint(attribute: &str, color: Rgba) -> String {
+    let mut paint = format!(
+        r#"{}="rgb({},{},{})""#,
+        attribute, color[0], color[1], color[2]
+    );
+    if color[3] != 255 {
+        let _ = write!(paint, r#" {}-opacity="{:.3}""#, attribute, color[3] as f32 / 255.0);
+    }
+    paint
+}
+
+#[inline]
+fn frame_out_of_bounds(frame: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("frame {} is out of bounds", frame),
+    )
+}
@@ -0,0 +1,408 @@
+//! The rest pose of a `Bvh`, and converting a clip to a new rest pose.
+//!
+//! The rest pose of a skeleton is its pose when every channel is zero, which
+//! is described entirely by the offsets of the joints. Different exporters
+//! choose different rest poses, most often a T-pose, with the arms held out
+//! horizontally, or an A-pose, with the arms angled downwards.
+//!
+//! The rest pose can be changed by rotating the bones of a joint, which are
+//! the offsets of its children and of its end site. The motion values of
+//! every frame are changed to compensate, so that the world positions of the
+//! joints are unchanged in each frame, and only the rotations of the joints
+//! relative to the rest pose differ.
+//!
+//! Arms are found from the shape of the skeleton rather than from the names
+//! of the joints: an arm is a chain of joints which branches off a joint
+//! other than the root, and which points sideways rather than up or down.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, rest_pose::{RestPose, RestPoseOptions}};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Chest
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             JOINT LeftArm
+//!             {
+//!                 OFFSET 2.0 0.0 0.0
+//!                 CHANNELS 3 Zrotation Xrotation Yrotation
+//!                 End Site
+//!                 {
+//!                     OFFSET 5.0 -5.0 0.0
+//!                 }
+//!             }
+//!             JOINT RightArm
+//!             {
+//!                 OFFSET -2.0 0.0 0.0
+//!                 CHANNELS 3 Zrotation Xrotation Yrotation
+//!                 End Site
+//!                 {
+//!                     OFFSET -5.0 -5.0 0.0
+//!                 }
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! let options = RestPoseOptions::new();
+//! assert_eq!(options.detect(&bvh), RestPose::APose);
+//!
+//! let before = bvh.world_positions(0).unwrap();
+//! options.to_t_pose(&mut bvh).unwrap();
+//! assert_eq!(options.detect(&bvh), RestPose::TPose);
+//!
+//! // The arm points sideways in the rest pose, but is still rotated down by
+//! // the motion.
+//! let left_arm = bvh.joints().find_by_name("LeftArm").unwrap();
+//! let end_site = left_arm.data().end_site().unwrap();
+//! assert!(end_site.y.abs() < 1.0e-4);
+//! assert!((bvh.world_positions(0).unwrap()[2].x - before[2].x).abs() < 1.0e-4);
+//! ```
+
+use crate::{
+    errors::RestPoseError,
+    hierarchy::Hierarchy,
+    joint::joint_index_by_name,
+    kinematics::{
+        local_rotation, pose_preserved, position_channels, set_position_channels,
+        set_rotation_channels, world_transforms,
+    },
+    math::{Quat, Vec3, EPSILON},
+    Axis, Bvh, Joint, JointData,
+};
+use bstr::{BString, ByteSlice};
+use mint::Vector3;
+
+/// The range of angles, in degrees above the horizontal plane, in which a
+/// chain of joints is considered to be an arm.
+const ARM_ELEVATION: (f32, f32) = (-75.0, 45.0);
+
+/// The kind of rest pose of a skeleton.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RestPose {
+    /// The arms are held out horizontally.
+    TPose,
+    /// The arms are angled downwards.
+    APose,
+    /// The arms are raised, or no arms were found.
+    Other,
+}
+
+/// Specify options for detecting and normalising the rest pose of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestPoseOptions {
+    /// The axis which points up in the rest pose. Defaults to `Axis::Y`.
+    pub up_axis: Axis,
+    /// The largest angle, in degrees, between the arms and the horizontal
+    /// plane for the rest pose to be a T-pose. Defaults to `15.0`.
+    pub tolerance: f32,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for RestPoseOptions {
+    #[inline]
+    fn default() -> Self {
+        RestPoseOptions {
+            up_axis: Axis::Y,
+            tolerance: 15.0,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl RestPoseOptions {
+    /// Create a new `RestPoseOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `up_axis` on `self` to the new `Axis`.
+    #[inline]
+    pub fn with_up_axis(self, up_axis: Axis) -> Self {
+        RestPoseOptions { up_axis, ..self }
+    }
+
+    /// Sets `tolerance` on `self` to the new angle, in degrees.
+    #[inline]
+    pub fn with_tolerance(self, tolerance: f32) -> Self {
+        RestPoseOptions { tolerance, ..self }
+    }
+
+    /// Returns the average angle, in degrees, of the arms of `bvh` above the
+    /// horizontal plane in the rest pose, or `None` if no arms were found.
+    pub fn arm_angle(&self, bvh: &Bvh) -> Option<f32> {
+        let arms = self.arms(&bvh.joints);
+        if arms.is_empty() {
+            return None;
+        }
+
+        let total = arms.iter().map(|arm| arm.elevation).sum::<f32>();
+        Some(total / arms.len() as f32)
+    }
+
+    /// Detects whether the rest pose of `bvh` is a T-pose or an A-pose.
+    pub fn detect(&self, bvh: &Bvh) -> RestPose {
+        match self.arm_angle(bvh) {
+            Some(angle) if angle.abs() <= self.tolerance => RestPose::TPose,
+            Some(angle) if angle < -self.tolerance => RestPose::APose,
+            _ => RestPose::Other,
+        }
+    }
+
+    /// Changes the rest pose of `bvh` to a T-pose, keeping the world
+    /// positions of the joints in each frame.
+    ///
+    /// Every bone of each arm is rotated to point sideways along the
+    /// horizontal plane, and the joints beyond the end of the arm, such as
+    /// fingers, are rotated with the last bone of the arm.
+    ///
+    /// Returns an error without changing `bvh` if no arms were found, or if
+    /// the channels of a joint cannot describe its pose relative to the new
+    /// rest pose.
+    pub fn to_t_pose(&self, bvh: &mut Bvh) -> Result<(), RestPoseError> {
+        let arms = self.arms(&bvh.joints);
+        if arms.is_empty() {
+            return Err(RestPoseError::MissingArms);
+        }
+
+        let hierarchy = Hierarchy::new(&bvh.joints);
+        let mut rotations = vec![Quat::identity(); bvh.joints.len()];
+        for arm in &arms {
+            let mut rotation = Quat::identity();
+            for &index in &arm.joints {
+                let bone = bone(&bvh.joints, index);
+                if bone.length() > EPSILON {
+                    rotation = Quat::from_to(bone, arm.sideways);
+                }
+                rotations[index] = rotation;
+            }
+
+            let last = arm.joints[arm.joints.len() - 1];
+            for (index, value) in rotations.iter_mut().enumerate() {
+                if hierarchy.is_descendant(index, last) {
+                    *value = rotation;
+                }
+            }
+        }
+
+        rotate_bones(bvh, &rotations)
+    }
+
+    /// Returns the arms of the skeleton made of `joints`.
+    fn arms(&self, joints: &[JointData]) -> Vec<Arm> {
+        let rest = rest_positions(joints);
+        let up = Vec3::axis(self.up_axis);
+        let horizontal = |v: Vec3| v - up * v.dot(up);
+
+        // Joints are in pre-order, so the joints of an arm, such as fingers,
+        // are marked before they are visited.
+        let mut in_arm = vec![false; joints.len()];
+        let mut arms = Vec::new();
+        for branch in joints.iter().filter(|j| !j.is_root()) {
+            if let Some(parent) = branch.parent_index() {
+                in_arm[branch.index()] |= in_arm[parent];
+            }
+            let starts = Joint {
+                index: branch.index(),
+                joints,
+            }
+            .children()
+            .map(|child| child.index)
+            .collect::<Vec<_>>();
+            if in_arm[branch.index()] || starts.len() < 2 {
+                continue;
+            }
+
+            for start in starts {
+                let mut chain = vec![start];
+                loop {
+                    let last = Joint {
+                        index: chain[chain.len() - 1],
+                        joints,
+                    };
+                    let mut next = last.children();
+                    match (next.next(), next.next()) {
+                        (Some(child), None) => chain.push(child.index),
+                        _ => break,
+                    }
+                }
+
+                let last = chain[chain.len() - 1];
+                let end = rest[last]
+                    + joints[last]
+                        .end_site()
+                        .map(Vec3::from)
+                        .unwrap_or_else(Vec3::zero);
+                let direction = end - rest[start];
+                let sideways = horizontal(end - rest[branch.index()]);
+                if direction.length() <= EPSILON || sideways.length() <= EPSILON {
+                    continue;
+                }
+
+                let elevation = direction
+                    .dot(up)
+                    .atan2(horizontal(direction).length())
+                    .to_degrees();
+                if elevation >= ARM_ELEVATION.0 && elevation <= ARM_ELEVATION.1 {
+                    for &index in &chain {
+                        in_arm[index] = true;
+                    }
+                    arms.push(Arm {
+                        joints: chain,
+                        elevation,
+                        sideways: sideways.normalize(),
+                    });
+                }
+            }
+        }
+        arms
+    }
+}
+
+/// A chain of joints which forms an arm.
+struct Arm {
+    /// The indices of the joints, from the shoulder to the hand.
+    joints: Vec<usize>,
+    /// The angle, in degrees, of the arm above the horizontal plane.
+    elevation: f32,
+    /// The horizontal direction in which the arm points.
+    sideways: Vec3,
+}
+
+impl Bvh {
+    /// Computes the world-space position of each joint in the rest pose,
+    /// where every channel is zero.
+    ///
+    /// The returned array is indexed by [`JointData::index`][`JointData::index`].
+    ///
+    /// [`JointData::index`]: enum.JointData.html#method.index
+    pub fn rest_positions(&self) -> Vec<Vector3<f32>> {
+        rest_positions(&self.joints)
+            .into_iter()
+            .map(Vector3::from)
+            .collect()
+    }
+
+    /// Changes the rest pose by rotating the bones of each named joint to
+    /// point along the given direction, keeping the world positions of the
+    /// joints in each frame.
+    ///
+    /// The bone of a joint is the offset of its only child or of its end
+    /// site, or the sum of the offsets of its children if it has several.
+    ///
+    /// Returns an error without changing the `Bvh` if a joint does not exist
+    /// or has no bone, or if the channels of a joint cannot describe its pose
+    /// relative to the new rest pose.
+    pub fn set_bone_directions(
+        &mut self,
+        directions: &[(&str, Vector3<f32>)],
+    ) -> Result<(), RestPoseError> {
+        let mut rotations = vec![Quat::identity(); self.joints.len()];
+        for &(name, direction) in directions {
+            let index = joint_index_by_name(&self.joints, name.as_bytes(), |name| {
+                RestPoseError::MissingJoint { name }
+            })?;
+
+            let bone = bone(&self.joints, index);
+            if bone.length() <= EPSILON {
+                return Err(RestPoseError::MissingBone {
+                    name: BString::from(name),
+                });
+            }
+            rotations[index] = Quat::from_to(bone, direction.into());
+        }
+
+        rotate_bones(self, &rotations)
+    }
+}
+
+/// Returns the world-space position of each joint when every channel is zero.
+fn rest_positions(joints: &[JointData]) -> Vec<Vec3> {
+    let num_channels = joints.iter().map(|j| j.channels().len()).sum();
+    world_transforms(joints, &vec![0.0; num_channels])
+        .into_iter()
+        .map(|t| t.translation)
+        .collect()
+}
+
+/// Returns the bone of the joint at `index`, which is the sum of the offsets
+/// of its children, or its end site if it has no children.
+fn bone(joints: &[JointData], index: usize) -> Vec3 {
+    let joint = Joint { index, joints };
+    let mut children = joint.children().peekable();
+    if children.peek().is_none() {
+        return joints[index]
+            .end_site()
+            .map(Vec3::from)
+            .unwrap_or_else(Vec3::zero);
+    }
+
+    children.fold(Vec3::zero(), |acc, child| {
+        acc + child.data().offset().into()
+    })
+}
+
+/// Rotates the bones of each joint of `bvh` by the rotation in `rotations`,
+/// and changes the motion values so that the world positions of the joints
+/// are unchanged.
+fn rotate_bones(bvh: &mut Bvh, rotations: &[Quat]) -> Result<(), RestPoseError> {
+    let parent_rotation = |joint: &JointData| {
+        joint
+            .parent_index()
+            .map(|parent| rotations[parent])
+            .unwrap_or_else(Quat::identity)
+    };
+
+    let mut edited = bvh.clone();
+    for (joint, &rotation) in edited.joints.iter_mut().zip(rotations) {
+        let offset = parent_rotation(joint).rotate(joint.offset().into());
+        let end_site = joint.end_site().map(|end| rotation.rotate(end.into()));
+        joint.set_offset(offset.into(), false);
+        if let Some(end_site) = end_site {
+            joint.set_offset(end_site.into(), true);
+        }
+    }
+
+    // A joint whose bones are rotated by `a`, and whose parent's bones are
+    // rotated by `p`, keeps its world position if its local rotation `q`
+    // becomes `p * q * a⁻¹` and its position channels are rotated by `p`.
+    let num_channels = bvh.num_channels;
+    for frame in 0..bvh.num_frames {
+        let original = &bvh.motion_values[frame * num_channels..][..num_channels];
+        let values = &mut edited.motion_values[frame * num_channels..][..num_channels];
+        for (joint, &rotation) in edited.joints.iter().zip(rotations) {
+            let parent = parent_rotation(joint);
+            if parent.angle() <= EPSILON && rotation.angle() <= EPSILON {
+                continue;
+            }
+
+            let channels = joint.channels();
+            let new_rotation = parent * local_rotation(joint, original) * rotation.conjugate();
+            let new_position = parent.rotate(position_channels(joint, original));
+            set_rotation_channels(channels, values, new_rotation);
+            set_position_channels(channels, values, new_position);
+            if !pose_preserved(channels, values, new_rotation, new_position) {
+                return Err(RestPoseError::PoseNotPreserved {
+                    name: BString::from(joint.name().as_bytes()),
+                    frame,
+                });
+            }
+        }
+    }
+
+    *bvh = edited;
+    Ok(())
+}
@@ -0,0 +1,375 @@
+//! Separates the motion of the root joint of a `Bvh` from the rest of its
+//! animation.
+//!
+//! The root motion of a clip is the translation of the root joint across the
+//! ground, and its heading (the rotation of the root around the up axis).
+//! Game engines usually move characters with a separate root motion track,
+//! and play animations which stay in place.
+//!
+//! `RootMotionOptions::extract` reads the root motion of a clip,
+//! `RootMotionOptions::in_place` removes it from the clip, and
+//! `RootMotion::apply` adds it back.
+//!
+//! The translation is read from the position channels of the root joint,
+//! and the rotation channels are rewritten in the order in which they are
+//! listed.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, root_motion::RootMotionOptions};
+//! let mut bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Hips
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+//!         JOINT Head
+//!         {
+//!             OFFSET 0.0 10.0 0.0
+//!             CHANNELS 0
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 5.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 2
+//!     Frame Time: 0.033333333
+//!     0.0 90.0 0.0 0.0 0.0 0.0
+//!     5.0 90.0 2.0 0.0 0.0 30.0
+//! };
+//!
+//! let root_motion = RootMotionOptions::new().in_place(&mut bvh);
+//! assert_eq!(root_motion.translations()[1], [5.0, 0.0, 2.0].into());
+//! assert!((root_motion.yaws()[1] - 30.0).abs() < 1.0e-4);
+//!
+//! // The root stays over the origin, facing forwards.
+//! let frame = bvh.frames().nth(1).unwrap().as_slice();
+//! assert_eq!(frame[0], 0.0);
+//! assert_eq!(frame[1], 90.0);
+//! assert_eq!(frame[2], 0.0);
+//! assert!(frame[5].abs() < 1.0e-4);
+//! ```
+
+use crate::{
+    errors::RootMotionError,
+    euler::unwrap_angle,
+    kinematics::{
+        local_rotation, position_channel, position_channels, position_from_channels,
+        rotation_channel, rotation_from_channels, set_position_channels, set_rotation_channels,
+    },
+    math::{wrap_degrees, Quat, Vec3, EPSILON},
+    Axis, Bvh, Channel, ChannelType,
+};
+use mint::Vector3;
+
+/// The translation across the ground and the heading of the root joint of a
+/// `Bvh`, in each frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootMotion {
+    up_axis: Axis,
+    translations: Vec<Vector3<f32>>,
+    yaws: Vec<f32>,
+}
+
+impl RootMotion {
+    /// Create a new, empty `RootMotion` track with the up axis `up_axis`.
+    #[inline]
+    pub fn new(up_axis: Axis) -> Self {
+        RootMotion {
+            up_axis,
+            translations: Vec::new(),
+            yaws: Vec::new(),
+        }
+    }
+
+    /// Adds a frame to the end of the track. The component of `translation`
+    /// along the up axis is ignored, and `yaw` is the rotation around the up
+    /// axis in degrees.
+    pub fn push(&mut self, translation: Vector3<f32>, yaw: f32) {
+        let mut translation = Vec3::from(translation);
+        translation.set(self.up_axis, 0.0);
+        self.translations.push(translation.into());
+        self.yaws.push(yaw);
+    }
+
+    /// The axis which points up, away from the ground.
+    #[inline]
+    pub fn up_axis(&self) -> Axis {
+        self.up_axis
+    }
+
+    /// The number of frames in the track.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.yaws.len()
+    }
+
+    /// Returns `true` if the track has no frames.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.yaws.is_empty()
+    }
+
+    /// The translation of the root across the ground in each frame. The
+    /// component along the up axis is always zero.
+    #[inline]
+    pub fn translations(&self) -> &[Vector3<f32>] {
+        &self.translations[..]
+    }
+
+    /// The rotation of the root around the up axis in each frame, in
+    /// degrees. The angles are unwrapped, so that they change smoothly
+    /// between frames.
+    #[inline]
+    pub fn yaws(&self) -> &[f32] {
+        &self.yaws[..]
+    }
+
+    /// Adds the root motion back onto the root joint of `bvh`, which is
+    /// usually a clip which was made in place with
+    /// [`RootMotionOptions::in_place`][`RootMotionOptions::in_place`].
+    ///
+    /// The position of the root in each frame is rotated by the yaw and then
+    /// moved by the translation of the track, and the yaw is applied to the
+    /// rotation of the root. Returns an error without changing `bvh` if the
+    /// number of frames differs, or if the root joint does not have the
+    /// channels needed to store the motion.
+    ///
+    /// [`RootMotionOptions::in_place`]: struct.RootMotionOptions.html#method.in_place
+    pub fn apply(&self, bvh: &mut Bvh) -> Result<(), RootMotionError> {
+        if self.len() != bvh.num_frames {
+            return Err(RootMotionError::FrameCountMismatch {
+                expected: bvh.num_frames,
+                found: self.len(),
+            });
+        }
+
+        let root = match bvh.joints.first() {
+            Some(root) => root.channels().to_vec(),
+            None => return Ok(()),
+        };
+
+        let (first, second) = ground_axes(self.up_axis);
+        let needs_axis = |axis: Axis| {
+            self.translations
+                .iter()
+                .any(|t| Vec3::from(*t).get(axis) != 0.0)
+        };
+        for &axis in &[first, second] {
+            if needs_axis(axis) && !has_channel(&root, position_channel(axis)) {
+                return Err(RootMotionError::MissingChannel {
+                    channel_type: position_channel(axis),
+                });
+            }
+        }
+        let needs_rotation = self.yaws.iter().any(|&yaw| yaw != 0.0);
+        if needs_rotation && !root.iter().any(|c| c.channel_type().is_rotation()) {
+            return Err(RootMotionError::MissingChannel {
+                channel_type: rotation_channel(self.up_axis),
+            });
+        }
+
+        let num_channels = bvh.num_channels;
+        let frames = bvh.motion_values.chunks_mut(num_channels).take(self.len());
+        for (frame, values) in frames.enumerate() {
+            let heading = Quat::from_axis_degrees(self.up_axis, self.yaws[frame]);
+            let position = position_from(&root, values);
+            let rotation = rotation_from(&root, values);
+
+            let position = Vec3::from(self.translations[frame]) + heading.rotate(position);
+            set_position_channels(&root, values, position);
+            if needs_rotation {
+                set_rotation_channels(&root, values, heading * rotation);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Specify options for extracting the root motion of a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootMotionOptions {
+    /// The axis which points up, away from the ground. Defaults to `Axis::Y`.
+    pub up_axis: Axis,
+    /// Whether the heading of the root is extracted along with its
+    /// translation. Defaults to `true`.
+    pub yaw: bool,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for RootMotionOptions {
+    #[inline]
+    fn default() -> Self {
+        RootMotionOptions {
+            up_axis: Axis::Y,
+            yaw: true,
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl RootMotionOptions {
+    /// Create a new `RootMotionOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `up_axis` on `self` to the new `Axis`.
+    #[inline]
+    pub fn with_up_axis(self, up_axis: Axis) -> Self {
+        RootMotionOptions { up_axis, ..self }
+    }
+
+    /// Sets `yaw` on `self` to the new value.
+    #[inline]
+    pub fn with_yaw(self, yaw: bool) -> Self {
+        RootMotionOptions { yaw, ..self }
+    }
+
+    /// Extracts the root motion of `bvh`, without changing it.
+    pub fn extract(&self, bvh: &Bvh) -> RootMotion {
+        let mut root_motion = RootMotion::new(self.up_axis);
+        let root = match bvh.joints.first() {
+            Some(root) => root,
+            None => return root_motion,
+        };
+
+        let mut previous_yaw = None;
+        for frame in bvh.frames() {
+            let translation = position_channels(root, frame.as_slice());
+            let yaw = if self.yaw {
+                let yaw = heading_degrees(local_rotation(root, frame.as_slice()), self.up_axis);
+                let yaw = match previous_yaw {
+                    Some(previous) => unwrap_angle(previous, yaw),
+                    None => yaw,
+                };
+                previous_yaw = Some(yaw);
+                yaw
+            } else {
+                0.0
+            };
+            root_motion.push(translation.into(), yaw);
+        }
+
+        root_motion
+    }
+
+    /// Extracts the root motion of `bvh`, and then removes it, so that the
+    /// root joint stays over the origin and faces the same way in every
+    /// frame.
+    ///
+    /// Returns the root motion which was removed, which can be added back
+    /// with [`RootMotion::apply`][`RootMotion::apply`].
+    ///
+    /// [`RootMotion::apply`]: struct.RootMotion.html#method.apply
+    pub fn in_place(&self, bvh: &mut Bvh) -> RootMotion {
+        let root_motion = self.extract(bvh);
+        let root = match bvh.joints.first() {
+            Some(root) => root.channels().to_vec(),
+            None => return root_motion,
+        };
+
+        let num_channels = bvh.num_channels;
+        let frames = bvh.motion_values.chunks_mut(num_channels);
+        for (frame, values) in frames.take(root_motion.len()).enumerate() {
+            let heading =
+                Quat::from_axis_degrees(self.up_axis, root_motion.yaws[frame]).conjugate();
+            let position = position_from(&root, values);
+            let rotation = rotation_from(&root, values);
+
+            let translation = Vec3::from(root_motion.translations[frame]);
+            set_position_channels(&root, values, heading.rotate(position - translation));
+            if self.yaw {
+                set_rotation_channels(&root, values, heading * rotation);
+            }
+        }
+
+        root_motion
+    }
+}
+
+/// Returns the heading of `rotation` around `up_axis` in degrees, which is
+/// the angle of its forward axis when projected onto the ground.
+///
+/// If the forward axis points straight up or down, the angle of the right
+/// axis is used instead.
+fn heading_degrees(rotation: Quat, up_axis: Axis) -> f32 {
+    let (right, forward) = ground_axes(up_axis);
+    let f = rotation.rotate(Vec3::axis(forward));
+    let heading = if f.get(right).powi(2) + f.get(forward).powi(2) > EPSILON {
+        f.get(right).atan2(f.get(forward))
+    } else {
+        let r = rotation.rotate(Vec3::axis(right));
+        (-r.get(forward)).atan2(r.get(right))
+    };
+    wrap_degrees(heading.to_degrees())
+}
+
+/// Returns the right and forward axes, which span the ground, such that a
+/// positive rotation around `up_axis` turns the forward axis towards the
+/// right axis.
+#[inline]
+fn ground_axes(up_axis: Axis) -> (Axis, Axis) {
+    match up_axis {
+        Axis::X => (Axis::Z, Axis::Y),
+        Axis::Y => (Axis::X, Axis::Z),
+        Axis::Z => (Axis::Y, Axis::X),
+    }
+}
+
+#[inline]
+fn has_channel(channels: &[Channel], channel_type: ChannelType) -> bool {
+    channels.iter().any(|c| c.channel_type() == channel_type)
+}
+
+#[inline]
+fn position_from(channels: &[Channel], frame: &[f32]) -> Vec3 {
+    position_from_channels(channels.iter().cloned(), frame)
+}
+
+#[inline]
+fn rotation_from(channels: &[Channel], frame: &[f32]) -> Quat {
+    rotation_from_channels(channels.iter().cloned(), frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::heading_degrees;
+    use crate::{math::Quat, Axis};
+
+    #[test]
+    fn heading_of_rotation() {
+        for &up_axis in &[Axis::X, Axis::Y, Axis::Z] {
+            for &angle in &[0.0, 40.0, -170.0] {
+                let yaw = Quat::from_axis_degrees(up_axis, angle);
+                let heading = heading_degrees(yaw, up_axis);
+                assert!(
+                    (heading - angle).abs() < 1.0e-3,
+                    "{:?} {}",
+                    up_axis,
+                    heading
+                );
+            }
+        }
+
+        // Removing the heading from the left leaves a rotation which faces
+        // forwards.
+        let rotation = Quat::from_axis_degrees(Axis::Z, 5.0)
+            * Quat::from_axis_degrees(Axis::X, 10.0)
+            * Quat::from_axis_degrees(Axis::Y, 70.0);
+        let heading = heading_degrees(rotation, Axis::Y);
+        let remaining = Quat::from_axis_degrees(Axis::Y, heading).conjugate() * rotation;
+        assert!(heading_degrees(remaining, Axis::Y).abs() < 1.0e-3);
+
+        // A forward axis which points straight up falls back to the right axis.
+        let pitched =
+            Quat::from_axis_degrees(Axis::Y, 30.0) * Quat::from_axis_degrees(Axis::X, -90.0);
+        assert!((heading_degrees(pitched, Axis::Y) - 30.0).abs() < 1.0e-2);
+    }
+}
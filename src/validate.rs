@@ -0,0 +1,523 @@
+//! Checks a `Bvh` for common problems in its skeleton and motion.
+//!
+//! Files exported by different tools often parse correctly, but contain data
+//! which will cause problems further down the line, such as duplicate joint
+//! names or `NaN` motion values. The [`ValidateOptions::validate`]
+//! [`ValidateOptions::validate`] method reports each of these problems as a
+//! [`Diagnostic`][`Diagnostic`].
+//!
+//! # Examples
+//!
+//! ```
+//! # use bvh_anim::{bvh, validate::{DiagnosticKind, Severity}};
+//! let bvh = bvh! {
+//!     HIERARCHY
+//!     ROOT Base
+//!     {
+//!         OFFSET 0.0 0.0 0.0
+//!         CHANNELS 3 Zrotation Xrotation Yrotation
+//!         JOINT Base
+//!         {
+//!             OFFSET 0.0 1.0 0.0
+//!             CHANNELS 3 Zrotation Xrotation Yrotation
+//!             End Site
+//!             {
+//!                 OFFSET 0.0 1.0 0.0
+//!             }
+//!         }
+//!     }
+//!     MOTION
+//!     Frames: 1
+//!     Frame Time: 0.033333333
+//!     0.0 0.0 0.0 0.0 0.0 0.0
+//! };
+//!
+//! let diagnostics = bvh.validate();
+//! assert_eq!(diagnostics.len(), 1);
+//! assert_eq!(diagnostics[0].severity(), Severity::Error);
+//! assert_eq!(diagnostics[0].joint(), Some(1));
+//! assert_eq!(
+//!     diagnostics[0].kind(),
+//!     &DiagnosticKind::DuplicateJointName { first_index: 0 },
+//! );
+//! ```
+//!
+//! [`ValidateOptions::validate`]: struct.ValidateOptions.html#method.validate
+//! [`Diagnostic`]: struct.Diagnostic.html
+
+use crate::{math::Vec3, Axis, Bvh, ChannelType};
+use smallvec::SmallVec;
+use std::{fmt, time::Duration};
+
+/// How serious a problem reported by a `Diagnostic` is.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// The data is unusual, but can still be used.
+    Warning,
+    /// The data is invalid, and is likely to cause problems when it is used.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// The kind of problem reported by a `Diagnostic`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// The joint has the same name as an earlier joint.
+    DuplicateJointName {
+        /// The index of the first joint with the same name.
+        first_index: usize,
+    },
+    /// The joint has an empty name.
+    EmptyJointName,
+    /// A channel of the joint has `NaN` or infinite motion values.
+    NonFiniteMotion {
+        /// The motion index of the channel.
+        motion_index: usize,
+        /// The first frame with a non-finite value.
+        first_frame: usize,
+        /// The number of frames with a non-finite value.
+        num_frames: usize,
+    },
+    /// The joint is at the same position as its parent.
+    ZeroLengthBone,
+    /// The joint has more than one channel of the same type.
+    RepeatedChannel {
+        /// The type of the repeated channel.
+        channel_type: ChannelType,
+    },
+    /// The joint is not the root, but has position channels.
+    PositionChannelOnChild {
+        /// The type of the position channel.
+        channel_type: ChannelType,
+    },
+    /// The order of the rotation channels of the joint is different from
+    /// the order used by the first joint with rotation channels.
+    InconsistentRotationOrder {
+        /// The rotation order of the first joint with rotation channels.
+        expected: SmallVec<[Axis; 3]>,
+        /// The rotation order of this joint.
+        found: SmallVec<[Axis; 3]>,
+    },
+    /// The joint is not the root, but has no children and no end site.
+    MissingEndSite,
+    /// The end site of the joint is at the same position as the joint.
+    ZeroLengthEndSite,
+    /// The frame time is outside of the range set in the `ValidateOptions`.
+    SuspiciousFrameTime {
+        /// The frame time of the `Bvh`.
+        frame_time: Duration,
+    },
+}
+
+impl DiagnosticKind {
+    /// Returns the `Severity` of this kind of problem.
+    pub fn severity(&self) -> Severity {
+        match *self {
+            DiagnosticKind::DuplicateJointName { .. }
+            | DiagnosticKind::EmptyJointName
+            | DiagnosticKind::NonFiniteMotion { .. }
+            | DiagnosticKind::RepeatedChannel { .. } => Severity::Error,
+            DiagnosticKind::ZeroLengthBone
+            | DiagnosticKind::PositionChannelOnChild { .. }
+            | DiagnosticKind::InconsistentRotationOrder { .. }
+            | DiagnosticKind::MissingEndSite
+            | DiagnosticKind::ZeroLengthEndSite
+            | DiagnosticKind::SuspiciousFrameTime { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fmt_order = |f: &mut fmt::Formatter<'_>, order: &[Axis]| -> fmt::Result {
+            for axis in order {
+                write!(f, "{}", axis)?;
+            }
+            Ok(())
+        };
+
+        match *self {
+            DiagnosticKind::DuplicateJointName { first_index } => {
+                write!(f, "the joint name is already used by joint {}", first_index)
+            }
+            DiagnosticKind::EmptyJointName => f.write_str("the joint name is empty"),
+            DiagnosticKind::NonFiniteMotion {
+                motion_index,
+                first_frame,
+                num_frames,
+            } => write!(
+                f,
+                "channel {} has {} non-finite motion values, starting at frame {}",
+                motion_index, num_frames, first_frame
+            ),
+            DiagnosticKind::ZeroLengthBone => f.write_str("the joint has a zero length offset"),
+            DiagnosticKind::RepeatedChannel { channel_type } => {
+                write!(f, "the {} channel is repeated", channel_type)
+            }
+            DiagnosticKind::PositionChannelOnChild { channel_type } => write!(
+                f,
+                "the {} channel is only expected on the root joint",
+                channel_type
+            ),
+            DiagnosticKind::InconsistentRotationOrder {
+                ref expected,
+                ref found,
+            } => {
+                f.write_str("the rotation order ")?;
+                fmt_order(f, found)?;
+                f.write_str(" is different from the rotation order ")?;
+                fmt_order(f, expected)
+            }
+            DiagnosticKind::MissingEndSite => {
+                f.write_str("the joint has no children and no end site")
+            }
+            DiagnosticKind::ZeroLengthEndSite => {
+                f.write_str("the end site of the joint has a zero length offset")
+            }
+            DiagnosticKind::SuspiciousFrameTime { frame_time } => {
+                write!(f, "the frame time of {:?} is suspicious", frame_time)
+            }
+        }
+    }
+}
+
+/// A single problem found when validating a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    kind: DiagnosticKind,
+    joint: Option<usize>,
+}
+
+impl Diagnostic {
+    /// Returns the kind of the problem.
+    #[inline]
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+
+    /// Returns the `Severity` of the problem.
+    #[inline]
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+
+    /// Returns the index of the joint which the problem was found in, or
+    /// `None` if the problem does not belong to a joint.
+    #[inline]
+    pub fn joint(&self) -> Option<usize> {
+        self.joint
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.joint {
+            Some(joint) => write!(f, "{}: joint {}: {}", self.severity(), joint, self.kind),
+            None => write!(f, "{}: {}", self.severity(), self.kind),
+        }
+    }
+}
+
+/// Specify options for validating a `Bvh`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidateOptions {
+    /// Offsets shorter than this length are reported as zero length bones,
+    /// or as zero length end sites.
+    pub min_bone_length: f32,
+    /// Frame times shorter than this are reported as suspicious.
+    pub min_frame_time: Duration,
+    /// Frame times longer than this are reported as suspicious.
+    pub max_frame_time: Duration,
+    #[doc(hidden)]
+    _nonexhaustive: (),
+}
+
+impl Default for ValidateOptions {
+    #[inline]
+    fn default() -> Self {
+        ValidateOptions {
+            min_bone_length: 1.0e-6,
+            min_frame_time: Duration::from_millis(1),
+            max_frame_time: Duration::from_secs(1),
+            _nonexhaustive: (),
+        }
+    }
+}
+
+impl ValidateOptions {
+    /// Create a new `ValidateOptions` with default values.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `min_bone_length` on `self` to the new `min_bone_length`.
+    #[inline]
+    pub fn with_min_bone_length(self, min_bone_length: f32) -> Self {
+        ValidateOptions {
+            min_bone_length,
+            ..self
+        }
+    }
+
+    /// Sets `min_frame_time` on `self` to the new `min_frame_time`.
+    #[inline]
+    pub fn with_min_frame_time(self, min_frame_time: Duration) -> Self {
+        ValidateOptions {
+            min_frame_time,
+            ..self
+        }
+    }
+
+    /// Sets `max_frame_time` on `self` to the new `max_frame_time`.
+    #[inline]
+    pub fn with_max_frame_time(self, max_frame_time: Duration) -> Self {
+        ValidateOptions {
+            max_frame_time,
+            ..self
+        }
+    }
+
+    /// Checks `bvh` for problems, returning a `Diagnostic` for each one.
+    ///
+    /// The diagnostics of each joint are reported in the order in which the
+    /// joints appear in the file, followed by the diagnostics which do not
+    /// belong to a joint.
+    pub fn validate(&self, bvh: &Bvh) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut push = |joint: Option<usize>, kind: DiagnosticKind| {
+            diagnostics.push(Diagnostic { kind, joint });
+        };
+
+        let reference_order = bvh
+            .joints
+            .iter()
+            .map(|joint| rotation_order(joint.channels().iter().map(|c| c.channel_type())))
+            .find(|order| !order.is_empty())
+            .unwrap_or_default();
+
+        for (index, joint) in bvh.joints.iter().enumerate() {
+            let here = Some(index);
+
+            if joint.name().is_empty() {
+                push(here, DiagnosticKind::EmptyJointName);
+            } else if let Some(first_index) = bvh.joints[..index]
+                .iter()
+                .position(|other| other.name() == joint.name())
+            {
+                push(here, DiagnosticKind::DuplicateJointName { first_index });
+            }
+
+            let mut seen: SmallVec<[ChannelType; 6]> = SmallVec::new();
+            for channel in joint.channels() {
+                let channel_type = channel.channel_type();
+                if seen.contains(&channel_type) {
+                    push(here, DiagnosticKind::RepeatedChannel { channel_type });
+                } else {
+                    seen.push(channel_type);
+                }
+
+                if joint.is_child() && channel_type.is_position() {
+                    push(
+                        here,
+                        DiagnosticKind::PositionChannelOnChild { channel_type },
+                    );
+                }
+            }
+
+            for channel in joint.channels() {
+                let motion_index = channel.motion_index();
+                let mut non_finite = (0..bvh.num_frames).filter(|frame| {
+                    !bvh.motion_values[frame * bvh.num_channels + motion_index].is_finite()
+                });
+                if let Some(first_frame) = non_finite.next() {
+                    let num_frames = 1 + non_finite.count();
+                    push(
+                        here,
+                        DiagnosticKind::NonFiniteMotion {
+                            motion_index,
+                            first_frame,
+                            num_frames,
+                        },
+                    );
+                }
+            }
+
+            if joint.is_child() && Vec3::from(joint.offset()).length() < self.min_bone_length {
+                push(here, DiagnosticKind::ZeroLengthBone);
+            }
+
+            let found = rotation_order(seen.iter().cloned());
+            if !found.is_empty() && !is_consistent_order(&reference_order, &found) {
+                push(
+                    here,
+                    DiagnosticKind::InconsistentRotationOrder {
+                        expected: reference_order.clone(),
+                        found,
+                    },
+                );
+            }
+
+            let has_children = bvh.joints[index + 1..]
+                .iter()
+                .any(|other| other.parent_index() == here);
+            if joint.is_child() && !has_children && !joint.has_end_site() {
+                push(here, DiagnosticKind::MissingEndSite);
+            }
+
+            if let Some(end_site) = joint.end_site() {
+                if Vec3::from(end_site).length() < self.min_bone_length {
+                    push(here, DiagnosticKind::ZeroLengthEndSite);
+                }
+            }
+        }
+
+        if bvh.num_frames > 1
+            && (bvh.frame_time < self.min_frame_time || bvh.frame_time > self.max_frame_time)
+        {
+            push(
+                None,
+                DiagnosticKind::SuspiciousFrameTime {
+                    frame_time: bvh.frame_time,
+                },
+            );
+        }
+
+        diagnostics
+    }
+}
+
+impl Bvh {
+    /// Checks `self` for problems with the default `ValidateOptions`,
+    /// returning a `Diagnostic` for each one.
+    ///
+    /// See the [`validate`][`validate`] module for more information.
+    ///
+    /// [`validate`]: validate/index.html
+    #[inline]
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        ValidateOptions::new().validate(self)
+    }
+}
+
+/// Returns the axes of the rotation channels in `channel_types`, in order.
+fn rotation_order<I>(channel_types: I) -> SmallVec<[Axis; 3]>
+where
+    I: IntoIterator<Item = ChannelType>,
+{
+    channel_types
+        .into_iter()
+        .filter(|ty| ty.is_rotation())
+        .map(|ty| ty.axis())
+        .collect()
+}
+
+/// Returns `true` if the axes of `found` appear in the same relative order as
+/// they do in `reference`. Joints with fewer rotation channels than the
+/// reference are allowed, as long as they do not reorder the axes they have.
+fn is_consistent_order(reference: &[Axis], found: &[Axis]) -> bool {
+    let filtered = reference
+        .iter()
+        .filter(|axis| found.contains(axis))
+        .cloned()
+        .collect::<SmallVec<[Axis; 3]>>();
+    filtered.as_slice() == found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiagnosticKind;
+    use crate::JointData;
+
+    #[test]
+    fn missing_end_site() {
+        let mut bvh = bvh! {
+            HIERARCHY
+            ROOT Base
+            {
+                OFFSET 0.0 0.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Tip
+                {
+                    OFFSET 0.0 1.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    End Site
+                    {
+                        OFFSET 0.0 1.0 0.0
+                    }
+                }
+            }
+            MOTION
+            Frames: 1
+            Frame Time: 0.033333333
+            0.0 0.0 0.0 0.0 0.0 0.0
+        };
+
+        assert!(bvh.validate().is_empty());
+
+        if let JointData::Child {
+            ref mut end_site_offset,
+            ..
+        } = bvh.joints[1]
+        {
+            *end_site_offset = None;
+        }
+
+        let diagnostics = bvh.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].joint(), Some(1));
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::MissingEndSite);
+
+        bvh.joints.truncate(1);
+        assert!(bvh.validate().is_empty());
+    }
+
+    #[test]
+    fn zero_length_end_site() {
+        let mut bvh = bvh! {
+            HIERARCHY
+            ROOT Base
+            {
+                OFFSET 0.0 0.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Tip
+                {
+                    OFFSET 0.0 1.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    End Site
+                    {
+                        OFFSET 0.0 0.0 0.0
+                    }
+                }
+            }
+            MOTION
+            Frames: 1
+            Frame Time: 0.033333333
+            0.0 0.0 0.0 0.0 0.0 0.0
+        };
+
+        let diagnostics = bvh.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].joint(), Some(1));
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::ZeroLengthEndSite);
+
+        if let JointData::Child {
+            ref mut end_site_offset,
+            ..
+        } = bvh.joints[1]
+        {
+            *end_site_offset = Some([0.0, 0.0, 1.0].into());
+        }
+
+        assert!(bvh.validate().is_empty());
+    }
+}
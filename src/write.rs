@@ -3,7 +3,7 @@
 //! Contains options for `bvh` file formatting.
 
 use bstr::{BStr, BString, B};
-use crate::{duation_to_fractional_seconds, Bvh, Frame, Frames, Joint, Joints};
+use crate::{duration_to_fractional_seconds, Bvh, Frame, Frames, Joint, Joints};
 use mint::Vector3;
 use smallvec::SmallVec;
 use std::{
@@ -325,7 +325,7 @@ impl WriteOptions {
                     *chunk = format!(
                         "Frame Time: {:.*}",
                         self.frame_time_significant_figures,
-                        duation_to_fractional_seconds(bvh.frame_time())
+                        duration_to_fractional_seconds(bvh.frame_time())
                     )
                     .into_bytes();
                     chunk.extend_from_slice(terminator);
@@ -0,0 +1,123 @@
+mod common;
+
+use bvh_anim::{
+    additive::{AdditiveOptions, ReferencePose},
+    bvh,
+    errors::BlendError,
+    Bvh,
+};
+use common::ARM_SKELETON;
+
+fn clip(frames: &[[f32; 9]]) -> Bvh {
+    common::clip(ARM_SKELETON, 0.1, frames)
+}
+
+fn assert_frames_eq(a: &Bvh, b: &Bvh) {
+    assert_eq!(a.num_frames(), b.num_frames());
+    for (a, b) in a.frames().zip(b.frames()) {
+        for (a, b) in a.as_slice().iter().zip(b.as_slice()) {
+            assert!((a - b).abs() < 1.0e-3, "{} != {}", a, b);
+        }
+    }
+}
+
+const REFERENCE: [f32; 9] = [0.0, 90.0, 0.0, 10.0, 20.0, 30.0, 15.0, -25.0, 40.0];
+
+#[test]
+fn additive_round_trips_onto_reference() {
+    let source = clip(&[
+        REFERENCE,
+        [1.0, 91.0, 0.0, 12.0, 25.0, 28.0, 30.0, -10.0, 45.0],
+        [2.0, 89.0, 0.5, 5.0, 15.0, 35.0, 0.0, -40.0, 60.0],
+    ]);
+
+    let options = AdditiveOptions::new();
+    let additive = options.make_additive(&source).unwrap();
+    assert_eq!(additive.num_frames(), 3);
+    for value in additive.frames().next().unwrap().as_slice() {
+        assert!(value.abs() < 1.0e-3);
+    }
+
+    // Layering the additive clip onto its reference pose gives back the
+    // source clip.
+    let mut base = clip(&[REFERENCE, REFERENCE, REFERENCE]);
+    options.apply(&mut base, &additive).unwrap();
+    assert_frames_eq(&base, &source);
+
+    // The reference pose can also be given as motion values.
+    let from_values = AdditiveOptions::new()
+        .with_reference(ReferencePose::Values(REFERENCE.to_vec()))
+        .make_additive(&source)
+        .unwrap();
+    assert_frames_eq(&from_values, &additive);
+}
+
+#[test]
+fn additive_is_weighted_masked_and_repeated() {
+    let additive = clip(&[[0.0; 9], [4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 40.0, 0.0, 0.0]]);
+    let base_frames = [[0.0, 90.0, 0.0, 0.0, 0.0, 0.0, 10.0, 0.0, 0.0]; 4];
+
+    let mut base = clip(&base_frames);
+    AdditiveOptions::new()
+        .with_weight(0.5)
+        .apply(&mut base, &additive)
+        .unwrap();
+    let root = base.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+    let arm = base.frames().map(|f| f.as_slice()[6]).collect::<Vec<_>>();
+    assert_eq!(root, vec![0.0, 2.0, 0.0, 2.0]);
+    for (a, e) in arm.iter().zip(&[10.0, 30.0, 10.0, 30.0]) {
+        assert!((a - e).abs() < 1.0e-3, "{} != {}", a, e);
+    }
+
+    let mut base = clip(&base_frames);
+    AdditiveOptions::new()
+        .with_joints(vec!["Arm"])
+        .apply(&mut base, &additive)
+        .unwrap();
+    assert!(base.frames().all(|f| f.as_slice()[0] == 0.0));
+    assert!((base.frames().nth(1).unwrap().as_slice()[6] - 50.0).abs() < 1.0e-3);
+}
+
+#[test]
+fn additive_errors() {
+    let source = clip(&[REFERENCE]);
+
+    let err = AdditiveOptions::new()
+        .with_reference(ReferencePose::Frame(3))
+        .make_additive(&source)
+        .unwrap_err();
+    assert_eq!(err, BlendError::FrameOutOfBounds { frame: 3 });
+
+    let err = AdditiveOptions::new()
+        .with_reference(ReferencePose::Values(vec![0.0; 6]))
+        .make_additive(&source)
+        .unwrap_err();
+    assert_eq!(err, BlendError::InvalidReference);
+
+    let mut base = source.clone();
+    let err = AdditiveOptions::new()
+        .with_joints(vec!["Leg"])
+        .apply(&mut base, &source)
+        .unwrap_err();
+    assert_eq!(err, BlendError::MissingJoint { name: "Leg".into() });
+    assert_eq!(base, source);
+
+    let other = bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            End Site
+            {
+                OFFSET 0.0 1.0 0.0
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.1
+        0.0 0.0 0.0
+    };
+    let err = AdditiveOptions::new().apply(&mut base, &other).unwrap_err();
+    assert_eq!(err, BlendError::HierarchyMismatch);
+}
@@ -0,0 +1,163 @@
+use bvh_anim::{
+    binary::{BinaryBvh, BinaryOptions},
+    bvh,
+    errors::LoadBinaryError,
+    Bvh,
+};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn binary_round_trip() {
+    const BVH_BYTES: &[u8] = include_bytes!("../data/test_mocapbank.bvh");
+
+    let bvh = bvh_anim::from_bytes(BVH_BYTES).unwrap();
+    let binary = bvh.to_binary().unwrap();
+
+    assert_eq!(Bvh::from_binary(&binary).unwrap(), bvh);
+    assert_eq!(
+        Bvh::from_binary(&binary).unwrap().to_bstring(),
+        bvh.to_bstring()
+    );
+}
+
+#[test]
+fn binary_view() {
+    const BVH_BYTES: &[u8] = include_bytes!("../data/test_simple.bvh");
+
+    let bvh = bvh_anim::from_bytes(BVH_BYTES).unwrap();
+    let mut binary = vec![];
+    BinaryOptions::new().write(&bvh, &mut binary).unwrap();
+
+    let view = BinaryBvh::from_bytes(&binary).unwrap();
+    assert!(!view.is_compressed());
+    assert_eq!(view.num_joints(), bvh.joints().count());
+    assert_eq!(view.num_channels(), bvh.num_channels());
+    assert_eq!(view.num_frames(), bvh.num_frames());
+    assert_eq!(view.frame_time(), *bvh.frame_time());
+
+    for (frame_index, frame) in bvh.frames().enumerate() {
+        let view_frame = view.frame(frame_index).unwrap();
+        assert_eq!(view_frame.iter().collect::<Vec<_>>(), frame.as_slice());
+    }
+
+    assert!(view.frame(bvh.num_frames()).is_none());
+    assert!(view.motion_value(0, bvh.num_channels()).is_none());
+}
+
+#[test]
+fn binary_errors() {
+    const BVH_BYTES: &[u8] = include_bytes!("../data/test_simple.bvh");
+
+    let binary = bvh_anim::from_bytes(BVH_BYTES)
+        .unwrap()
+        .to_binary()
+        .unwrap();
+
+    match BinaryBvh::from_bytes(&binary[..binary.len() - 1]) {
+        Err(LoadBinaryError::UnexpectedEof { .. }) => {}
+        other => panic!("expected an unexpected eof error, got {:?}", other),
+    }
+
+    match BinaryBvh::from_bytes(b"BVH\n") {
+        Err(LoadBinaryError::InvalidMagic) => {}
+        other => panic!("expected an invalid magic error, got {:?}", other),
+    }
+
+    let mut future_version = binary.clone();
+    future_version[4] = 0xff;
+    match BinaryBvh::from_bytes(&future_version) {
+        Err(LoadBinaryError::UnsupportedVersion { .. }) => {}
+        other => panic!("expected an unsupported version error, got {:?}", other),
+    }
+}
+
+#[test]
+fn binary_invalid_joints() {
+    let bvh = bvh! {
+        HIERARCHY
+        ROOT Root
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Xposition Yposition Zposition
+            JOINT A
+            {
+                OFFSET 1.0 0.0 0.0
+                CHANNELS 0
+                End Site
+                {
+                    OFFSET 1.0 0.0 0.0
+                }
+            }
+            JOINT B
+            {
+                OFFSET 0.0 1.0 0.0
+                CHANNELS 0
+                JOINT C
+                {
+                    OFFSET 0.0 1.0 0.0
+                    CHANNELS 0
+                    End Site
+                    {
+                        OFFSET 0.0 1.0 0.0
+                    }
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333
+        0.0 0.0 0.0
+    };
+    let binary = bvh.to_binary().unwrap();
+
+    // The joint table starts after the 32 byte header. Each joint has a
+    // parent, a name length, the name, an offset, an end site flag and maybe
+    // an end site.
+    let root = 32;
+    let a = root + 4 + 4 + 4 + 12 + 1;
+    let b = a + 4 + 4 + 1 + 12 + 1 + 12;
+    let c = b + 4 + 4 + 1 + 12 + 1;
+
+    // `C` is moved under `A`, but `B` comes between them.
+    let mut not_depth_first = binary.clone();
+    not_depth_first[c..c + 4].copy_from_slice(&1u32.to_le_bytes());
+    match BinaryBvh::from_bytes(&not_depth_first) {
+        Err(LoadBinaryError::InvalidJoint { index: 3 }) => {}
+        other => panic!("expected an invalid joint error, got {:?}", other),
+    }
+
+    let mut root_end_site = binary.clone();
+    root_end_site[a - 1] = 1;
+    match BinaryBvh::from_bytes(&root_end_site) {
+        Err(LoadBinaryError::InvalidJoint { index: 0 }) => {}
+        other => panic!("expected an invalid joint error, got {:?}", other),
+    }
+
+    assert_eq!(Bvh::from_binary(&binary).unwrap(), bvh);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn binary_compressed_round_trip() {
+    const BVH_BYTES: &[u8] = include_bytes!("../data/test_mocapbank.bvh");
+
+    let bvh = bvh_anim::from_bytes(BVH_BYTES).unwrap();
+    let binary = BinaryOptions::new()
+        .with_compression(true)
+        .write_to_vec(&bvh)
+        .unwrap();
+
+    let view = BinaryBvh::from_bytes(&binary).unwrap();
+    assert!(view.is_compressed());
+    assert_eq!(view.to_bvh().unwrap(), bvh);
+    assert!(binary.len() < bvh.to_binary().unwrap().len());
+
+    // A motion block which decompresses to more bytes than the header allows
+    // is rejected.
+    let mut fewer_frames = binary.clone();
+    fewer_frames[16..20].copy_from_slice(&1u32.to_le_bytes());
+    match BinaryBvh::from_bytes(&fewer_frames) {
+        Err(LoadBinaryError::MotionSizeMismatch { .. }) => {}
+        other => panic!("expected a motion size mismatch error, got {:?}", other),
+    }
+}
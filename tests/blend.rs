@@ -0,0 +1,89 @@
+mod common;
+
+use bvh_anim::{blend::CrossfadeOptions, errors::BlendError, Bvh};
+use common::{channel, ARM_SKELETON};
+use std::time::Duration;
+
+/// A clip of `frames` frames at 10Hz, in which the root moves along `x` by
+/// `step` per frame and the arm is held at `arm` degrees around `z`.
+fn clip(frames: usize, start: f32, step: f32, arm: f32) -> Bvh {
+    let frames = (0..frames).map(|i| {
+        let x = start + i as f32 * step;
+        [x, 90.0, 0.0, 0.0, 0.0, 0.0, arm, 0.0, 0.0]
+    });
+    common::clip(ARM_SKELETON, 0.1, frames)
+}
+
+#[test]
+fn crossfade_blends_rotations() {
+    let first = clip(10, 0.0, 1.0, 0.0);
+    let second = clip(10, 100.0, 2.0, 80.0);
+
+    let blended = CrossfadeOptions::new()
+        .with_frames(5, 2)
+        .with_duration(Duration::from_millis(300))
+        .crossfade(&first, &second)
+        .unwrap();
+
+    // 5 frames of the first clip, 3 blended frames, and the remaining 5
+    // frames of the second clip.
+    assert_eq!(blended.num_frames(), 13);
+    assert_eq!(blended.frame_time(), first.frame_time());
+
+    let arm = channel(&blended, 6);
+    let expected = [
+        0.0, 0.0, 0.0, 0.0, 0.0, 20.0, 40.0, 60.0, 80.0, 80.0, 80.0, 80.0, 80.0,
+    ];
+    for (a, e) in arm.iter().zip(&expected) {
+        assert!((a - e).abs() < 1.0e-3, "{:?}", arm);
+    }
+
+    // The second clip continues from where the first clip was at the
+    // transition, and the height is not aligned.
+    let root = channel(&blended, 0);
+    assert_eq!(root[4], 4.0);
+    assert_eq!(&root[8..], &[11.0, 13.0, 15.0, 17.0, 19.0]);
+    assert_eq!(channel(&blended, 1), vec![90.0; 13]);
+}
+
+#[test]
+fn crossfade_without_alignment() {
+    let first = clip(4, 0.0, 1.0, 0.0);
+    let second = clip(4, 100.0, 1.0, 0.0);
+
+    let cut = CrossfadeOptions::new()
+        .with_frames(2, 2)
+        .with_align_root(false)
+        .crossfade(&first, &second)
+        .unwrap();
+    assert_eq!(channel(&cut, 0), vec![0.0, 1.0, 102.0, 103.0]);
+}
+
+#[test]
+fn crossfade_errors() {
+    let first = clip(4, 0.0, 1.0, 0.0);
+
+    let mut slower = clip(4, 0.0, 1.0, 0.0);
+    slower.set_frame_time(Duration::from_millis(200));
+    assert_eq!(
+        CrossfadeOptions::new().crossfade(&first, &slower),
+        Err(BlendError::FrameTimeMismatch)
+    );
+
+    let skeleton = ARM_SKELETON.replace(
+        "CHANNELS 3 Zrotation Xrotation Yrotation",
+        "CHANNELS 3 Xrotation Yrotation Zrotation",
+    );
+    let reordered = common::clip(&skeleton, 0.1, &[[0.0; 9]]);
+    assert_eq!(
+        CrossfadeOptions::new().crossfade(&first, &reordered),
+        Err(BlendError::HierarchyMismatch)
+    );
+
+    assert_eq!(
+        CrossfadeOptions::new()
+            .with_frames(0, 4)
+            .crossfade(&first, &first),
+        Err(BlendError::FrameOutOfBounds { frame: 4 })
+    );
+}
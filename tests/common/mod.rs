@@ -0,0 +1,92 @@
+//! Fixtures which are shared by the integration tests.
+
+// Each test only uses some of the fixtures.
+#![allow(dead_code)]
+
+use bvh_anim::Bvh;
+
+/// The hierarchy of a root which can move, with a single arm above it. Each
+/// frame has the 6 channels of the root, followed by the 3 channels of the
+/// arm.
+pub const ARM_SKELETON: &str = "\
+HIERARCHY
+ROOT Hips
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Arm
+    {
+        OFFSET 0.0 10.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 5.0 0.0
+        }
+    }
+}
+";
+
+/// Builds a clip of the hierarchy `skeleton`, with one frame for each of
+/// `frames`, and `frame_time` seconds between frames.
+pub fn clip<I>(skeleton: &str, frame_time: f64, frames: I) -> Bvh
+where
+    I: IntoIterator,
+    I::Item: AsRef<[f32]>,
+{
+    let frames = frames.into_iter().collect::<Vec<_>>();
+    let mut text = format!(
+        "{}MOTION\nFrames: {}\nFrame Time: {}\n",
+        skeleton,
+        frames.len(),
+        frame_time
+    );
+    for frame in &frames {
+        let values = frame
+            .as_ref()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        text.push_str(&values.join(" "));
+        text.push('\n');
+    }
+    Bvh::from_bytes(text).unwrap()
+}
+
+/// Returns the values of the channel at `index` in each frame of `bvh`.
+pub fn channel(bvh: &Bvh, index: usize) -> Vec<f32> {
+    bvh.frames().map(|f| f.as_slice()[index]).collect()
+}
+
+/// Returns the names of the joints of `bvh`, in order.
+pub fn names(bvh: &Bvh) -> Vec<String> {
+    bvh.joints().map(|j| j.data().name().to_string()).collect()
+}
+
+/// Asserts that every joint of `after` has the same world transform in each
+/// frame as the joint of the same name in `before`. New joints are skipped.
+pub fn assert_same_pose(before: &Bvh, after: &Bvh) {
+    assert_eq!(before.num_frames(), after.num_frames());
+    let before_names = names(before);
+    for frame in 0..before.num_frames() {
+        let expected = before.world_transforms(frame).unwrap();
+        let actual = after.world_transforms(frame).unwrap();
+        for (name, actual) in names(after).iter().zip(actual) {
+            let index = match before_names.iter().position(|n| n == name) {
+                Some(index) => index,
+                None => continue,
+            };
+            let actual: [[f32; 4]; 4] = actual.into();
+            let expected: [[f32; 4]; 4] = expected[index].into();
+            for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+                assert!(
+                    (a - e).abs() < 1.0e-3,
+                    "{} in frame {}: {:?} != {:?}",
+                    name,
+                    frame,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use bvh_anim::{contact::ContactOptions, errors::ContactError, Bvh};
+
+const SKELETON: &str = "\
+HIERARCHY
+ROOT Hips
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT UpLeg
+    {
+        OFFSET 1.0 0.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Leg
+        {
+            OFFSET 0.0 -10.0 2.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            JOINT Foot
+            {
+                OFFSET 0.0 -10.0 -2.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 0.0 2.0
+                }
+            }
+        }
+    }
+}
+";
+
+/// A leg whose hips drift forwards while the foot is on the ground, so that
+/// the foot slides, before the leg is lifted off the ground.
+fn sliding_leg() -> Bvh {
+    let frames = (0..12).map(|i| {
+        let height = if i < 8 { 20.0 } else { 30.0 };
+        let mut frame = [0.0; 15];
+        frame[0] = i as f32 * 0.1;
+        frame[1] = height;
+        frame
+    });
+    common::clip(SKELETON, 0.033333333, frames)
+}
+
+fn foot_positions(bvh: &Bvh) -> Vec<mint::Vector3<f32>> {
+    (0..bvh.num_frames())
+        .map(|frame| bvh.world_positions(frame).unwrap()[3])
+        .collect()
+}
+
+#[test]
+fn detect_sliding_foot() {
+    let bvh = sliding_leg();
+    let contacts = ContactOptions::new()
+        .with_feet(vec!["Foot"])
+        .detect(&bvh)
+        .unwrap();
+
+    assert_eq!(contacts.len(), 1);
+    assert_eq!(contacts[0].joint(), 3);
+    assert_eq!(
+        contacts[0].contacts(),
+        &[true, true, true, true, true, true, true, false, false, false, false, false]
+    );
+    assert!(!contacts[0].is_planted(12));
+
+    let strict = ContactOptions::new()
+        .with_feet(vec!["Foot"])
+        .with_velocity_threshold(1.0)
+        .detect(&bvh)
+        .unwrap();
+    assert!(strict[0].contacts().iter().all(|&c| !c));
+}
+
+#[test]
+fn cleanup_pins_planted_foot() {
+    let original = sliding_leg();
+    let mut bvh = original.clone();
+    ContactOptions::new()
+        .with_feet(vec!["Foot"])
+        .with_blend_frames(2)
+        .cleanup(&mut bvh)
+        .unwrap();
+
+    let positions = foot_positions(&bvh);
+    for position in &positions[..7] {
+        assert!(
+            (position.x - positions[0].x).abs() < 1.0e-3,
+            "{:?}",
+            position
+        );
+        assert!(
+            (position.y - positions[0].y).abs() < 1.0e-3,
+            "{:?}",
+            position
+        );
+        assert!(
+            (position.z - positions[0].z).abs() < 1.0e-3,
+            "{:?}",
+            position
+        );
+    }
+
+    // The foot keeps its world rotation, so the end site stays level.
+    for frame in 0..7 {
+        let transforms = bvh.world_transforms(frame).unwrap();
+        assert!((transforms[3].y.y - 1.0).abs() < 1.0e-3);
+    }
+
+    // The root and the frames after the blend are untouched.
+    let frames = bvh.frames().zip(original.frames());
+    for (frame, (cleaned, original)) in frames.enumerate() {
+        assert_eq!(&cleaned.as_slice()[..6], &original.as_slice()[..6]);
+        if frame >= 9 {
+            assert_eq!(cleaned.as_slice(), original.as_slice());
+        }
+    }
+}
+
+#[test]
+fn contact_errors() {
+    let mut bvh = sliding_leg();
+
+    let missing = ContactOptions::new()
+        .with_feet(vec!["Foot", "Hand"])
+        .detect(&bvh);
+    assert_eq!(
+        missing,
+        Err(ContactError::MissingJoint {
+            name: "Hand".into()
+        })
+    );
+
+    let original = bvh.clone();
+    let invalid = ContactOptions::new()
+        .with_feet(vec!["UpLeg"])
+        .cleanup(&mut bvh);
+    assert_eq!(
+        invalid,
+        Err(ContactError::InvalidLeg {
+            name: "UpLeg".into()
+        })
+    );
+    assert_eq!(bvh, original);
+}
@@ -0,0 +1,154 @@
+use bstr::ByteSlice;
+use bvh_anim::{bvh, csv::CsvOptions, errors::LoadCsvError, write::LineTerminator};
+use pretty_assertions::assert_eq;
+use std::time::Duration;
+
+#[test]
+fn csv_round_trip() {
+    let bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT End
+            {
+                OFFSET 0.0 0.0 15.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 0.0 30.0
+                }
+            }
+        }
+        MOTION
+        Frames: 2
+        Frame Time: 0.5
+        0.0 1.0 2.0 3.0 4.0 5.0 6.0 7.0 8.0
+        1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0 1.0
+    };
+
+    let options = CsvOptions::new()
+        .with_line_terminator(LineTerminator::Unix)
+        .with_time_significant_figures(1)
+        .with_motion_values_significant_figures(1);
+    let csv = options.write_to_string(&bvh);
+
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap().as_bstr(),
+        "time,Base.Xposition,Base.Yposition,Base.Zposition,Base.Zrotation,Base.Xrotation,\
+         Base.Yrotation,End.Zrotation,End.Xrotation,End.Yrotation"
+    );
+    assert_eq!(
+        lines.next().unwrap().as_bstr(),
+        "0.0,0.0,1.0,2.0,3.0,4.0,5.0,6.0,7.0,8.0"
+    );
+    assert_eq!(
+        lines.next().unwrap().as_bstr(),
+        "0.5,1.0,1.0,1.0,1.0,1.0,1.0,1.0,1.0,1.0"
+    );
+
+    let mut loaded = bvh.clone();
+    for frame in loaded.frames_mut() {
+        for value in frame.as_mut_slice() {
+            *value = 0.0;
+        }
+    }
+
+    options.read_motion(&mut loaded, csv.as_bytes()).unwrap();
+    assert_eq!(loaded, bvh);
+
+    // Whole seconds are kept in the time column, and in the frame time which
+    // is read back from it.
+    let mut slow = bvh.clone();
+    slow.set_frame_time(Duration::from_millis(1500));
+    let csv = options.write_to_string(&slow);
+    assert!(csv.lines().nth(2).unwrap().starts_with(b"1.5,"));
+
+    options.read_motion(&mut loaded, csv.as_bytes()).unwrap();
+    assert_eq!(loaded.frame_time(), &Duration::from_millis(1500));
+}
+
+#[test]
+fn csv_world_positions() {
+    let bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Xposition Yposition Zposition
+            JOINT Tip
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 1 Zrotation
+                End Site
+                {
+                    OFFSET 0.0 5.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333
+        1.0 2.0 3.0 90.0
+    };
+
+    let options = CsvOptions::new()
+        .with_world_positions(true)
+        .with_line_terminator(LineTerminator::Unix)
+        .with_motion_values_significant_figures(1);
+    let csv = options.write_to_string(&bvh);
+
+    let mut lines = csv.lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .ends_with(b"Base.WorldX,Base.WorldY,Base.WorldZ,Tip.WorldX,Tip.WorldY,Tip.WorldZ"));
+    assert!(lines
+        .next()
+        .unwrap()
+        .ends_with(b"1.0,2.0,3.0,90.0,1.0,2.0,3.0,1.0,12.0,3.0"));
+
+    // World position columns are ignored when reading the table back in.
+    let mut loaded = bvh.clone();
+    options.read_motion(&mut loaded, csv.as_bytes()).unwrap();
+    assert_eq!(loaded, bvh);
+}
+
+#[test]
+fn csv_missing_channel() {
+    let mut bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 1 Xposition
+            End Site
+            {
+                OFFSET 0.0 5.0 0.0
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333
+        1.0
+    };
+
+    let result = CsvOptions::new().read_motion(&mut bvh, &b"time,Base.Yposition\n0.0,1.0\n"[..]);
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().line(), Some(0));
+
+    let result = CsvOptions::new().read_motion(
+        &mut bvh,
+        &b"time,Base.Xposition,Base.Xposition\n0.0,1.0,2.0\n"[..],
+    );
+    match result {
+        Err(LoadCsvError::DuplicateColumn { name, line }) => {
+            assert_eq!(name, "Base.Xposition");
+            assert_eq!(line, 0);
+        }
+        other => panic!("expected a duplicate column error, got {:?}", other),
+    }
+    assert_eq!(bvh.frames().next().unwrap().as_slice(), &[1.0]);
+}
@@ -0,0 +1,100 @@
+use bvh_anim::{bvh, derivative::RotationSpace, Bvh};
+
+fn swinging_arm() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Arm
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 10.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 4
+        Frame Time: 0.1
+        0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        1.0 0.0 0.0 0.0 0.0 0.0 10.0 0.0 0.0
+        4.0 0.0 0.0 0.0 0.0 0.0 20.0 0.0 0.0
+        9.0 0.0 0.0 0.0 0.0 0.0 30.0 0.0 0.0
+    }
+}
+
+fn assert_close(actual: mint::Vector3<f32>, expected: [f32; 3]) {
+    let actual = [actual.x, actual.y, actual.z];
+    for (a, e) in actual.iter().zip(expected.iter()) {
+        assert!((a - e).abs() < 1.0e-3, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn linear_velocity_and_acceleration() {
+    let bvh = swinging_arm();
+
+    let velocities = bvh.linear_velocities();
+    assert_eq!(velocities.len(), 4);
+    assert_eq!(velocities[0].len(), 2);
+    assert_close(velocities[0][0], [10.0, 0.0, 0.0]);
+    assert_close(velocities[1][0], [20.0, 0.0, 0.0]);
+    assert_close(velocities[2][0], [40.0, 0.0, 0.0]);
+    assert_close(velocities[3][0], [50.0, 0.0, 0.0]);
+
+    let accelerations = bvh.linear_accelerations();
+    for frame in &accelerations {
+        assert_close(frame[0], [200.0, 0.0, 0.0]);
+    }
+}
+
+#[test]
+fn angular_velocity_spaces() {
+    let bvh = swinging_arm();
+    let speed = 100.0f32.to_radians();
+
+    let local = bvh.angular_velocities(RotationSpace::Local);
+    for frame in &local {
+        assert_close(frame[0], [0.0, 0.0, 0.0]);
+        assert_close(frame[1], [0.0, 0.0, speed]);
+    }
+
+    let global = bvh.angular_velocities(RotationSpace::Global);
+    for frame in &global {
+        assert_close(frame[1], [0.0, 0.0, speed]);
+    }
+}
+
+#[test]
+fn derivatives_of_a_single_frame() {
+    let bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Xposition Yposition Zposition
+            JOINT Tip
+            {
+                OFFSET 0.0 1.0 0.0
+                CHANNELS 0
+                End Site
+                {
+                    OFFSET 0.0 1.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333
+        1.0 2.0 3.0
+    };
+
+    let zero = vec![vec![[0.0, 0.0, 0.0].into(); 2]];
+    assert_eq!(bvh.linear_velocities(), zero);
+    assert_eq!(bvh.linear_accelerations(), zero);
+    assert_eq!(bvh.angular_velocities(RotationSpace::Local), zero);
+}
@@ -0,0 +1,106 @@
+use bvh_anim::{
+    bvh,
+    euler::{UnwrapMode, UnwrapOptions},
+};
+
+fn rotations(bvh: &bvh_anim::Bvh) -> Vec<Vec<f32>> {
+    bvh.frames().map(|f| f.as_slice()[3..6].to_vec()).collect()
+}
+
+#[test]
+fn unwrap_per_channel() {
+    let mut bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Tip
+            {
+                OFFSET 0.0 1.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 1.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 4
+        Frame Time: 0.033333333
+        170.0 0.0 0.0 170.0 -175.0 10.0 0.0 0.0 0.0
+        175.0 0.0 0.0 179.0 175.0 10.0 0.0 0.0 0.0
+        -179.0 0.0 0.0 -178.0 160.0 10.0 0.0 0.0 0.0
+        -170.0 0.0 0.0 -150.0 -170.0 10.0 0.0 0.0 0.0
+    };
+
+    bvh.unwrap_rotations();
+
+    // Position channels are left alone.
+    let positions = bvh.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+    assert_eq!(positions, vec![170.0, 175.0, -179.0, -170.0]);
+
+    assert_eq!(
+        rotations(&bvh),
+        vec![
+            vec![170.0, -175.0, 10.0],
+            vec![179.0, -185.0, 10.0],
+            vec![182.0, -200.0, 10.0],
+            vec![210.0, -170.0, 10.0],
+        ]
+    );
+}
+
+#[test]
+fn unwrap_closest_euler_preserves_pose() {
+    let mut bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Middle
+            {
+                OFFSET 0.0 1.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Tip
+                {
+                    OFFSET 0.0 1.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    End Site
+                    {
+                        OFFSET 0.0 1.0 0.0
+                    }
+                }
+            }
+        }
+        MOTION
+        Frames: 3
+        Frame Time: 0.033333333
+        0.0 0.0 0.0 10.0 85.0 20.0 5.0 0.0 0.0 0.0 0.0 0.0
+        0.0 0.0 0.0 10.0 89.0 20.0 5.0 0.0 0.0 0.0 0.0 0.0
+        0.0 0.0 0.0 -170.0 87.0 -160.0 5.0 0.0 0.0 0.0 0.0 0.0
+    };
+
+    let before = (0..bvh.num_frames())
+        .map(|f| bvh.world_positions(f).unwrap())
+        .collect::<Vec<_>>();
+
+    UnwrapOptions::new()
+        .with_mode(UnwrapMode::ClosestEuler)
+        .unwrap(&mut bvh);
+
+    let last = &rotations(&bvh)[2];
+    assert!((last[0] - 10.0).abs() < 1.0e-3, "{:?}", last);
+    assert!((last[1] - 93.0).abs() < 1.0e-3, "{:?}", last);
+    assert!((last[2] - 20.0).abs() < 1.0e-3, "{:?}", last);
+
+    for (frame, before) in before.iter().enumerate() {
+        let after = bvh.world_positions(frame).unwrap();
+        for (a, b) in after.iter().zip(before.iter()) {
+            assert!((a.x - b.x).abs() < 1.0e-4);
+            assert!((a.y - b.y).abs() < 1.0e-4);
+            assert!((a.z - b.z).abs() < 1.0e-4);
+        }
+    }
+}
@@ -109,3 +109,417 @@ fn ffi_load_from_cfile() {
 
 #[test]
 fn custom_allocators() {}
+
+fn branching_bvh() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Middle1
+            {
+                OFFSET 0.0 0.0 15.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 0.0 30.0
+                }
+            }
+            JOINT Middle2
+            {
+                OFFSET 0.0 15.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 30.0 0.0
+                }
+            }
+        }
+
+        MOTION
+        Frames: 2
+        Frame Time: 0.033333333333
+        0.0 1.0 2.0 3.0 4.0 5.0 6.0 7.0 8.0 9.0 10.0 11.0
+        12.0 13.0 14.0 15.0 16.0 17.0 18.0 19.0 20.0 21.0 22.0 23.0
+    }
+}
+
+#[test]
+fn ffi_write() {
+    use bvh_anim::{
+        ffi::{
+            bvh_IndentStyle, bvh_LineTerminator, bvh_destroy, bvh_write, bvh_write_options_default,
+            bvh_write_with_options,
+        },
+        write::{IndentStyle, LineTerminator, WriteOptions},
+    };
+    use libc;
+
+    unsafe fn write_to_vec(write: impl FnOnce(*mut libc::FILE) -> libc::c_int) -> Vec<u8> {
+        let file = libc::tmpfile();
+        assert!(!file.is_null());
+        assert_ne!(write(file), 0);
+
+        libc::rewind(file);
+        let mut bytes = vec![];
+        let mut buf = [0u8; 256];
+        loop {
+            let n = libc::fread(buf.as_mut_ptr() as *mut _, 1, buf.len(), file);
+            if n == 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buf[..n]);
+        }
+        libc::fclose(file);
+        bytes
+    }
+
+    let bvh = branching_bvh();
+    let mut bvh_ffi = bvh.clone().into_ffi();
+
+    unsafe {
+        let written = write_to_vec(|file| bvh_write(&bvh_ffi, file));
+        assert_eq!(&written[..], &bvh.to_bstring()[..]);
+
+        let mut options = bvh_write_options_default();
+        options.indent_style = bvh_IndentStyle::INDENT_STYLE_SPACES as libc::c_int;
+        options.indent_num_spaces = 2;
+        options.line_terminator = bvh_LineTerminator::LINE_TERMINATOR_WINDOWS as libc::c_int;
+        options.motion_values_significant_figures = 3;
+
+        let written = write_to_vec(|file| bvh_write_with_options(&bvh_ffi, file, &options));
+        let expected = WriteOptions::new()
+            .with_indent(IndentStyle::with_spaces(2))
+            .with_line_terminator(LineTerminator::Windows)
+            .with_motion_values_significant_figures(3)
+            .write_to_string(&bvh);
+        assert_eq!(&written[..], &expected[..]);
+
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
+
+#[test]
+fn ffi_write_invalid_channel() {
+    use bvh_anim::ffi::{
+        bvh_Error, bvh_ErrorKind, bvh_clear_last_error, bvh_destroy, bvh_error_destroy,
+        bvh_get_last_error, bvh_write, BVH_ALLOCATOR_DEFAULT,
+    };
+    use std::mem;
+
+    let mut bvh_ffi = branching_bvh().into_ffi();
+
+    unsafe {
+        let channel = &mut *(*bvh_ffi.bvh_joints.add(1)).joint_channels;
+        let original_index = channel.channel_index;
+        channel.channel_index = bvh_ffi.bvh_num_channels;
+
+        bvh_clear_last_error();
+        let file = libc::tmpfile();
+        assert!(!file.is_null());
+        assert_eq!(bvh_write(&bvh_ffi, file), 0);
+        libc::fclose(file);
+
+        let mut error: bvh_Error = mem::zeroed();
+        assert_ne!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        assert_eq!(error.error_kind, bvh_ErrorKind::ERROR_KIND_INVALID_DATA);
+        assert_ne!(bvh_error_destroy(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+
+        channel.channel_index = original_index;
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
+
+#[test]
+fn ffi_write_invalid_options() {
+    use bvh_anim::ffi::{
+        bvh_Error, bvh_ErrorKind, bvh_clear_last_error, bvh_destroy, bvh_error_destroy,
+        bvh_get_last_error, bvh_write_options_default, bvh_write_with_options,
+        BVH_ALLOCATOR_DEFAULT,
+    };
+    use std::mem;
+
+    let mut bvh_ffi = branching_bvh().into_ffi();
+
+    unsafe {
+        let file = libc::tmpfile();
+        assert!(!file.is_null());
+
+        for &(indent_style, line_terminator) in &[(3, 0), (0, -1)] {
+            let mut options = bvh_write_options_default();
+            options.indent_style = indent_style;
+            options.line_terminator = line_terminator;
+
+            bvh_clear_last_error();
+            assert_eq!(bvh_write_with_options(&bvh_ffi, file, &options), 0);
+
+            let mut error: bvh_Error = mem::zeroed();
+            assert_ne!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+            assert_eq!(error.error_kind, bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT);
+            assert_ne!(bvh_error_destroy(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        }
+
+        libc::fclose(file);
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
+
+#[test]
+fn ffi_find_joint_and_children() {
+    use bvh_anim::ffi::{bvh_destroy, bvh_find_joint, bvh_get_child, bvh_num_children};
+    use std::ffi::CStr;
+
+    let mut bvh_ffi = branching_bvh().into_ffi();
+    let name = |bytes: &'static [u8]| CStr::from_bytes_with_nul(bytes).unwrap().as_ptr();
+
+    unsafe {
+        assert_eq!(bvh_find_joint(&bvh_ffi, name(b"Base\0")), 0);
+        assert_eq!(bvh_find_joint(&bvh_ffi, name(b"Middle2\0")), 2);
+        assert_eq!(bvh_find_joint(&bvh_ffi, name(b"Missing\0")), usize::MAX);
+
+        assert_eq!(bvh_num_children(&bvh_ffi, 0), 2);
+        assert_eq!(bvh_get_child(&bvh_ffi, 0, 0), 1);
+        assert_eq!(bvh_get_child(&bvh_ffi, 0, 1), 2);
+        assert_eq!(bvh_get_child(&bvh_ffi, 0, 2), usize::MAX);
+
+        assert_eq!(bvh_num_children(&bvh_ffi, 1), 0);
+        assert_eq!(bvh_num_children(&bvh_ffi, 10), 0);
+        assert_eq!(bvh_get_child(&bvh_ffi, 10, 0), usize::MAX);
+
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
+
+#[test]
+fn ffi_get_set_motion() {
+    use bvh_anim::ffi::{bvh_destroy, bvh_get_motion, bvh_set_motion};
+
+    let mut bvh_ffi = branching_bvh().into_ffi();
+
+    unsafe {
+        let mut value = 0.0;
+        assert_ne!(bvh_get_motion(&bvh_ffi, 1, 3, &mut value), 0);
+        assert_eq!(value, 15.0);
+
+        assert_ne!(bvh_set_motion(&mut bvh_ffi, 1, 3, -1.5), 0);
+        assert_ne!(bvh_get_motion(&bvh_ffi, 1, 3, &mut value), 0);
+        assert_eq!(value, -1.5);
+
+        assert_eq!(bvh_get_motion(&bvh_ffi, 2, 0, &mut value), 0);
+        assert_eq!(bvh_set_motion(&mut bvh_ffi, 0, 12, 1.0), 0);
+        assert_eq!(value, -1.5);
+
+        let bvh = Bvh::from_ffi(bvh_ffi).unwrap();
+        let channel = bvh.joints().next().unwrap().data().channels()[3];
+        assert_eq!(bvh.get_motion(1, &channel), -1.5);
+
+        bvh_ffi = bvh.into_ffi();
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
+
+#[test]
+fn ffi_last_error() {
+    use bvh_anim::ffi::{
+        bvh_BvhFile, bvh_Error, bvh_ErrorCategory, bvh_ErrorKind, bvh_clear_last_error,
+        bvh_error_destroy, bvh_get_last_error, bvh_parse, BVH_ALLOCATOR_DEFAULT,
+    };
+    use std::{ffi::CStr, mem, ptr};
+
+    const BAD_MOTION: &[u8] = b"HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 3 Xposition Yposition Zposition
+}
+MOTION
+Frames: 1
+Frame Time: 0.033333333
+0.0 x 0.0
+\0";
+
+    unsafe {
+        bvh_clear_last_error();
+        let mut error: bvh_Error = mem::zeroed();
+        assert_eq!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+
+        let mut bvh = bvh_BvhFile::default();
+        let bvh_c_str = CStr::from_bytes_with_nul(BAD_MOTION).unwrap();
+        assert_eq!(bvh_parse(bvh_c_str.as_ptr(), &mut bvh), 0);
+
+        assert_ne!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        assert_eq!(
+            error.error_category,
+            bvh_ErrorCategory::ERROR_CATEGORY_MOTION
+        );
+        assert_eq!(
+            error.error_kind,
+            bvh_ErrorKind::ERROR_KIND_PARSE_MOTION_SECTION
+        );
+        assert_ne!(error.error_has_line, 0);
+        assert_eq!(error.error_line, 9);
+        let message = CStr::from_ptr(error.error_message).to_str().unwrap();
+        assert!(message.contains("9: "), "{}", message);
+        assert_ne!(bvh_error_destroy(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        assert_eq!(error.error_message, ptr::null_mut());
+
+        let missing_root = CStr::from_bytes_with_nul(b"HIERARCHY\nMOTION\n\0").unwrap();
+        assert_eq!(bvh_parse(missing_root.as_ptr(), &mut bvh), 0);
+        assert_ne!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        assert_eq!(
+            error.error_category,
+            bvh_ErrorCategory::ERROR_CATEGORY_JOINTS
+        );
+        assert_eq!(error.error_kind, bvh_ErrorKind::ERROR_KIND_MISSING_ROOT);
+        assert_eq!(error.error_has_line, 0);
+        assert_ne!(bvh_error_destroy(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+
+        assert_eq!(bvh_parse(ptr::null(), &mut bvh), 0);
+        assert_ne!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+        assert_eq!(
+            error.error_category,
+            bvh_ErrorCategory::ERROR_CATEGORY_OTHER
+        );
+        assert_eq!(error.error_kind, bvh_ErrorKind::ERROR_KIND_INVALID_ARGUMENT);
+        assert_ne!(bvh_error_destroy(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+
+        bvh_clear_last_error();
+        assert_eq!(bvh_get_last_error(&mut error, &BVH_ALLOCATOR_DEFAULT), 0);
+    }
+}
+
+#[test]
+fn ffi_last_error_custom_allocator() {
+    use bvh_anim::ffi::{
+        bvh_AllocCallbacks, bvh_BvhFile, bvh_Error, bvh_error_destroy, bvh_get_last_error,
+        bvh_parse,
+    };
+    use libc::{c_void, size_t};
+    use std::{cell::Cell, ffi::CStr, mem};
+
+    thread_local! {
+        static LIVE_BYTES: Cell<usize> = Cell::new(0);
+    }
+
+    unsafe extern "C" fn counting_alloc(size: size_t, _align: size_t) -> *mut c_void {
+        LIVE_BYTES.with(|b| b.set(b.get() + size));
+        libc::calloc(1, size)
+    }
+
+    unsafe extern "C" fn counting_free(ptr: *mut c_void, size: size_t, _align: size_t) {
+        LIVE_BYTES.with(|b| b.set(b.get() - size));
+        libc::free(ptr);
+    }
+
+    let allocator = bvh_AllocCallbacks::new(
+        Some(counting_alloc as unsafe extern "C" fn(size_t, size_t) -> *mut c_void),
+        Some(counting_free as unsafe extern "C" fn(*mut c_void, size_t, size_t)),
+    );
+
+    unsafe {
+        let mut bvh = bvh_BvhFile::default();
+        let bad = CStr::from_bytes_with_nul(b"HIERARCHY\nMOTION\n\0").unwrap();
+        assert_eq!(bvh_parse(bad.as_ptr(), &mut bvh), 0);
+
+        let mut error: bvh_Error = mem::zeroed();
+        assert_ne!(bvh_get_last_error(&mut error, &allocator), 0);
+
+        let message = CStr::from_ptr(error.error_message);
+        assert_eq!(
+            LIVE_BYTES.with(Cell::get),
+            message.to_bytes_with_nul().len()
+        );
+
+        assert_ne!(bvh_error_destroy(&mut error, &allocator), 0);
+        assert_eq!(LIVE_BYTES.with(Cell::get), 0);
+    }
+}
+
+#[test]
+fn ffi_forward_kinematics() {
+    use bvh_anim::ffi::{
+        bvh_Matrix4, bvh_Position, bvh_destroy, bvh_get_world_matrices,
+        bvh_get_world_matrices_at_time, bvh_get_world_positions, bvh_get_world_positions_at_time,
+    };
+    use std::{fs::File, io::BufReader};
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1.0e-3 * (1.0 + a.abs()), "{} != {}", a, b);
+    }
+
+    let bvh = Bvh::from_reader(BufReader::new(
+        File::open("./data/test_mocapbank.bvh").unwrap(),
+    ))
+    .unwrap();
+    let num_joints = bvh.joints().count();
+    let mut bvh_ffi = bvh.clone().into_ffi();
+
+    let mut positions = vec![bvh_Position::default(); num_joints];
+    let mut matrices = vec![bvh_Matrix4::default(); num_joints];
+
+    unsafe {
+        let frame = 3;
+        assert_ne!(
+            bvh_get_world_positions(&bvh_ffi, frame, positions.as_mut_ptr(), num_joints),
+            0
+        );
+        assert_ne!(
+            bvh_get_world_matrices(&bvh_ffi, frame, matrices.as_mut_ptr(), num_joints),
+            0
+        );
+
+        let expected_positions = bvh.world_positions(frame).unwrap();
+        let expected_matrices = bvh.world_transforms(frame).unwrap();
+        for i in 0..num_joints {
+            assert_close(positions[i].position_x, expected_positions[i].x);
+            assert_close(positions[i].position_y, expected_positions[i].y);
+            assert_close(positions[i].position_z, expected_positions[i].z);
+
+            let expected: [[f32; 4]; 4] = expected_matrices[i].into();
+            let expected = expected.iter().flat_map(|c| c.iter());
+            for (&actual, &expected) in matrices[i].matrix_values.iter().zip(expected) {
+                assert_close(actual, expected);
+            }
+        }
+
+        let time = *bvh.frame_time() * 5 / 2;
+        let time_secs = time.as_secs() as f64 + f64::from(time.subsec_nanos()) * 1.0e-9;
+        assert_ne!(
+            bvh_get_world_positions_at_time(
+                &bvh_ffi,
+                time_secs,
+                positions.as_mut_ptr(),
+                num_joints
+            ),
+            0
+        );
+        assert_ne!(
+            bvh_get_world_matrices_at_time(&bvh_ffi, time_secs, matrices.as_mut_ptr(), num_joints),
+            0
+        );
+
+        let expected_positions = bvh.world_positions_at_time(time).unwrap();
+        let expected_matrices = bvh.world_transforms_at_time(time).unwrap();
+        for i in 0..num_joints {
+            assert_close(positions[i].position_x, expected_positions[i].x);
+            assert_close(positions[i].position_y, expected_positions[i].y);
+            assert_close(positions[i].position_z, expected_positions[i].z);
+            assert_close(matrices[i].matrix_values[12], expected_matrices[i].w.x);
+            assert_close(matrices[i].matrix_values[13], expected_matrices[i].w.y);
+            assert_close(matrices[i].matrix_values[14], expected_matrices[i].w.z);
+        }
+
+        let num_frames = bvh.num_frames();
+        assert_eq!(
+            bvh_get_world_positions(&bvh_ffi, num_frames, positions.as_mut_ptr(), num_joints),
+            0
+        );
+        assert_eq!(
+            bvh_get_world_positions(&bvh_ffi, 0, positions.as_mut_ptr(), num_joints - 1),
+            0
+        );
+
+        assert_ne!(bvh_destroy(&mut bvh_ffi), 0);
+    }
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use bvh_anim::{
+    errors::FilterError,
+    filter::{Filter, FilterOptions},
+    Bvh,
+};
+use common::channel;
+use std::time::Duration;
+
+const SKELETON: &str = "\
+HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 4 Xposition Yposition Zposition Zrotation
+    JOINT Tip
+    {
+        OFFSET 0.0 1.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 1.0 0.0
+        }
+    }
+}
+";
+
+/// A two joint skeleton with 60 frames of noisy motion, sampled at 50Hz.
+fn noisy_bvh() -> Bvh {
+    let frames = (0..60).map(|i| {
+        let noise = if i % 2 == 0 { 0.5 } else { -0.5 };
+        let t = i as f32 / 60.0;
+        // The root rotation jitters around 180 degrees, so that the raw
+        // values flip between 179.5 and -179.5.
+        let root_rotation = if i % 2 == 0 { 179.5 } else { -179.5 };
+        let (x, tip_rotation) = (t * 10.0 + noise, t * 45.0 + noise);
+        [x, 1.0, 0.0, root_rotation, tip_rotation, 0.0, 0.0]
+    });
+    common::clip(SKELETON, 0.02, frames)
+}
+
+/// The mean absolute second difference, which is large for jittery signals.
+fn roughness(values: &[f32]) -> f32 {
+    let sum = values
+        .windows(3)
+        .map(|w| (w[0] - 2.0 * w[1] + w[2]).abs())
+        .sum::<f32>();
+    sum / (values.len() - 2) as f32
+}
+
+#[test]
+fn filters_reduce_jitter() {
+    let filters = [
+        Filter::MovingAverage { window: 5 },
+        Filter::Gaussian { sigma: 2.0 },
+        Filter::SavitzkyGolay {
+            window: 9,
+            order: 2,
+        },
+        Filter::Butterworth {
+            cutoff: 6.0,
+            order: 2,
+        },
+    ];
+
+    for filter in &filters {
+        let mut bvh = noisy_bvh();
+        let before = channel(&bvh, 0);
+        FilterOptions::new()
+            .with_filter(*filter)
+            .apply(&mut bvh)
+            .unwrap();
+        let after = channel(&bvh, 0);
+
+        assert!(
+            roughness(&after) < roughness(&before) * 0.25,
+            "{:?}: {} >= {}",
+            filter,
+            roughness(&after),
+            roughness(&before)
+        );
+
+        // The underlying motion is preserved.
+        for (i, value) in after.iter().enumerate().skip(5).take(50) {
+            let expected = i as f32 / 60.0 * 10.0;
+            assert!((value - expected).abs() < 0.3, "{:?}: frame {}", filter, i);
+        }
+    }
+}
+
+#[test]
+fn filter_rotations_in_quaternion_space() {
+    let mut bvh = noisy_bvh();
+    FilterOptions::new()
+        .with_filter(Filter::Gaussian { sigma: 2.0 })
+        .apply(&mut bvh)
+        .unwrap();
+
+    // Averaging the raw values would pull the rotation towards 0 degrees.
+    for value in channel(&bvh, 3) {
+        let distance = (value.abs() - 180.0).abs();
+        assert!(distance < 1.0, "{}", value);
+    }
+
+    // The rotation of the tip is smoothed in the same way as a position.
+    let tip = channel(&bvh, 4);
+    assert!(roughness(&tip) < 0.5);
+}
+
+#[test]
+fn filter_selected_joints() {
+    let original = noisy_bvh();
+
+    let mut bvh = original.clone();
+    FilterOptions::new()
+        .with_joints(vec!["Tip"])
+        .apply(&mut bvh)
+        .unwrap();
+
+    assert_eq!(channel(&bvh, 0), channel(&original, 0));
+    assert_ne!(channel(&bvh, 4), channel(&original, 4));
+
+    let mut bvh = original.clone();
+    FilterOptions::new()
+        .with_rotations(false)
+        .apply(&mut bvh)
+        .unwrap();
+
+    assert_ne!(channel(&bvh, 0), channel(&original, 0));
+    assert_eq!(channel(&bvh, 4), channel(&original, 4));
+
+    let error = FilterOptions::new()
+        .with_joints(vec!["Missing"])
+        .apply(&mut bvh)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        FilterError::MissingJoint {
+            name: "Missing".into()
+        }
+    );
+}
+
+#[test]
+fn butterworth_cutoff_uses_frame_time() {
+    let mut bvh = noisy_bvh();
+    let butterworth = FilterOptions::new().with_filter(Filter::Butterworth {
+        cutoff: 30.0,
+        order: 2,
+    });
+
+    assert_eq!(
+        butterworth.apply(&mut bvh),
+        Err(FilterError::InvalidCutoff {
+            cutoff: 30.0,
+            nyquist: 25.0,
+        })
+    );
+
+    bvh.set_frame_time(Duration::from_millis(5));
+    assert_eq!(butterworth.apply(&mut bvh), Ok(()));
+
+    bvh.set_frame_time(Duration::from_secs(0));
+    assert_eq!(
+        butterworth.apply(&mut bvh),
+        Err(FilterError::InvalidFrameTime)
+    );
+}
+
+#[test]
+fn filter_rotations_which_channels_cannot_describe() {
+    const TWO_AXES: &str = "\
+HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 2 Zrotation Xrotation
+    End Site
+    {
+        OFFSET 0.0 1.0 0.0
+    }
+}
+";
+
+    let frames = (0..20).map(|i| if i % 2 == 0 { [0.0, 0.0] } else { [90.0, 90.0] });
+    let original = common::clip(TWO_AXES, 0.02, frames);
+
+    let mut bvh = original.clone();
+    let error = FilterOptions::new()
+        .with_filter(Filter::MovingAverage { window: 3 })
+        .apply(&mut bvh)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        FilterError::PoseNotPreserved {
+            name: "Base".into(),
+            frame: 1,
+        }
+    );
+    assert_eq!(bvh, original);
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use bvh_anim::{bvh, errors::HierarchyError, hierarchy::InsertOptions, Bvh, ChannelType};
+use common::{assert_same_pose, names};
+
+fn skeleton() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Spine
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Chest
+                {
+                    OFFSET 0.0 10.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    JOINT Arm
+                    {
+                        OFFSET 5.0 0.0 0.0
+                        CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+                        End Site
+                        {
+                            OFFSET 10.0 0.0 0.0
+                        }
+                    }
+                    JOINT Head
+                    {
+                        OFFSET 0.0 5.0 0.0
+                        CHANNELS 3 Zrotation Xrotation Yrotation
+                        End Site
+                        {
+                            OFFSET 0.0 5.0 0.0
+                        }
+                    }
+                }
+            }
+        }
+        MOTION
+        Frames: 3
+        Frame Time: 0.1
+        0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        1.0 90.0 0.0 10.0 5.0 0.0 20.0 0.0 10.0 30.0 -10.0 15.0 0.0 0.0 0.0 45.0 0.0 10.0 5.0 0.0 0.0
+        2.0 90.0 1.0 -5.0 0.0 30.0 -15.0 25.0 0.0 60.0 20.0 0.0 1.0 0.0 0.0 0.0 90.0 0.0 0.0 -5.0 10.0
+    }
+}
+
+/// Asserts that writing and loading `bvh` gives back the same skeleton.
+fn assert_round_trips(bvh: &Bvh) {
+    let loaded = Bvh::from_bytes(bvh.to_bstring()).unwrap();
+    assert_eq!(names(&loaded), names(bvh));
+    assert_eq!(loaded.num_channels(), bvh.num_channels());
+    for (a, b) in loaded.joints().zip(bvh.joints()) {
+        assert_eq!(a.data().parent_index(), b.data().parent_index());
+        assert_eq!(a.data().channels(), b.data().channels());
+    }
+}
+
+#[test]
+fn rename_joint() {
+    let mut bvh = skeleton();
+    bvh.rename_joint("Head", "Skull").unwrap();
+    assert_eq!(names(&bvh), vec!["Hips", "Spine", "Chest", "Arm", "Skull"]);
+    bvh.rename_joint("Skull", "Skull").unwrap();
+
+    assert_eq!(
+        bvh.rename_joint("Arm", "Skull"),
+        Err(HierarchyError::DuplicateJoint {
+            name: "Skull".into()
+        })
+    );
+    assert_eq!(
+        bvh.rename_joint("Head", "Neck"),
+        Err(HierarchyError::MissingJoint {
+            name: "Head".into()
+        })
+    );
+    for &name in &["", " ", "Left Arm"] {
+        assert_eq!(
+            bvh.rename_joint("Skull", name),
+            Err(HierarchyError::InvalidName { name: name.into() })
+        );
+    }
+}
+
+#[test]
+fn remove_joint() {
+    let original = skeleton();
+
+    // The head has no position channels, so it cannot stay in place once the
+    // chest is baked into it.
+    let mut baked = original.clone();
+    assert_eq!(
+        baked.remove_joint("Chest", true),
+        Err(HierarchyError::PoseNotPreserved {
+            name: "Head".into(),
+            frame: 1
+        })
+    );
+    assert_eq!(baked, original);
+
+    // Baking the chest into the arm alone keeps the arm in place.
+    baked.remove_joint("Head", false).unwrap();
+    baked.remove_joint("Chest", true).unwrap();
+    assert_eq!(names(&baked), vec!["Hips", "Spine", "Arm"]);
+    assert_eq!(baked.num_channels(), original.num_channels() - 6);
+    let arm = baked.joints().find_by_name("Arm").unwrap();
+    assert_eq!(arm.data().offset(), &[5.0, 10.0, 0.0].into());
+    assert_eq!(arm.data().parent_index(), Some(1));
+    assert_same_pose(&original, &baked);
+    assert_round_trips(&baked);
+
+    // Without baking, the motion of the chest is dropped, but the motion of
+    // the other joints is unchanged.
+    let mut dropped = original.clone();
+    dropped.remove_joint("Chest", false).unwrap();
+    for (before, after) in original.frames().zip(dropped.frames()) {
+        let (before, after) = (before.as_slice(), after.as_slice());
+        assert_eq!(&after[..9], &before[..9]);
+        assert_eq!(&after[9..], &before[12..]);
+    }
+
+    // Removing a leaf gives its parent an end site where the leaf was.
+    let mut leaf = original.clone();
+    leaf.remove_joint("Head", false).unwrap();
+    leaf.remove_joint("Arm", false).unwrap();
+    let chest = leaf.joints().find_by_name("Chest").unwrap();
+    assert_eq!(chest.data().end_site(), Some(&[5.0, 0.0, 0.0].into()));
+    assert_round_trips(&leaf);
+
+    assert_eq!(
+        leaf.remove_joint("Hips", false),
+        Err(HierarchyError::RootJoint)
+    );
+}
+
+#[test]
+fn insert_joint() {
+    let original = skeleton();
+    let mut bvh = original.clone();
+
+    let options = InsertOptions::new()
+        .with_offset([0.0, 5.0, 0.0].into())
+        .with_children(vec!["Chest"]);
+    bvh.insert_joint("Spine", "Spine1", &options).unwrap();
+    let options = InsertOptions::new().with_channels(vec![ChannelType::RotationX]);
+    bvh.insert_joint("Arm", "Hand", &options).unwrap();
+
+    assert_eq!(
+        names(&bvh),
+        vec!["Hips", "Spine", "Spine1", "Chest", "Arm", "Hand", "Head"]
+    );
+    assert_eq!(bvh.num_channels(), original.num_channels() + 4);
+    let chest = bvh.joints().find_by_name("Chest").unwrap();
+    assert_eq!(chest.data().offset(), &[0.0, 5.0, 0.0].into());
+    let arm = bvh.joints().find_by_name("Arm").unwrap();
+    assert_eq!(arm.data().end_site(), None);
+    let hand = bvh.joints().find_by_name("Hand").unwrap();
+    assert_eq!(hand.data().end_site(), Some(&[0.0, 0.0, 0.0].into()));
+
+    for frame in bvh.frames() {
+        assert_eq!(&frame.as_slice()[9..12], &[0.0; 3]);
+    }
+    assert_same_pose(&original, &bvh);
+    assert_round_trips(&bvh);
+
+    assert_eq!(
+        bvh.insert_joint("Chest", "Hand", &InsertOptions::new()),
+        Err(HierarchyError::DuplicateJoint {
+            name: "Hand".into()
+        })
+    );
+    assert_eq!(
+        bvh.insert_joint("Chest", "\t", &InsertOptions::new()),
+        Err(HierarchyError::InvalidName { name: "\t".into() })
+    );
+    let options = InsertOptions::new().with_children(vec!["Spine"]);
+    assert_eq!(
+        bvh.insert_joint("Chest", "Clavicle", &options),
+        Err(HierarchyError::NotAChild {
+            name: "Spine".into(),
+            parent: "Chest".into()
+        })
+    );
+}
+
+#[test]
+fn reparent_joint() {
+    let original = skeleton();
+    let mut bvh = original.clone();
+
+    bvh.reparent_joint("Arm", "Spine").unwrap();
+    assert_eq!(names(&bvh), vec!["Hips", "Spine", "Chest", "Head", "Arm"]);
+    let arm = bvh.joints().find_by_name("Arm").unwrap();
+    assert_eq!(arm.data().offset(), &[5.0, 10.0, 0.0].into());
+    assert_same_pose(&original, &bvh);
+    assert_round_trips(&bvh);
+
+    // The head has no position channels, so it can only be moved under the
+    // hips while the spine and the chest keep still.
+    let moved = bvh.clone();
+    assert_eq!(
+        bvh.reparent_joint("Head", "Hips"),
+        Err(HierarchyError::PoseNotPreserved {
+            name: "Head".into(),
+            frame: 1
+        })
+    );
+    assert_eq!(bvh, moved);
+
+    for frame in bvh.frames_mut() {
+        for value in &mut frame.as_mut_slice()[6..12] {
+            *value = 0.0;
+        }
+    }
+    let still = bvh.clone();
+    bvh.reparent_joint("Head", "Hips").unwrap();
+    assert_same_pose(&still, &bvh);
+    let chest = bvh.joints().find_by_name("Chest").unwrap();
+    assert_eq!(chest.data().end_site(), Some(&[0.0, 5.0, 0.0].into()));
+    assert_round_trips(&bvh);
+
+    assert_eq!(
+        bvh.reparent_joint("Spine", "Chest"),
+        Err(HierarchyError::InvalidParent {
+            name: "Spine".into(),
+            parent: "Chest".into()
+        })
+    );
+    assert_eq!(
+        bvh.reparent_joint("Hips", "Chest"),
+        Err(HierarchyError::RootJoint)
+    );
+}
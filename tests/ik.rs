@@ -0,0 +1,182 @@
+use bvh_anim::{
+    bvh,
+    errors::IkError,
+    ik::{IkOptions, RotationLimit, Solver},
+    Bvh,
+};
+
+fn arm() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Shoulder
+            {
+                OFFSET 0.0 0.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Elbow
+                {
+                    OFFSET 0.0 5.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    JOINT Wrist
+                    {
+                        OFFSET 0.0 5.0 0.0
+                        CHANNELS 3 Xrotation Yrotation Zrotation
+                        JOINT Hand
+                        {
+                            OFFSET 0.0 5.0 0.0
+                            CHANNELS 3 Zrotation Xrotation Yrotation
+                            End Site
+                            {
+                                OFFSET 0.0 1.0 0.0
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        MOTION
+        Frames: 2
+        Frame Time: 0.033333333
+        0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+    }
+}
+
+fn distance(a: mint::Vector3<f32>, b: [f32; 3]) -> f32 {
+    ((a.x - b[0]).powi(2) + (a.y - b[1]).powi(2) + (a.z - b[2]).powi(2)).sqrt()
+}
+
+#[test]
+fn iterative_solvers_reach_target() {
+    let target = [5.0, 8.0, 3.0];
+
+    for &solver in &[Solver::Ccd, Solver::Fabrik] {
+        let mut bvh = arm();
+        IkOptions::new()
+            .with_chain("Shoulder", "Hand")
+            .with_solver(solver)
+            .with_iterations(100)
+            .apply(&mut bvh, 0, target.into())
+            .unwrap();
+
+        let positions = bvh.world_positions(0).unwrap();
+        assert!(distance(positions[4], target) < 1.0e-2, "{:?}", solver);
+
+        // The bone lengths and the other frame are unchanged.
+        assert!((distance(positions[2], [0.0, 0.0, 0.0]) - 5.0).abs() < 1.0e-3);
+        assert_eq!(
+            bvh.frames().nth(1).unwrap().as_slice(),
+            arm().frames().nth(1).unwrap().as_slice()
+        );
+    }
+}
+
+#[test]
+fn two_bone_solver() {
+    let bvh = arm();
+    let target = [4.0, 6.0, 0.0];
+    let values = IkOptions::new()
+        .with_chain("Elbow", "Hand")
+        .with_solver(Solver::TwoBone)
+        .solve(&bvh, 0, target.into())
+        .unwrap();
+
+    let mut solved = bvh.clone();
+    solved
+        .frames_mut()
+        .next()
+        .unwrap()
+        .as_mut_slice()
+        .copy_from_slice(&values);
+
+    let positions = solved.world_positions(0).unwrap();
+    assert!(
+        distance(positions[4], target) < 1.0e-3,
+        "{:?}",
+        positions[4]
+    );
+
+    // Only the rotations of the elbow and the wrist are changed.
+    let original = bvh.frames().next().unwrap().as_slice().to_vec();
+    assert_eq!(&values[..9], &original[..9]);
+    assert_eq!(&values[15..], &original[15..]);
+}
+
+#[test]
+fn rotation_limits() {
+    let mut bvh = arm();
+    let zero = [0.0, 0.0, 0.0];
+    IkOptions::new()
+        .with_chain("Shoulder", "Hand")
+        .with_solver(Solver::Ccd)
+        .with_limit(RotationLimit::new("Elbow", zero.into(), zero.into()))
+        .with_limit(RotationLimit::new("Wrist", zero.into(), zero.into()))
+        .apply(&mut bvh, 1, [0.0, 0.0, 20.0].into())
+        .unwrap();
+
+    let values = bvh.frames().nth(1).unwrap().as_slice().to_vec();
+    assert_eq!(
+        &values[9..15],
+        &zero.iter().chain(&zero).cloned().collect::<Vec<_>>()[..]
+    );
+
+    let positions = bvh.world_positions(1).unwrap();
+    assert!(
+        distance(positions[4], [0.0, 0.0, 15.0]) < 1.0e-2,
+        "{:?}",
+        positions[4]
+    );
+}
+
+#[test]
+fn ik_errors() {
+    let bvh = arm();
+    let target = [0.0, 0.0, 0.0].into();
+
+    assert_eq!(
+        IkOptions::new()
+            .with_chain("Shoulder", "Foot")
+            .solve(&bvh, 0, target),
+        Err(IkError::MissingJoint {
+            name: "Foot".into()
+        })
+    );
+    assert_eq!(
+        IkOptions::new()
+            .with_chain("Hand", "Shoulder")
+            .solve(&bvh, 0, target),
+        Err(IkError::NotAChain {
+            start: "Hand".into(),
+            end: "Shoulder".into()
+        })
+    );
+    assert_eq!(
+        IkOptions::new()
+            .with_chain("Shoulder", "Hand")
+            .with_solver(Solver::TwoBone)
+            .solve(&bvh, 0, target),
+        Err(IkError::InvalidChainLength { len: 4 })
+    );
+    assert_eq!(
+        IkOptions::new()
+            .with_chain("Shoulder", "Hand")
+            .solve(&bvh, 2, target),
+        Err(IkError::FrameOutOfBounds { frame: 2 })
+    );
+    assert_eq!(
+        IkOptions::new()
+            .with_chain("Shoulder", "Hand")
+            .with_limit(RotationLimit::new(
+                "Elbow",
+                [0.0, 10.0, 0.0].into(),
+                [0.0, -10.0, 0.0].into()
+            ))
+            .solve(&bvh, 0, target),
+        Err(IkError::InvalidLimit {
+            name: "Elbow".into()
+        })
+    );
+}
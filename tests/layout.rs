@@ -0,0 +1,166 @@
+mod common;
+
+use bvh_anim::{
+    bvh,
+    errors::LayoutError,
+    layout::{LayoutOptions, PositionChannels},
+    Axis, Bvh, ChannelType,
+};
+use common::assert_same_pose;
+
+fn skeleton() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Spine
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT Arm
+                {
+                    OFFSET 5.0 0.0 0.0
+                    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+                    End Site
+                    {
+                        OFFSET 10.0 0.0 0.0
+                    }
+                }
+                JOINT Head
+                {
+                    OFFSET 0.0 5.0 0.0
+                    CHANNELS 3 Xrotation Yrotation Zrotation
+                    End Site
+                    {
+                        OFFSET 0.0 5.0 0.0
+                    }
+                }
+            }
+        }
+        MOTION
+        Frames: 3
+        Frame Time: 0.1
+        0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        1.0 90.0 0.0 10.0 5.0 0.0 20.0 0.0 10.0 2.0 0.0 0.0 45.0 0.0 10.0 5.0 0.0 0.0
+        2.0 90.0 1.0 -5.0 0.0 30.0 -15.0 25.0 0.0 3.0 0.0 0.0 0.0 90.0 0.0 0.0 -5.0 10.0
+    }
+}
+
+fn channel_types(bvh: &Bvh, name: &str) -> Vec<ChannelType> {
+    let joint = bvh.joints().find_by_name(name).unwrap();
+    let data = joint.data();
+    data.channels().iter().map(|c| c.channel_type()).collect()
+}
+
+#[test]
+fn rotation_order() {
+    use ChannelType::*;
+
+    let original = skeleton();
+    let mut bvh = original.clone();
+    LayoutOptions::new()
+        .with_rotation_order([Axis::X, Axis::Y, Axis::Z])
+        .apply(&mut bvh)
+        .unwrap();
+
+    assert_eq!(bvh.num_channels(), original.num_channels());
+    assert_eq!(
+        channel_types(&bvh, "Hips"),
+        vec![PositionX, PositionY, PositionZ, RotationX, RotationY, RotationZ]
+    );
+    assert_eq!(
+        channel_types(&bvh, "Spine"),
+        vec![RotationX, RotationY, RotationZ]
+    );
+    // The head already uses this order, so its values are unchanged.
+    for (before, after) in original.frames().zip(bvh.frames()) {
+        assert_eq!(&after.as_slice()[15..], &before.as_slice()[15..]);
+    }
+    assert_same_pose(&original, &bvh);
+
+    // Changing the order back gives the original values.
+    LayoutOptions::new().apply(&mut bvh).unwrap();
+    bvh.set_joint_channels("Head", &[RotationX, RotationY, RotationZ])
+        .unwrap();
+    for (before, after) in original.frames().zip(bvh.frames()) {
+        for (a, b) in after.as_slice().iter().zip(before.as_slice()) {
+            assert!((a - b).abs() < 1.0e-3, "{:?} != {:?}", after, before);
+        }
+    }
+
+    let loaded = Bvh::from_bytes(bvh.to_bstring()).unwrap();
+    assert_eq!(channel_types(&loaded, "Head"), channel_types(&bvh, "Head"));
+    assert_same_pose(&original, &loaded);
+}
+
+#[test]
+fn position_channels() {
+    use ChannelType::*;
+
+    let original = skeleton();
+
+    // Every joint gets position channels, which are zero where they are new.
+    let mut all = original.clone();
+    LayoutOptions::new()
+        .with_positions(PositionChannels::All)
+        .apply(&mut all)
+        .unwrap();
+    assert_eq!(all.num_channels(), 24);
+    assert_eq!(
+        channel_types(&all, "Spine"),
+        vec![PositionX, PositionY, PositionZ, RotationZ, RotationX, RotationY]
+    );
+    for frame in all.frames() {
+        assert_eq!(&frame.as_slice()[6..9], &[0.0; 3]);
+    }
+    assert_same_pose(&original, &all);
+
+    // Positions which never change are moved into the offset.
+    let mut bvh = original.clone();
+    bvh.set_joint_channels(
+        "Hips",
+        &[PositionX, PositionZ, RotationZ, RotationX, RotationY],
+    )
+    .unwrap();
+    bvh.set_joint_channels("Arm", &[PositionX, RotationZ, RotationX, RotationY])
+        .unwrap();
+    assert_eq!(bvh.num_channels(), original.num_channels() - 3);
+    let hips = bvh.joints().find_by_name("Hips").unwrap();
+    assert_eq!(hips.data().offset(), &[0.0, 90.0, 0.0].into());
+    assert_same_pose(&original, &bvh);
+}
+
+#[test]
+fn pose_not_preserved() {
+    use ChannelType::*;
+
+    let original = skeleton();
+    let mut bvh = original.clone();
+
+    // The arm moves along `x`, so it must keep its `Xposition` channel.
+    assert_eq!(
+        LayoutOptions::new()
+            .with_positions(PositionChannels::Root)
+            .apply(&mut bvh),
+        Err(LayoutError::PoseNotPreserved {
+            name: "Arm".into(),
+            frame: 1,
+        })
+    );
+    assert_eq!(
+        bvh.set_joint_channels("Spine", &[RotationZ]),
+        Err(LayoutError::PoseNotPreserved {
+            name: "Spine".into(),
+            frame: 1,
+        })
+    );
+    assert_eq!(
+        bvh.set_joint_channels("Tail", &[RotationZ]),
+        Err(LayoutError::MissingJoint {
+            name: "Tail".into()
+        })
+    );
+    assert_eq!(bvh, original);
+}
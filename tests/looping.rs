@@ -0,0 +1,65 @@
+mod common;
+
+use bvh_anim::{looping::LoopOptions, Bvh};
+use common::{channel, ARM_SKELETON};
+
+/// A clip which walks forwards while swinging its arm with a period of 12
+/// frames, and slowly raises the arm, so that no two cycles match exactly.
+fn swinging() -> Bvh {
+    let frames = (0..40).map(|i| {
+        let phase = i as f32 * std::f32::consts::PI * 2.0 / 12.0;
+        let arm = 40.0 * phase.sin() + i as f32 * 0.1;
+        [i as f32 * 2.0, 90.0, 0.0, 0.0, 0.0, 0.0, arm, 0.0, 0.0]
+    });
+    common::clip(ARM_SKELETON, 0.033333333, frames)
+}
+
+fn arm(bvh: &Bvh) -> Vec<f32> {
+    channel(bvh, 6)
+}
+
+#[test]
+fn find_best_loop() {
+    let bvh = swinging();
+    let range = LoopOptions::new().find(&bvh).unwrap();
+
+    // The shortest whole cycle matches best, because the arm drifts upwards.
+    assert_eq!(range.len(), 12);
+    assert!(range.error() > 0.0);
+    assert_eq!(range.root_offset(), [24.0, 0.0, 0.0].into());
+
+    let longer = LoopOptions::new()
+        .with_min_length(13)
+        .with_max_length(Some(30))
+        .find(&bvh)
+        .unwrap();
+    assert_eq!(longer.len(), 24);
+    assert!(longer.error() > range.error());
+
+    assert_eq!(LoopOptions::new().with_min_length(40).find(&bvh), None);
+}
+
+#[test]
+fn make_seamless_loop() {
+    let bvh = swinging();
+    let (looped, range) = LoopOptions::new().make_loop(&bvh).unwrap();
+    assert_eq!(looped.num_frames(), range.len());
+
+    let original = arm(&bvh);
+    let looped_arm = arm(&looped);
+
+    // The start of the loop is unchanged, and the jump from the last frame
+    // back to the first is the same as the step into the end frame.
+    assert_eq!(looped_arm[0], original[range.start()]);
+    let seam = (looped_arm[0] - looped_arm[range.len() - 1]).abs();
+    let step = (original[range.end()] - original[range.end() - 1]).abs();
+    assert!((seam - step).abs() < 1.0e-2, "{} != {}", seam, step);
+    let unblended_seam = (original[range.start()] - original[range.end() - 1]).abs();
+    assert!((unblended_seam - step).abs() > 1.0);
+
+    // The root keeps walking forwards.
+    let root = looped.frames().map(|f| f.as_slice()[0]).collect::<Vec<_>>();
+    for pair in root.windows(2) {
+        assert_eq!(pair[1] - pair[0], 2.0);
+    }
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use bvh_anim::{
+    additive::AdditiveOptions,
+    blend::CrossfadeOptions,
+    errors::{BlendError, FilterError, MaskError},
+    filter::{Filter, FilterOptions},
+    mask::JointMask,
+    Bvh,
+};
+use common::channel;
+
+const SKELETON: &str = "\
+HIERARCHY
+ROOT Hips
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Chest
+    {
+        OFFSET 0.0 10.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Arm
+        {
+            OFFSET 5.0 0.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            End Site
+            {
+                OFFSET 5.0 0.0 0.0
+            }
+        }
+    }
+    JOINT Leg
+    {
+        OFFSET 0.0 -10.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 -10.0 0.0
+        }
+    }
+}
+";
+
+/// A clip of `frames` frames in which the chest, arm and leg are rotated by
+/// `angle` degrees around `z`, and the root moves along `x` by `step`.
+fn clip(frames: usize, step: f32, angle: impl Fn(usize) -> f32) -> Bvh {
+    let frames = (0..frames).map(|i| {
+        let a = angle(i);
+        let x = i as f32 * step;
+        [
+            x, 0.0, 0.0, 0.0, 0.0, 0.0, a, 0.0, 0.0, a, 0.0, 0.0, a, 0.0, 0.0,
+        ]
+    });
+    common::clip(SKELETON, 0.1, frames)
+}
+
+fn assert_near(a: &[f32], b: &[f32], tolerance: f32) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        assert!((a - b).abs() < tolerance, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn mask_weights() {
+    let bvh = clip(1, 0.0, |_| 0.0);
+    let chest = bvh.joints().find_by_name("Chest").unwrap();
+
+    let upper_body = JointMask::from_subtree(&chest);
+    assert_eq!(
+        upper_body.joint_weights(&bvh).unwrap(),
+        vec![0.0, 1.0, 1.0, 0.0]
+    );
+
+    let mask = JointMask::from_weights(vec![("Leg", 0.25), ("Hips", 2.0)])
+        .with_subtree(&chest, 0.5)
+        .with_joint("Arm", 0.0);
+    assert_eq!(mask.weight("Leg"), 0.25);
+    assert_eq!(mask.weight("Missing"), 0.0);
+    assert_eq!(mask.joint_weights(&bvh).unwrap(), vec![1.0, 0.5, 0.0, 0.25]);
+
+    let channels = mask.channel_weights(&bvh).unwrap();
+    assert_eq!(channels.len(), bvh.num_channels());
+    assert_eq!(&channels[..6], &[1.0; 6]);
+    assert_eq!(&channels[6..9], &[0.5; 3]);
+    assert_eq!(&channels[9..12], &[0.0; 3]);
+    assert_eq!(&channels[12..], &[0.25; 3]);
+
+    assert!(JointMask::new().is_empty());
+    assert_eq!(
+        JointMask::from_names(vec!["Tail"]).joint_weights(&bvh),
+        Err(MaskError::MissingJoint {
+            name: "Tail".into()
+        })
+    );
+}
+
+#[test]
+fn mask_filter() {
+    let spike = |i: usize| if i == 2 { 30.0 } else { 0.0 };
+    let mut bvh = clip(5, 0.0, spike);
+    let mask = JointMask::from_names(vec!["Chest"]).with_joint("Arm", 0.5);
+
+    FilterOptions::new()
+        .with_filter(Filter::MovingAverage { window: 3 })
+        .with_mask(mask)
+        .apply(&mut bvh)
+        .unwrap();
+
+    // The chest is fully filtered, the arm is halfway between the original
+    // and the filtered motion, and the leg is unchanged. Rotations
+    // are averaged as quaternions, so they are close to the euler average.
+    assert_near(&channel(&bvh, 6), &[0.0, 10.0, 10.0, 10.0, 0.0], 0.1);
+    assert_near(&channel(&bvh, 9), &[0.0, 5.0, 20.0, 5.0, 0.0], 0.1);
+    assert_eq!(channel(&bvh, 12), vec![0.0, 0.0, 30.0, 0.0, 0.0]);
+
+    let err = FilterOptions::new()
+        .with_mask(JointMask::from_names(vec!["Tail"]))
+        .apply(&mut bvh)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        FilterError::MissingJoint {
+            name: "Tail".into()
+        }
+    );
+}
+
+#[test]
+fn mask_crossfade_and_additive() {
+    let walk = clip(4, 1.0, |_| 0.0);
+    let wave = clip(4, 0.0, |_| 60.0);
+    let bvh = clip(1, 0.0, |_| 0.0);
+    let chest = bvh.joints().find_by_name("Chest").unwrap();
+    let upper_body = JointMask::from_subtree(&chest);
+
+    // Only the upper body transitions to the wave, and the rest of the body
+    // keeps walking, holding the last frame of the walk once it ends.
+    let blended = CrossfadeOptions::new()
+        .with_frames(1, 0)
+        .with_mask(upper_body.clone())
+        .crossfade(&walk, &wave)
+        .unwrap();
+    assert_eq!(blended.num_frames(), 5);
+    assert_eq!(channel(&blended, 0), vec![0.0, 1.0, 2.0, 3.0, 3.0]);
+    assert_near(
+        &channel(&blended, 6),
+        &[0.0, 60.0, 60.0, 60.0, 60.0],
+        1.0e-3,
+    );
+    assert_near(
+        &channel(&blended, 9),
+        &[0.0, 60.0, 60.0, 60.0, 60.0],
+        1.0e-3,
+    );
+    assert_near(&channel(&blended, 12), &[0.0; 5], 1.0e-3);
+
+    // Additive layers are scaled by the weight of each joint.
+    let mut base = clip(4, 1.0, |_| 0.0);
+    let layer = clip(1, 0.0, |_| 40.0);
+    AdditiveOptions::new()
+        .with_reference(bvh_anim::additive::ReferencePose::Values(vec![0.0; 15]))
+        .make_additive(&layer)
+        .and_then(|additive| {
+            AdditiveOptions::new()
+                .with_mask(upper_body.with_joint("Arm", 0.5))
+                .apply(&mut base, &additive)
+        })
+        .unwrap();
+    assert_near(&channel(&base, 3), &[0.0; 4], 1.0e-3);
+    assert_near(&channel(&base, 6), &[40.0; 4], 1.0e-3);
+    assert_near(&channel(&base, 9), &[20.0; 4], 1.0e-3);
+    assert_near(&channel(&base, 12), &[0.0; 4], 1.0e-3);
+
+    let err = CrossfadeOptions::new()
+        .with_mask(JointMask::from_names(vec!["Tail"]))
+        .crossfade(&walk, &wave)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        BlendError::MissingJoint {
+            name: "Tail".into()
+        }
+    );
+}
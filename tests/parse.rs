@@ -130,3 +130,61 @@ fn nonstandard_formatting() {
 
     assert_eq!(bvh, bvh_from_macro);
 }
+
+#[test]
+fn joint_children() {
+    let bvh = bvh_anim::bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Xposition Yposition Zposition
+            JOINT Middle1
+            {
+                OFFSET 0.0 0.0 15.0
+                CHANNELS 0
+                JOINT Tip1
+                {
+                    OFFSET 0.0 0.0 30.0
+                    CHANNELS 0
+                    End Site
+                    {
+                        OFFSET 0.0 0.0 45.0
+                    }
+                }
+            }
+            JOINT Middle2
+            {
+                OFFSET 0.0 15.0 0.0
+                CHANNELS 0
+                End Site
+                {
+                    OFFSET 0.0 30.0 0.0
+                }
+            }
+        }
+
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333333
+        0.0 0.0 0.0
+    };
+
+    let names = |joints: bvh_anim::Joints<'_>| {
+        joints
+            .map(|j| j.data().name().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let root = bvh.root_joint().unwrap();
+    assert_eq!(names(root.children()), vec!["Middle1", "Middle2"]);
+
+    let middle1 = bvh.joints().find_by_name("Middle1").unwrap();
+    assert_eq!(names(middle1.children()), vec!["Tip1"]);
+
+    let tip1 = bvh.joints().find_by_name("Tip1").unwrap();
+    assert_eq!(names(tip1.children()), Vec::<String>::new());
+
+    let middle2 = bvh.joints().find_by_name("Middle2").unwrap();
+    assert_eq!(names(middle2.children()), Vec::<String>::new());
+}
@@ -0,0 +1,79 @@
+use bvh_anim::{
+    bvh,
+    reduce::{Interpolation, ReduceOptions, ReductionMode},
+};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn reduce_linear_motion() {
+    let bvh = bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 2 Xposition Zrotation
+            End Site
+            {
+                OFFSET 0.0 1.0 0.0
+            }
+        }
+        MOTION
+        Frames: 5
+        Frame Time: 0.033333333
+        0.0 0.0
+        1.0 10.0
+        2.0 20.0
+        3.0 10.0
+        4.0 0.0
+    };
+
+    let sparse = ReduceOptions::new().reduce(&bvh);
+    let key_frames = sparse
+        .tracks()
+        .iter()
+        .map(|t| t.keys().iter().map(|k| k.frame()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    assert_eq!(key_frames, vec![vec![0, 2, 4], vec![0, 4]]);
+    assert_eq!(sparse.to_bvh(), bvh);
+    assert_eq!(sparse.max_error().rotation, 0.0);
+    assert_eq!(sparse.max_error().position, 0.0);
+}
+
+#[test]
+fn reduce_within_tolerance() {
+    const BVH_BYTES: &[u8] = include_bytes!("../data/test_mocapbank.bvh");
+    let bvh = bvh_anim::from_bytes(BVH_BYTES).unwrap();
+    let total_values = bvh.num_frames() * bvh.num_channels();
+
+    for &mode in &[ReductionMode::PerChannel, ReductionMode::PerJointRotation] {
+        let options = ReduceOptions::new()
+            .with_rotation_tolerance(1.0)
+            .with_position_tolerance(0.1)
+            .with_mode(mode);
+        let sparse = options.reduce(&bvh);
+
+        assert!(sparse.num_keys() < total_values);
+        assert!(sparse.max_error().rotation <= 1.0 + 1.0e-3);
+        assert!(sparse.max_error().position <= 0.1 + 1.0e-3);
+
+        if mode == ReductionMode::PerJointRotation {
+            assert!(sparse
+                .tracks()
+                .iter()
+                .any(|t| t.interpolation() == Interpolation::Slerp));
+        }
+
+        let baked = sparse.to_bvh();
+        assert_eq!(baked.num_frames(), bvh.num_frames());
+        assert_eq!(baked.num_channels(), bvh.num_channels());
+
+        if mode == ReductionMode::PerChannel {
+            for (baked, original) in baked.frames().zip(bvh.frames()) {
+                for (a, b) in baked.as_slice().iter().zip(original.as_slice()) {
+                    assert!((a - b).abs() <= 1.0 + 1.0e-3);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use bvh_anim::{
+    bvh,
+    render::{Bounds, Camera, PreviewOptions, RenderOptions},
+    Bvh,
+};
+use pretty_assertions::assert_eq;
+use std::time::Duration;
+
+fn two_bones() -> Bvh {
+    bvh! {
+        HIERARCHY
+        ROOT Base
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Tip
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 10.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 3
+        Frame Time: 0.033333333
+        0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        0.0 0.0 0.0 0.0 0.0 0.0 45.0 0.0 0.0
+        0.0 0.0 0.0 0.0 0.0 0.0 90.0 0.0 0.0
+    }
+}
+
+fn options() -> RenderOptions {
+    RenderOptions::new()
+        .with_size(100, 100)
+        .with_camera(Camera::orthographic(
+            [0.0, 10.0, 100.0],
+            [0.0, 10.0, 0.0],
+            25.0,
+        ))
+}
+
+#[test]
+fn render_svg() {
+    let bvh = two_bones();
+    let svg = options().render_svg(&bvh, 0).unwrap();
+
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<line").count(), 2);
+    assert_eq!(svg.matches("<circle").count(), 3);
+    assert!(svg.contains(r#"<line x1="50.00" y1="90.00" x2="50.00" y2="50.00"/>"#));
+
+    assert!(options().render_svg(&bvh, 3).is_none());
+}
+
+#[test]
+fn render_image() {
+    let bvh = two_bones();
+    let options = options();
+    let image = options.render_image(&bvh, 0).unwrap();
+
+    assert_eq!(image.pixel(0, 0), Some(options.background));
+    assert_eq!(image.pixel(50, 70), Some(options.bone_color));
+    assert_eq!(image.pixel(50, 10), Some(options.end_site_color));
+    assert_eq!(image.pixel(100, 0), None);
+}
+
+#[test]
+fn render_filmstrip() {
+    let bvh = two_bones();
+    let options = options();
+
+    let image = options.render_filmstrip_image(&bvh, 3).unwrap();
+    assert_eq!((image.width(), image.height()), (300, 100));
+    // The tip of the last frame is rotated by 90 degrees around z.
+    assert_eq!(image.pixel(250, 10), Some(options.background));
+    assert_eq!(image.pixel(230, 50), Some(options.bone_color));
+
+    let svg = options.render_filmstrip_svg(&bvh, 3).unwrap();
+    assert!(svg.contains(r#"width="300""#));
+    assert_eq!(svg.matches("<line").count(), 6);
+
+    assert!(options.render_filmstrip_svg(&bvh, 1 << 30).is_err());
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn render_png() {
+    let bvh = two_bones();
+    let mut png = vec![];
+    options().write_png(&bvh, 0, &mut png).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn preview_frames() {
+    let bvh = two_bones();
+
+    let preview = PreviewOptions::new();
+    assert_eq!(preview.frames(&bvh), vec![0, 1, 2]);
+    assert_eq!(preview.frame_delay(&bvh), *bvh.frame_time());
+
+    let preview = PreviewOptions::new()
+        .with_frame_range(1, 3)
+        .with_frame_rate(60.0);
+    assert_eq!(preview.frames(&bvh), vec![1, 1, 2, 2]);
+
+    let images = preview.render_images(&bvh);
+    assert_eq!(images.len(), 4);
+
+    // Frame times of a second or more are resampled the same way.
+    let mut slow = two_bones();
+    slow.set_frame_time(Duration::from_secs(2));
+    let preview = PreviewOptions::new().with_frame_rate(1.0);
+    assert_eq!(preview.frames(&slow), vec![0, 0, 1, 1, 2, 2]);
+}
+
+#[test]
+fn camera_framing() {
+    let bvh = two_bones();
+    let bounds = Bounds::of_motion(&bvh, 0..bvh.num_frames()).unwrap();
+
+    for camera in &[
+        Camera::orthographic([0.0, 0.0, 10.0], [0.0, 0.0, 0.0], 1.0),
+        Camera::perspective([0.0, 0.0, 10.0], [0.0, 0.0, 0.0], 45.0),
+    ] {
+        let camera = camera.framing(&bounds, 2.0);
+        for frame in 0..bvh.num_frames() {
+            for position in bvh.world_positions(frame).unwrap() {
+                let [x, y] = camera.project(position, 200, 100).unwrap();
+                assert!(x >= 0.0 && x <= 200.0, "{} out of bounds", x);
+                assert!(y >= 0.0 && y <= 100.0, "{} out of bounds", y);
+            }
+        }
+    }
+    // Looking straight down along the up direction still gives an image.
+    let top_down = Camera::orthographic([0.0, 10.0, 0.0], [0.0, 0.0, 0.0], 4.0);
+    let [x, y] = top_down.project([1.0, 0.0, 1.0].into(), 100, 100).unwrap();
+    assert!(x.is_finite() && y.is_finite());
+    assert!((x - 50.0).abs() > 1.0 && (y - 50.0).abs() > 1.0);
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn preview_gif() {
+    let bvh = two_bones();
+    let mut gif = vec![];
+    PreviewOptions::new()
+        .with_render_options(options())
+        .write_gif(&bvh, &mut gif)
+        .unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+}
@@ -0,0 +1,298 @@
+mod common;
+
+use bvh_anim::{
+    bvh,
+    errors::RestPoseError,
+    rest_pose::{RestPose, RestPoseOptions},
+    Bvh,
+};
+
+/// A skeleton in an A-pose, with fingers on the left hand.
+const SKELETON: &str = "\
+HIERARCHY
+ROOT Hips
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Spine
+    {
+        OFFSET 0.0 10.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Chest
+        {
+            OFFSET 0.0 10.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            JOINT Neck
+            {
+                OFFSET 0.0 5.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 5.0 0.0
+                }
+            }
+            JOINT LeftShoulder
+            {
+                OFFSET 2.0 5.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT LeftArm
+                {
+                    OFFSET 3.0 0.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    JOINT LeftForeArm
+                    {
+                        OFFSET 7.0 -7.0 0.0
+                        CHANNELS 3 Zrotation Xrotation Yrotation
+                        JOINT LeftHand
+                        {
+                            OFFSET 7.0 -7.0 0.0
+                            CHANNELS 3 Zrotation Xrotation Yrotation
+                            JOINT LeftFinger
+                            {
+                                OFFSET 2.0 -2.0 0.0
+                                CHANNELS 3 Zrotation Xrotation Yrotation
+                                End Site
+                                {
+                                    OFFSET 1.0 -1.0 0.0
+                                }
+                            }
+                            JOINT LeftThumb
+                            {
+                                OFFSET 1.0 -2.0 1.0
+                                CHANNELS 3 Zrotation Xrotation Yrotation
+                                End Site
+                                {
+                                    OFFSET 1.0 -1.0 0.0
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            JOINT RightShoulder
+            {
+                OFFSET -2.0 5.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                JOINT RightArm
+                {
+                    OFFSET -3.0 0.0 0.0
+                    CHANNELS 3 Zrotation Xrotation Yrotation
+                    JOINT RightForeArm
+                    {
+                        OFFSET -7.0 -7.0 0.0
+                        CHANNELS 3 Zrotation Xrotation Yrotation
+                        End Site
+                        {
+                            OFFSET -7.0 -7.0 0.0
+                        }
+                    }
+                }
+            }
+        }
+    }
+    JOINT LeftUpLeg
+    {
+        OFFSET 3.0 0.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 -20.0 0.0
+        }
+    }
+    JOINT RightUpLeg
+    {
+        OFFSET -3.0 0.0 0.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 -20.0 0.0
+        }
+    }
+}
+";
+
+/// A clip of `SKELETON` with `frames` frames, in which every joint is
+/// rotated by a different amount in each frame.
+fn clip(frames: usize) -> Bvh {
+    let frames = (0..frames).map(|i| {
+        (0..48)
+            .map(|c| ((i * 7 + c * 13) % 40) as f32 - 20.0)
+            .collect::<Vec<_>>()
+    });
+    common::clip(SKELETON, 0.1, frames)
+}
+
+fn index(bvh: &Bvh, name: &str) -> usize {
+    bvh.joints().find_by_name(name).unwrap().data().index()
+}
+
+/// Asserts that every joint has the same world position in each frame in
+/// `before` and `after`, up to the precision of the re-encoded angles.
+fn assert_same_positions(before: &Bvh, after: &Bvh) {
+    for frame in 0..before.num_frames() {
+        let expected = before.world_positions(frame).unwrap();
+        let actual = after.world_positions(frame).unwrap();
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            let (a, e): ([f32; 3], [f32; 3]) = ((*a).into(), (*e).into());
+            for (a, e) in a.iter().zip(e.iter()) {
+                assert!(
+                    (a - e).abs() < 1.0e-2,
+                    "frame {}: {:?} != {:?}",
+                    frame,
+                    a,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn detect_rest_pose() {
+    let bvh = clip(1);
+    let rest = bvh.rest_positions();
+    assert_eq!(rest[index(&bvh, "LeftHand")], [19.0, 11.0, 0.0].into());
+    assert_eq!(rest[index(&bvh, "RightUpLeg")], [-3.0, 0.0, 0.0].into());
+
+    let options = RestPoseOptions::new();
+    assert_eq!(options.detect(&bvh), RestPose::APose);
+    let angle = options.arm_angle(&bvh).unwrap();
+    assert!((angle - (-14.0f32).atan2(17.0).to_degrees()).abs() < 1.0e-3);
+    assert_eq!(
+        options.clone().with_tolerance(45.0).detect(&bvh),
+        RestPose::TPose
+    );
+
+    // Legs branch off the root, so they are not arms.
+    let mut legs = bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            JOINT LeftUpLeg
+            {
+                OFFSET 3.0 0.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 10.0 -10.0 0.0
+                }
+            }
+            JOINT RightUpLeg
+            {
+                OFFSET -3.0 0.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET -10.0 -10.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.1
+        0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+    };
+    assert_eq!(options.detect(&legs), RestPose::Other);
+    assert_eq!(options.arm_angle(&legs), None);
+    assert_eq!(
+        options.to_t_pose(&mut legs),
+        Err(RestPoseError::MissingArms)
+    );
+}
+
+#[test]
+fn to_t_pose() {
+    let original = clip(4);
+    let mut bvh = original.clone();
+    let options = RestPoseOptions::new();
+    options.to_t_pose(&mut bvh).unwrap();
+
+    assert_eq!(options.detect(&bvh), RestPose::TPose);
+    assert!(options.arm_angle(&bvh).unwrap().abs() < 1.0e-3);
+    assert_same_positions(&original, &bvh);
+
+    // The arms are straight and horizontal, and keep their length.
+    let rest = bvh.rest_positions();
+    let left_shoulder = rest[index(&bvh, "LeftShoulder")];
+    let left_hand = rest[index(&bvh, "LeftHand")];
+    assert!((left_hand.y - left_shoulder.y).abs() < 1.0e-4);
+    assert!(left_hand.z.abs() < 1.0e-4);
+    assert!((left_hand.x - left_shoulder.x - 3.0 - 2.0 * 98f32.sqrt()).abs() < 1.0e-3);
+
+    // The rest of the skeleton is unchanged.
+    let original_rest = original.rest_positions();
+    for name in &[
+        "Hips",
+        "Spine",
+        "Chest",
+        "Neck",
+        "LeftShoulder",
+        "LeftUpLeg",
+    ] {
+        assert_eq!(rest[index(&bvh, name)], original_rest[index(&bvh, name)]);
+    }
+
+    let loaded = Bvh::from_bytes(bvh.to_bstring()).unwrap();
+    assert_eq!(options.detect(&loaded), RestPose::TPose);
+    assert_same_positions(&original, &loaded);
+}
+
+#[test]
+fn set_bone_directions() {
+    let original = clip(3);
+    let mut bvh = original.clone();
+    bvh.set_bone_directions(&[
+        ("Spine", [0.0, 1.0, 1.0].into()),
+        ("LeftUpLeg", [0.0, -1.0, 0.5].into()),
+    ])
+    .unwrap();
+
+    let chest = bvh.joints().find_by_name("Chest").unwrap();
+    let offset = chest.data().offset();
+    assert!(offset.x.abs() < 1.0e-4);
+    assert!((offset.y - 50f32.sqrt()).abs() < 1.0e-4);
+    assert!((offset.z - 50f32.sqrt()).abs() < 1.0e-4);
+    assert_same_positions(&original, &bvh);
+
+    assert_eq!(
+        bvh.set_bone_directions(&[("Tail", [0.0, 1.0, 0.0].into())]),
+        Err(RestPoseError::MissingJoint {
+            name: "Tail".into()
+        })
+    );
+
+    // A joint which only rotates around `z` cannot compensate for its bone
+    // being rotated around `x`.
+    let mut limited = bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Xposition Yposition Zposition
+            JOINT Tip
+            {
+                OFFSET 0.0 1.0 0.0
+                CHANNELS 1 Zrotation
+                End Site
+                {
+                    OFFSET 0.0 5.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.1
+        0.0 0.0 0.0 10.0
+    };
+    let before = limited.clone();
+    assert_eq!(
+        limited.set_bone_directions(&[("Tip", [0.0, 0.0, 1.0].into())]),
+        Err(RestPoseError::PoseNotPreserved {
+            name: "Tip".into(),
+            frame: 0,
+        })
+    );
+    assert_eq!(limited, before);
+}
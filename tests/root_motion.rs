@@ -0,0 +1,135 @@
+use bvh_anim::{
+    bvh,
+    errors::RootMotionError,
+    root_motion::{RootMotion, RootMotionOptions},
+    Axis, Bvh, ChannelType,
+};
+
+/// A root which walks forwards while turning, leaning by `lean` degrees
+/// around the `x` axis.
+fn walk(lean: f32) -> Bvh {
+    let mut bvh = bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+            JOINT Head
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 3 Zrotation Xrotation Yrotation
+                End Site
+                {
+                    OFFSET 0.0 5.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 4
+        Frame Time: 0.033333333
+        0.0 90.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+        1.0 91.0 2.0 0.0 0.0 120.0 0.0 0.0 0.0
+        2.0 90.0 4.0 0.0 0.0 179.0 0.0 0.0 0.0
+        3.0 89.0 6.0 0.0 0.0 -178.0 0.0 0.0 0.0
+    };
+    for frame in bvh.frames_mut() {
+        frame.as_mut_slice()[4] = lean;
+    }
+    bvh
+}
+
+#[test]
+fn extract_root_motion() {
+    let bvh = walk(0.0);
+    let root_motion = RootMotionOptions::new().extract(&bvh);
+
+    assert_eq!(root_motion.len(), 4);
+    assert_eq!(root_motion.up_axis(), Axis::Y);
+    assert_eq!(root_motion.translations()[3], [3.0, 0.0, 6.0].into());
+
+    let expected = [0.0, 120.0, 179.0, 182.0];
+    for (yaw, expected) in root_motion.yaws().iter().zip(&expected) {
+        assert!((yaw - expected).abs() < 1.0e-2, "{} != {}", yaw, expected);
+    }
+
+    let without_yaw = RootMotionOptions::new().with_yaw(false).extract(&bvh);
+    assert!(without_yaw.yaws().iter().all(|&yaw| yaw == 0.0));
+}
+
+#[test]
+fn in_place_round_trip() {
+    let original = walk(10.0);
+    let mut bvh = original.clone();
+    let root_motion = RootMotionOptions::new().in_place(&mut bvh);
+
+    for frame in 0..bvh.num_frames() {
+        let positions = bvh.world_positions(frame).unwrap();
+        assert!(positions[0].x.abs() < 1.0e-4);
+        assert!(positions[0].z.abs() < 1.0e-4);
+
+        // The remaining root rotation has no heading, so the forward axis
+        // of the root points along `z` on the ground.
+        let transforms = bvh.world_transforms(frame).unwrap();
+        assert!(transforms[0].z.x.abs() < 1.0e-4);
+        assert!(transforms[0].z.z > 0.0);
+    }
+
+    root_motion.apply(&mut bvh).unwrap();
+    for frame in 0..bvh.num_frames() {
+        let expected = original.world_positions(frame).unwrap();
+        let actual = bvh.world_positions(frame).unwrap();
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a.x - e.x).abs() < 1.0e-3);
+            assert!((a.y - e.y).abs() < 1.0e-3);
+            assert!((a.z - e.z).abs() < 1.0e-3);
+        }
+    }
+}
+
+#[test]
+fn apply_errors() {
+    let mut bvh = walk(0.0);
+    let mut short = RootMotion::new(Axis::Y);
+    short.push([1.0, 0.0, 0.0].into(), 0.0);
+    assert_eq!(
+        short.apply(&mut bvh),
+        Err(RootMotionError::FrameCountMismatch {
+            expected: 4,
+            found: 1
+        })
+    );
+
+    let mut no_position = bvh! {
+        HIERARCHY
+        ROOT Hips
+        {
+            OFFSET 0.0 0.0 0.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            JOINT Head
+            {
+                OFFSET 0.0 10.0 0.0
+                CHANNELS 0
+                End Site
+                {
+                    OFFSET 0.0 5.0 0.0
+                }
+            }
+        }
+        MOTION
+        Frames: 1
+        Frame Time: 0.033333333
+        0.0 0.0 0.0
+    };
+    assert_eq!(
+        short.apply(&mut no_position),
+        Err(RootMotionError::MissingChannel {
+            channel_type: ChannelType::PositionX
+        })
+    );
+
+    let mut yaw_only = RootMotion::new(Axis::Y);
+    yaw_only.push([0.0, 5.0, 0.0].into(), 90.0);
+    yaw_only.apply(&mut no_position).unwrap();
+    assert_eq!(yaw_only.translations()[0], [0.0, 0.0, 0.0].into());
+    assert!((no_position.frames().next().unwrap().as_slice()[2] - 90.0).abs() < 1.0e-3);
+}
@@ -0,0 +1,115 @@
+use bvh_anim::{
+    validate::{DiagnosticKind, Severity, ValidateOptions},
+    Axis, Bvh, ChannelType,
+};
+use pretty_assertions::assert_eq;
+use smallvec::smallvec;
+use std::time::Duration;
+
+#[test]
+fn validate_clean_file() {
+    let bvh = Bvh::from_bytes(&include_bytes!("../data/test_simple.bvh")[..]).unwrap();
+    assert_eq!(bvh.validate(), vec![]);
+}
+
+#[test]
+fn validate_skeleton() {
+    const BVH_STRING: &str = "\
+HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Base
+    {
+        OFFSET 0.0 0.0 0.0
+        CHANNELS 4 Xposition Zrotation Zrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 0.0 30.0
+        }
+    }
+    JOINT Leaf
+    {
+        OFFSET 0.0 1.0 0.0
+        CHANNELS 3 Xrotation Zrotation Yrotation
+        End Site
+        {
+            OFFSET 0.0 1.0 0.0
+        }
+    }
+}
+MOTION
+Frames: 2
+Frame Time: 0.033333333
+0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+";
+
+    let bvh = Bvh::from_bytes(BVH_STRING).unwrap();
+
+    let found = bvh
+        .validate()
+        .into_iter()
+        .map(|d| (d.joint(), d.kind().clone()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        found,
+        vec![
+            (
+                Some(1),
+                DiagnosticKind::DuplicateJointName { first_index: 0 }
+            ),
+            (
+                Some(1),
+                DiagnosticKind::PositionChannelOnChild {
+                    channel_type: ChannelType::PositionX
+                }
+            ),
+            (
+                Some(1),
+                DiagnosticKind::RepeatedChannel {
+                    channel_type: ChannelType::RotationZ
+                }
+            ),
+            (Some(1), DiagnosticKind::ZeroLengthBone),
+            (
+                Some(2),
+                DiagnosticKind::InconsistentRotationOrder {
+                    expected: smallvec![Axis::Z, Axis::X, Axis::Y],
+                    found: smallvec![Axis::X, Axis::Z, Axis::Y],
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn validate_motion() {
+    let mut bvh = Bvh::from_bytes(&include_bytes!("../data/test_simple.bvh")[..]).unwrap();
+    for frame in bvh.frames_mut() {
+        frame.as_mut_slice()[7] = f32::NAN;
+    }
+    bvh.set_frame_time(Duration::from_secs(0));
+
+    let diagnostics = bvh.validate();
+    assert_eq!(diagnostics.len(), 2);
+
+    assert_eq!(diagnostics[0].severity(), Severity::Error);
+    assert_eq!(diagnostics[0].joint(), Some(1));
+    assert_eq!(
+        diagnostics[0].kind(),
+        &DiagnosticKind::NonFiniteMotion {
+            motion_index: 7,
+            first_frame: 0,
+            num_frames: bvh.num_frames(),
+        }
+    );
+
+    assert_eq!(diagnostics[1].severity(), Severity::Warning);
+    assert_eq!(diagnostics[1].joint(), None);
+
+    let lenient = ValidateOptions::new().with_min_frame_time(Duration::from_secs(0));
+    assert_eq!(lenient.validate(&bvh).len(), 1);
+}
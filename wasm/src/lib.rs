@@ -0,0 +1,273 @@
+//! WebAssembly bindings for the `bvh_anim` crate.
+//!
+//! The package can be built for the browser or for node with
+//! [`wasm-pack`](https://github.com/rustwasm/wasm-pack):
+//!
+//! ```text
+//! cd wasm
+//! wasm-pack build --target web
+//! ```
+//!
+//! ```javascript
+//! import init, { Bvh } from "./pkg/bvh_anim_wasm.js";
+//!
+//! await init();
+//!
+//! const bytes = new Uint8Array(await (await fetch("anim.bvh")).arrayBuffer());
+//! const bvh = Bvh.fromBytes(bytes);
+//!
+//! for (const joint of bvh.joints()) {
+//!     console.log(joint.name, joint.parent, joint.offset);
+//! }
+//!
+//! const frame = bvh.frame(0);                // Float32Array of numChannels values
+//! const positions = bvh.worldPositions(0);   // Float32Array of numJoints * 3 values
+//! const text = bvh.toString();
+//! ```
+
+use bvh_anim::{Bvh, Joint};
+use js_sys::Float32Array;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+
+/// A bvh file, containing a skeleton and its motion.
+#[wasm_bindgen(js_name = Bvh)]
+pub struct WasmBvh {
+    bvh: Bvh,
+}
+
+/// A snapshot of a single joint of the skeleton of a `Bvh`.
+#[wasm_bindgen]
+pub struct JointInfo {
+    name: String,
+    index: usize,
+    parent: Option<usize>,
+    depth: usize,
+    offset: [f32; 3],
+    end_site: Option<[f32; 3]>,
+    channels: Vec<String>,
+    motion_indices: Vec<u32>,
+}
+
+#[wasm_bindgen(js_class = Bvh)]
+impl WasmBvh {
+    /// Parse a bvh file from the bytes in `bytes`.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmBvh, JsError> {
+        Bvh::from_bytes(bytes)
+            .map(|bvh| WasmBvh { bvh })
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Parse a bvh file from the string `string`.
+    #[wasm_bindgen(js_name = fromString)]
+    pub fn from_string(string: &str) -> Result<WasmBvh, JsError> {
+        WasmBvh::from_bytes(string.as_bytes())
+    }
+
+    /// The number of frames of motion.
+    #[wasm_bindgen(getter, js_name = numFrames)]
+    pub fn num_frames(&self) -> usize {
+        self.bvh.num_frames()
+    }
+
+    /// The number of channels in each frame of motion.
+    #[wasm_bindgen(getter, js_name = numChannels)]
+    pub fn num_channels(&self) -> usize {
+        self.bvh.num_channels()
+    }
+
+    /// The number of joints in the skeleton.
+    #[wasm_bindgen(getter, js_name = numJoints)]
+    pub fn num_joints(&self) -> usize {
+        self.bvh.joints().count()
+    }
+
+    /// The time of each frame, in seconds.
+    #[wasm_bindgen(getter, js_name = frameTime)]
+    pub fn frame_time(&self) -> f64 {
+        self.bvh.frame_time().as_secs_f64()
+    }
+
+    /// All of the joints of the skeleton, in the order in which they appear
+    /// in the file.
+    pub fn joints(&self) -> Vec<JointInfo> {
+        self.bvh
+            .joints()
+            .map(|joint| JointInfo::new(&joint))
+            .collect()
+    }
+
+    /// Find the joint called `name`.
+    #[wasm_bindgen(js_name = findJoint)]
+    pub fn find_joint(&self, name: &str) -> Option<JointInfo> {
+        self.bvh
+            .joints()
+            .find_by_name(name)
+            .map(|joint| JointInfo::new(&joint))
+    }
+
+    /// A copy of the motion values of `frame`, with one value per channel.
+    pub fn frame(&self, frame: usize) -> Result<Float32Array, JsError> {
+        self.bvh
+            .frames()
+            .nth(frame)
+            .map(|frame| Float32Array::from(frame.as_slice()))
+            .ok_or_else(|| frame_out_of_bounds(frame))
+    }
+
+    /// A copy of all of the motion values, stored frame by frame in an array
+    /// of `numFrames * numChannels` values.
+    pub fn motion(&self) -> Float32Array {
+        let values = self
+            .bvh
+            .frames()
+            .flat_map(|frame| frame.as_slice().to_vec())
+            .collect::<Vec<_>>();
+        Float32Array::from(&values[..])
+    }
+
+    /// Replace the motion values of `frame` with `values`, which must contain
+    /// one value per channel.
+    #[wasm_bindgen(js_name = setFrame)]
+    pub fn set_frame(&mut self, frame: usize, values: &[f32]) -> Result<(), JsError> {
+        let num_channels = self.bvh.num_channels();
+        if values.len() != num_channels {
+            return Err(JsError::new(&format!(
+                "Expected {} motion values, but got {}",
+                num_channels,
+                values.len()
+            )));
+        }
+
+        let target = self
+            .bvh
+            .frames_mut()
+            .nth(frame)
+            .ok_or_else(|| frame_out_of_bounds(frame))?;
+        target.as_mut_slice().copy_from_slice(values);
+        Ok(())
+    }
+
+    /// The world-space positions of each joint at `frame`, as an array of
+    /// `numJoints * 3` values.
+    #[wasm_bindgen(js_name = worldPositions)]
+    pub fn world_positions(&self, frame: usize) -> Result<Float32Array, JsError> {
+        self.bvh
+            .world_positions(frame)
+            .map(|positions| positions_to_array(&positions))
+            .ok_or_else(|| frame_out_of_bounds(frame))
+    }
+
+    /// The world-space positions of each joint at `time` seconds from the
+    /// start of the animation, interpolating between frames.
+    #[wasm_bindgen(js_name = worldPositionsAtTime)]
+    pub fn world_positions_at_time(&self, time: f64) -> Result<Float32Array, JsError> {
+        let time = Duration::from_nanos((time.max(0.0) * 1.0e9) as u64);
+        self.bvh
+            .world_positions_at_time(time)
+            .map(|positions| positions_to_array(&positions))
+            .ok_or_else(|| JsError::new("The bvh has no frames"))
+    }
+
+    /// Format the bvh file as bytes.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.bvh.to_bstring().into()
+    }
+
+    /// Format the bvh file as a string.
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.bvh.to_bstring().to_string()
+    }
+}
+
+impl JointInfo {
+    fn new(joint: &Joint<'_>) -> Self {
+        let data = joint.data();
+        let offset = data.offset();
+        JointInfo {
+            name: data.name().to_string(),
+            index: data.index(),
+            parent: data.parent_index(),
+            depth: data.depth(),
+            offset: [offset.x, offset.y, offset.z],
+            end_site: data.end_site().map(|e| [e.x, e.y, e.z]),
+            channels: data
+                .channels()
+                .iter()
+                .map(|c| c.channel_type().as_str().to_string())
+                .collect(),
+            motion_indices: data
+                .channels()
+                .iter()
+                .map(|c| c.motion_index() as u32)
+                .collect(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl JointInfo {
+    /// The name of the joint.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// The index of the joint in the array returned by `Bvh.joints`.
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The index of the parent of the joint, or `undefined` for the root.
+    #[wasm_bindgen(getter)]
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// The depth of the joint, where the root joint has a depth of `0`.
+    #[wasm_bindgen(getter)]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The offset of the joint from its parent, as `[x, y, z]`.
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> Vec<f32> {
+        self.offset.to_vec()
+    }
+
+    /// The end site of the joint as `[x, y, z]`, or `undefined` if it does
+    /// not have one.
+    #[wasm_bindgen(getter, js_name = endSite)]
+    pub fn end_site(&self) -> Option<Vec<f32>> {
+        self.end_site.map(|e| e.to_vec())
+    }
+
+    /// The names of the channels of the joint, such as `"Xrotation"`.
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.clone()
+    }
+
+    /// The index of each channel of the joint within a frame.
+    #[wasm_bindgen(getter, js_name = motionIndices)]
+    pub fn motion_indices(&self) -> Vec<u32> {
+        self.motion_indices.clone()
+    }
+}
+
+fn frame_out_of_bounds(frame: usize) -> JsError {
+    JsError::new(&format!("Frame {} is out of bounds", frame))
+}
+
+fn positions_to_array(positions: &[mint::Vector3<f32>]) -> Float32Array {
+    let values = positions
+        .iter()
+        .flat_map(|p| vec![p.x, p.y, p.z])
+        .collect::<Vec<_>>();
+    Float32Array::from(&values[..])
+}
@@ -0,0 +1,107 @@
+//! Tests for the WebAssembly bindings, which are run under node with
+//! `wasm-pack test --node`.
+
+#![cfg(target_arch = "wasm32")]
+
+use bvh_anim_wasm::WasmBvh as Bvh;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const SIMPLE_SKELETON: &str = "
+HIERARCHY
+ROOT Base
+{
+    OFFSET 0.0 0.0 0.0
+    CHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation
+    JOINT Middle1
+    {
+        OFFSET 0.0 0.0 15.0
+        CHANNELS 3 Zrotation Xrotation Yrotation
+        JOINT Tip1
+        {
+            OFFSET 0.0 0.0 30.0
+            CHANNELS 3 Zrotation Xrotation Yrotation
+            End Site
+            {
+                OFFSET 0.0 0.0 45.0
+            }
+        }
+    }
+}
+MOTION
+Frames: 2
+Frame Time: 0.033333333333
+0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0 0.0
+1.0 2.0 3.0 4.0 5.0 6.0 7.0 8.0 9.0 10.0 11.0 12.0
+";
+
+#[wasm_bindgen_test]
+fn from_bytes() {
+    let bvh = Bvh::from_bytes(SIMPLE_SKELETON.as_bytes()).unwrap();
+    assert_eq!(bvh.num_frames(), 2);
+    assert_eq!(bvh.num_channels(), 12);
+    assert_eq!(bvh.num_joints(), 3);
+    assert!((bvh.frame_time() - 0.033333333).abs() < 1.0e-6);
+
+    assert!(Bvh::from_bytes(b"HIERARCHY\nMOTION\n").is_err());
+}
+
+#[wasm_bindgen_test]
+fn joints() {
+    let bvh = Bvh::from_string(SIMPLE_SKELETON).unwrap();
+    let joints = bvh.joints();
+
+    let names = joints.iter().map(|j| j.name()).collect::<Vec<_>>();
+    assert_eq!(names, ["Base", "Middle1", "Tip1"]);
+
+    let tip = bvh.find_joint("Tip1").unwrap();
+    assert_eq!(tip.index(), 2);
+    assert_eq!(tip.parent(), Some(1));
+    assert_eq!(tip.depth(), 2);
+    assert_eq!(tip.offset(), [0.0, 0.0, 30.0]);
+    assert_eq!(tip.end_site(), Some(vec![0.0, 0.0, 45.0]));
+    assert_eq!(tip.channels(), ["Zrotation", "Xrotation", "Yrotation"]);
+    assert_eq!(tip.motion_indices(), [9, 10, 11]);
+
+    assert!(bvh.find_joint("Missing").is_none());
+}
+
+#[wasm_bindgen_test]
+fn frames() {
+    let mut bvh = Bvh::from_string(SIMPLE_SKELETON).unwrap();
+
+    let frame = bvh.frame(1).unwrap().to_vec();
+    assert_eq!(frame.len(), 12);
+    assert_eq!(frame[0], 1.0);
+    assert_eq!(frame[11], 12.0);
+    assert!(bvh.frame(2).is_err());
+
+    assert_eq!(bvh.motion().length(), 24);
+
+    bvh.set_frame(0, &[5.0; 12]).unwrap();
+    assert_eq!(bvh.frame(0).unwrap().to_vec(), vec![5.0; 12]);
+    assert!(bvh.set_frame(0, &[5.0; 3]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn world_positions() {
+    let bvh = Bvh::from_string(SIMPLE_SKELETON).unwrap();
+
+    let positions = bvh.world_positions(0).unwrap().to_vec();
+    assert_eq!(positions, [0.0, 0.0, 0.0, 0.0, 0.0, 15.0, 0.0, 0.0, 45.0]);
+    assert!(bvh.world_positions(2).is_err());
+
+    let at_time = bvh.world_positions_at_time(0.0).unwrap().to_vec();
+    assert_eq!(at_time, positions);
+}
+
+#[wasm_bindgen_test]
+fn to_bstring() {
+    let bvh = Bvh::from_string(SIMPLE_SKELETON).unwrap();
+    let string = bvh.to_js_string();
+    assert!(string.starts_with("HIERARCHY"));
+    assert_eq!(string.as_bytes(), &bvh.to_bytes()[..]);
+
+    let reparsed = Bvh::from_bytes(&bvh.to_bytes()).unwrap();
+    assert_eq!(reparsed.num_joints(), bvh.num_joints());
+    assert_eq!(reparsed.motion().to_vec(), bvh.motion().to_vec());
+}